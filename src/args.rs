@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Minimal `viz --key=value --switch` command line parsing
+#[derive(Debug, Default, Clone)]
+pub struct Args {
+    pub positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    pub fn from_env() -> Self {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Self {
+        let mut positional = vec![];
+        let mut options = HashMap::new();
+
+        for arg in args {
+            if let Some(option) = arg.strip_prefix("--") {
+                let (key, value) = option.split_once('=').unwrap_or((option, ""));
+                options.insert(key.to_string(), value.to_string());
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        Self {
+            positional,
+            options,
+        }
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.options.contains_key(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options
+            .get(key)
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    }
+
+    pub fn value<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).map(|v| {
+            v.parse()
+                .unwrap_or_else(|_| panic!("Invalid value for --{key}: {v}"))
+        })
+    }
+}
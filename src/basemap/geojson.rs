@@ -0,0 +1,79 @@
+use ::geojson::Feature as GeoFeature;
+use ::geojson::GeoJson;
+use ::geojson::Value;
+use std::error::Error;
+use std::path::Path;
+
+use super::Basemap;
+use super::Feature;
+use super::Kind;
+use super::ROAD_CLASSES;
+
+fn property<'a>(feature: &'a GeoFeature, key: &str) -> Option<&'a str> {
+    feature.property(key).and_then(|v| v.as_str())
+}
+
+// Same tags as the OSM loader, plus an explicit `layer` of water, land, waterway, boundary or road
+fn classify(feature: &GeoFeature) -> Option<Kind> {
+    let highway = property(feature, "highway");
+
+    if let Some(class) = ROAD_CLASSES.iter().position(|c| highway == Some(c)) {
+        return Some(Kind::Road(class));
+    }
+
+    match (
+        property(feature, "layer"),
+        property(feature, "natural"),
+        property(feature, "waterway"),
+        property(feature, "boundary"),
+    ) {
+        (Some("water"), ..) | (_, Some("water"), ..) | (_, _, Some("riverbank"), _) => {
+            Some(Kind::Water)
+        }
+        (Some("land"), ..) => Some(Kind::Land),
+        (Some("waterway"), ..) | (_, _, Some("river" | "canal"), _) => Some(Kind::Waterway),
+        (Some("boundary"), ..) | (.., Some("administrative")) => Some(Kind::Boundary),
+        (Some("road"), ..) => Some(Kind::Road(ROAD_CLASSES.len() - 1)),
+        _ => None,
+    }
+}
+
+fn ring(positions: &[Vec<f64>]) -> Vec<(f64, f64)> {
+    positions.iter().map(|p| (p[0], p[1])).collect()
+}
+
+pub fn load(path: &Path) -> Result<Basemap, Box<dyn Error>> {
+    let raw = std::fs::read_to_string(path)?;
+
+    let collection = match raw.parse::<GeoJson>()? {
+        GeoJson::FeatureCollection(collection) => collection.features,
+        GeoJson::Feature(feature) => vec![feature],
+        GeoJson::Geometry(_) => return Err("Expected GeoJSON features".into()),
+    };
+
+    let features = collection
+        .iter()
+        .filter_map(|feature| {
+            let kind = classify(feature)?;
+            let geometry = feature.geometry.as_ref()?;
+
+            let parts = match &geometry.value {
+                Value::LineString(line) => vec![ring(line)],
+                Value::MultiLineString(lines) | Value::Polygon(lines) => {
+                    lines.iter().map(|l| ring(l)).collect()
+                }
+                Value::MultiPolygon(polygons) => {
+                    polygons.iter().flatten().map(|l| ring(l)).collect()
+                }
+                _ => return None,
+            };
+
+            Some(Feature { kind, parts })
+        })
+        .collect();
+
+    Ok(Basemap {
+        features,
+        tiles: None,
+    })
+}
@@ -0,0 +1,99 @@
+use nannou::image::DynamicImage;
+use nannou::wgpu::Texture;
+use nannou::App;
+use nannou::Draw;
+
+use super::Basemap;
+use crate::theme::Theme;
+use crate::utils::camera::Camera;
+use crate::utils::meta::Meta;
+use crate::utils::raster::Canvas;
+
+// The cached image covers this many window sizes, so small pans don't re-render
+const OVERSCAN: f64 = 2.;
+// Zoom ratio to the cached image beyond which it gets too blurry or too coarse
+const MAX_SCALE: f64 = 2.;
+
+// Pre-rendered basemap, re-rendered only when the feed extent, the theme or
+// the camera move too far from what the cached image covers
+pub struct BasemapLayer {
+    basemap: Basemap,
+    cache: Option<Cache>,
+}
+
+struct Cache {
+    key: String,
+    camera: Camera,
+    size: (f64, f64),
+    texture: Texture,
+}
+
+fn cache_key(meta: &Meta, theme: &Theme, opacity: f32) -> String {
+    let b = &meta.boundaries;
+    format!(
+        "{} {} {} {} {} {}",
+        theme.name, opacity, b.min_lng, b.max_lng, b.min_lat, b.max_lat
+    )
+}
+
+impl BasemapLayer {
+    pub fn new(basemap: Basemap) -> Self {
+        Self {
+            basemap,
+            cache: None,
+        }
+    }
+
+    fn is_fresh(&self, meta: &Meta, camera: &Camera, theme: &Theme, opacity: f32) -> bool {
+        let Some(cache) = &self.cache else {
+            return false;
+        };
+
+        let scale = camera.zoom / cache.camera.zoom;
+        // Offset of the cached image's center from the window's center, in pixels
+        let (dx, dy) = (
+            (cache.camera.x - camera.x) * camera.zoom,
+            (cache.camera.y - camera.y) * camera.zoom,
+        );
+        let (w, h) = (cache.size.0 * scale, cache.size.1 * scale);
+
+        cache.key == cache_key(meta, theme, opacity)
+            && (1. / MAX_SCALE..=MAX_SCALE).contains(&scale)
+            && dx.abs() + meta.width / 2. <= w / 2.
+            && dy.abs() + meta.height / 2. <= h / 2.
+    }
+
+    pub fn update(&mut self, app: &App, meta: &Meta, camera: &Camera, theme: &Theme, opacity: f32) {
+        if self.is_fresh(meta, camera, theme, opacity) {
+            return;
+        }
+
+        let size = (meta.width * OVERSCAN, meta.height * OVERSCAN);
+        let mut canvas = Canvas::transparent(size.0 as u32, size.1 as u32);
+        self.basemap.render(&mut canvas, meta, camera, theme);
+        canvas.fade(opacity);
+
+        let image = DynamicImage::from(canvas);
+
+        self.cache = Some(Cache {
+            key: cache_key(meta, theme, opacity),
+            camera: *camera,
+            size,
+            texture: Texture::from_image(app, &image),
+        });
+    }
+
+    pub fn draw(&self, draw: &Draw, camera: &Camera) {
+        if let Some(cache) = &self.cache {
+            let scale = camera.zoom / cache.camera.zoom;
+            let (x, y) = (
+                (cache.camera.x - camera.x) * camera.zoom,
+                (cache.camera.y - camera.y) * camera.zoom,
+            );
+
+            draw.texture(&cache.texture)
+                .x_y(x as f32, y as f32)
+                .w_h((cache.size.0 * scale) as f32, (cache.size.1 * scale) as f32);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use flate2::read::GzDecoder;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+use std::collections::HashMap;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::io::Read;
+use std::path::Path;
+
+use super::mvt;
+use super::mvt::GeomType;
+use super::mvt::MvtFeature;
+use super::Feature;
+use super::Kind;
+use super::ROAD_CLASSES;
+
+const TILE_SIZE: f64 = 256.;
+// Drops to a lower zoom rather than decoding more tiles than this for one view
+const MAX_TILES: u32 = 64;
+// Decoded tiles kept across views, the least recently used dropped first
+const CACHED_TILES: usize = 4 * MAX_TILES as usize;
+// Deepest zoom of any tileset, tile numbers past it would overflow
+const MAX_ZOOM: u8 = 22;
+
+// Offline vector tiles from an MBTiles SQLite database, decoded lazily per tile
+#[derive(Debug)]
+pub struct MbTiles {
+    conn: Connection,
+    min_zoom: u8,
+    max_zoom: u8,
+    // Decoded features by tile, with the view count they were last used at
+    tiles: HashMap<(u8, u32, u32), (u64, Vec<Feature>)>,
+    views: u64,
+}
+
+fn lng_to_tile(lng: f64, zoom: u8) -> f64 {
+    (lng + 180.) / 360. * 2f64.powi(zoom as i32)
+}
+
+fn lat_to_tile(lat: f64, zoom: u8) -> f64 {
+    let lat = lat.clamp(-85.05, 85.05).to_radians();
+    (1. - (lat.tan() + 1. / lat.cos()).ln() / PI) / 2. * 2f64.powi(zoom as i32)
+}
+
+fn tile_to_coordinate(x: f64, y: f64, zoom: u8) -> (f64, f64) {
+    let n = 2f64.powi(zoom as i32);
+    let lng = x / n * 360. - 180.;
+    let lat = (PI * (1. - 2. * y / n)).sinh().atan().to_degrees();
+
+    (lng, lat)
+}
+
+// OpenMapTiles and Mapbox Streets layer names
+fn classify(layer: &str, feature: &MvtFeature) -> Option<Kind> {
+    let class = feature.tags.get("class").map(String::as_str);
+    let is_area = feature.geom_type == GeomType::Polygon;
+
+    match layer {
+        "water" if is_area => Some(Kind::Water),
+        "water" | "waterway" => Some(Kind::Waterway),
+        "landuse" | "landcover" | "park" if is_area => Some(Kind::Land),
+        "transportation" | "road" => ROAD_CLASSES
+            .iter()
+            .position(|c| class == Some(c))
+            .map(Kind::Road),
+        "boundary" | "admin" => Some(Kind::Boundary),
+        _ => None,
+    }
+}
+
+impl MbTiles {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        let metadata = |name: &str| -> Option<u8> {
+            conn.query_row("SELECT value FROM metadata WHERE name = ?", [name], |row| {
+                row.get::<_, String>(0)
+            })
+            .ok()
+            .and_then(|v| v.parse().ok())
+        };
+
+        let min_zoom = metadata("minzoom").unwrap_or(0).min(MAX_ZOOM);
+        let max_zoom = metadata("maxzoom").unwrap_or(14).clamp(min_zoom, MAX_ZOOM);
+
+        Ok(Self {
+            conn,
+            min_zoom,
+            max_zoom,
+            tiles: HashMap::new(),
+            views: 0,
+        })
+    }
+
+    // Tile zoom whose resolution matches `px_per_degree`, limited by the tiles in the file
+    // and by `MAX_TILES` across the area
+    fn zoom_for(
+        &self,
+        (min_lng, min_lat, max_lng, max_lat): (f64, f64, f64, f64),
+        px_per_degree: f64,
+    ) -> u8 {
+        let ideal = (px_per_degree * 360. / TILE_SIZE).log2().round().max(0.) as u8;
+        let mut zoom = ideal.clamp(self.min_zoom, self.max_zoom);
+
+        while zoom > self.min_zoom {
+            let columns =
+                lng_to_tile(max_lng, zoom).floor() - lng_to_tile(min_lng, zoom).floor() + 1.;
+            let rows = lat_to_tile(min_lat, zoom).floor() - lat_to_tile(max_lat, zoom).floor() + 1.;
+
+            if (columns * rows) as u32 <= MAX_TILES {
+                break;
+            }
+
+            zoom -= 1;
+        }
+
+        zoom
+    }
+
+    fn read_tile(&self, zoom: u8, x: u32, y: u32) -> Result<Vec<Feature>, Box<dyn Error>> {
+        // MBTiles rows follow TMS, counting from the bottom
+        let row = (1u32 << zoom) - 1 - y;
+
+        let data = self.conn.query_row(
+            "SELECT tile_data FROM tiles WHERE zoom_level = ? AND tile_column = ? AND tile_row = ?",
+            [zoom as u32, x, row],
+            |row| row.get::<_, Vec<u8>>(0),
+        );
+
+        let data = match data {
+            Ok(data) => data,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let data = if data.starts_with(&[0x1f, 0x8b]) {
+            let mut inflated = vec![];
+            GzDecoder::new(data.as_slice()).read_to_end(&mut inflated)?;
+            inflated
+        } else {
+            data
+        };
+
+        let mut features = vec![];
+
+        for layer in mvt::decode(&data)? {
+            let extent = layer.extent as f64;
+
+            for feature in layer.features.iter() {
+                if let Some(kind) = classify(&layer.name, feature) {
+                    let parts = feature
+                        .parts
+                        .iter()
+                        .map(|part| {
+                            part.iter()
+                                .map(|(gx, gy)| {
+                                    tile_to_coordinate(
+                                        x as f64 + gx / extent,
+                                        y as f64 + gy / extent,
+                                        zoom,
+                                    )
+                                })
+                                .collect()
+                        })
+                        .collect();
+
+                    features.push(Feature { kind, parts });
+                }
+            }
+        }
+
+        Ok(features)
+    }
+
+    // Features of every tile covering the (min_lng, min_lat, max_lng, max_lat) area
+    pub fn features(&mut self, area: (f64, f64, f64, f64), px_per_degree: f64) -> Vec<&Feature> {
+        let zoom = self.zoom_for(area, px_per_degree);
+        let (min_lng, min_lat, max_lng, max_lat) = area;
+        let last = (1u32 << zoom) - 1;
+
+        let columns = lng_to_tile(min_lng, zoom).max(0.) as u32
+            ..=(lng_to_tile(max_lng, zoom) as u32).min(last);
+        let rows = lat_to_tile(max_lat, zoom).max(0.) as u32
+            ..=(lat_to_tile(min_lat, zoom) as u32).min(last);
+
+        let keys = columns
+            .flat_map(|x| rows.clone().map(move |y| (zoom, x, y)))
+            .collect::<Vec<_>>();
+
+        self.views += 1;
+
+        for key in keys.iter() {
+            match self.tiles.get_mut(key) {
+                Some((used, _)) => *used = self.views,
+                None => {
+                    let (zoom, x, y) = *key;
+                    let features = self.read_tile(zoom, x, y).unwrap_or_else(|e| {
+                        eprintln!("Couldn't read tile {zoom}/{x}/{y}: {e}");
+                        vec![]
+                    });
+
+                    self.tiles.insert(*key, (self.views, features));
+                }
+            }
+        }
+
+        // The view's own tiles were just used, so they're never the ones dropped
+        while self.tiles.len() > CACHED_TILES.max(keys.len()) {
+            let oldest = *self
+                .tiles
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key)
+                .unwrap();
+            self.tiles.remove(&oldest);
+        }
+
+        keys.iter()
+            .flat_map(|key| self.tiles[key].1.iter())
+            .collect()
+    }
+}
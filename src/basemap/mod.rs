@@ -0,0 +1,163 @@
+pub mod geojson;
+pub mod layer;
+pub mod mbtiles;
+pub mod mvt;
+pub mod osm;
+
+use std::error::Error;
+use std::path::Path;
+
+use mbtiles::MbTiles;
+
+use crate::theme::Theme;
+use crate::utils::camera::Camera;
+use crate::utils::math::coordinate_to_xy;
+use crate::utils::math::xy_to_coordinate;
+use crate::utils::meta::Meta;
+use crate::utils::raster::Canvas;
+
+// Highway values drawn on the basemap, widest first
+pub const ROAD_CLASSES: [&str; 5] = ["motorway", "trunk", "primary", "secondary", "tertiary"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    // Filled areas such as the Danube and lakes
+    Water,
+    // Parks, forests and other landuse areas
+    Land,
+    // Rivers and canals drawn as lines
+    Waterway,
+    // District boundaries
+    Boundary,
+    // Index into ROAD_CLASSES
+    Road(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub kind: Kind,
+    // (lng, lat) rings of an area or parts of a line
+    pub parts: Vec<Vec<(f64, f64)>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Basemap {
+    pub features: Vec<Feature>,
+    // Vector tiles are read per view instead of all at once
+    pub tiles: Option<MbTiles>,
+}
+
+impl Basemap {
+    // A `.osm.pbf` extract, an MBTiles database or a GeoJSON file, picked by extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        eprintln!("Loading basemap from {}...", path.display());
+
+        let name = path.to_string_lossy();
+
+        if name.ends_with(".pbf") {
+            osm::load(path)
+        } else if name.ends_with(".mbtiles") {
+            Ok(Self {
+                features: vec![],
+                tiles: Some(MbTiles::open(path)?),
+            })
+        } else if name.ends_with(".geojson") || name.ends_with(".json") {
+            geojson::load(path)
+        } else {
+            Err(format!("Unsupported basemap format: {name}").into())
+        }
+    }
+
+    // Draws every feature onto a transparent canvas, areas first and roads last
+    pub fn render(&mut self, canvas: &mut Canvas, meta: &Meta, camera: &Camera, theme: &Theme) {
+        let mut features = self.features.iter().collect::<Vec<_>>();
+
+        if let Some(tiles) = &mut self.tiles {
+            let (w, h) = (canvas.width as f64 / 2., canvas.height as f64 / 2.);
+            let (min_lng, min_lat) = xy_to_coordinate(-w, -h, meta, camera);
+            let (max_lng, max_lat) = xy_to_coordinate(w, h, meta, camera);
+            let px_per_degree = canvas.width as f64 / (max_lng - min_lng);
+
+            features.extend(tiles.features((min_lng, min_lat, max_lng, max_lat), px_per_degree));
+        }
+
+        let project = |part: &Vec<(f64, f64)>| {
+            part.iter()
+                .map(|(lng, lat)| {
+                    let (x, y) = coordinate_to_xy(*lng, *lat, meta, camera);
+                    (x as f32, y as f32)
+                })
+                .collect::<Vec<(f32, f32)>>()
+        };
+
+        let zoom = (camera.zoom as f32).sqrt();
+        let of_kind = |kind: Kind| features.iter().filter(move |f| f.kind == kind);
+
+        for (kind, color) in [(Kind::Land, theme.land), (Kind::Water, theme.water)] {
+            for feature in of_kind(kind) {
+                let rings = feature.parts.iter().map(project).collect::<Vec<_>>();
+                canvas.polygon_rings(&rings, color);
+            }
+        }
+
+        for feature in of_kind(Kind::Waterway) {
+            for part in feature.parts.iter() {
+                canvas.polyline(&project(part), 2. * zoom, theme.water);
+            }
+        }
+
+        for feature in of_kind(Kind::Boundary) {
+            for part in feature.parts.iter() {
+                canvas.polyline(&project(part), 0.75, theme.boundary);
+            }
+        }
+
+        for class in (0..ROAD_CLASSES.len()).rev() {
+            let weight = (ROAD_CLASSES.len() - class) as f32 * 0.5 * zoom;
+
+            for feature in of_kind(Kind::Road(class)) {
+                for part in feature.parts.iter() {
+                    canvas.polyline(&project(part), weight, theme.road);
+                }
+            }
+        }
+    }
+}
+
+// Joins line parts sharing endpoints into as few rings as possible,
+// areas in OSM are often split across several ways
+pub fn stitch(mut parts: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+    let mut rings = vec![];
+
+    while let Some(mut ring) = parts.pop() {
+        loop {
+            let (first, last) = (ring[0], ring[ring.len() - 1]);
+
+            if first == last && ring.len() > 2 {
+                break;
+            }
+
+            let next = parts
+                .iter()
+                .position(|p| p[0] == last || p[p.len() - 1] == last);
+
+            match next {
+                Some(idx) => {
+                    let mut part = parts.swap_remove(idx);
+
+                    if part[0] != last {
+                        part.reverse();
+                    }
+
+                    ring.extend(part.into_iter().skip(1));
+                }
+                None => break,
+            }
+        }
+
+        rings.push(ring);
+    }
+
+    rings
+}
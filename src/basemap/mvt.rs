@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+// Minimal Mapbox Vector Tile decoder, only what the basemap needs:
+// layer names, feature tags as strings and line/polygon geometry in tile units
+
+const MOVE_TO: u32 = 1;
+const LINE_TO: u32 = 2;
+const CLOSE_PATH: u32 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeomType {
+    Point,
+    LineString,
+    Polygon,
+}
+
+#[derive(Debug)]
+pub struct MvtFeature {
+    pub tags: HashMap<String, String>,
+    pub geom_type: GeomType,
+    // Parts in tile units, (0, 0) top left and `extent` bottom right
+    pub parts: Vec<Vec<(f64, f64)>>,
+}
+
+#[derive(Debug)]
+pub struct MvtLayer {
+    pub name: String,
+    pub extent: u32,
+    pub features: Vec<MvtFeature>,
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn is_done(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = *self.buf.get(self.pos).ok_or("Truncated varint")?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err("Varint too long".into())
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.varint()? as usize;
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or("Truncated field")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn fixed<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + N)
+            .ok_or("Truncated field")?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    // Field number and wire type of the next field
+    fn key(&mut self) -> Result<(u32, u8), String> {
+        let key = self.varint()?;
+        Ok(((key >> 3) as u32, (key & 7) as u8))
+    }
+
+    fn skip(&mut self, wire_type: u8) -> Result<(), String> {
+        match wire_type {
+            0 => {
+                self.varint()?;
+            }
+            1 => {
+                self.fixed::<8>()?;
+            }
+            2 => {
+                self.bytes()?;
+            }
+            5 => {
+                self.fixed::<4>()?;
+            }
+            _ => return Err(format!("Unsupported wire type {wire_type}")),
+        }
+
+        Ok(())
+    }
+
+    fn packed(&mut self) -> Result<Vec<u32>, String> {
+        let mut reader = Reader::new(self.bytes()?);
+        let mut values = vec![];
+
+        while !reader.is_done() {
+            values.push(reader.varint()? as u32);
+        }
+
+        Ok(values)
+    }
+}
+
+fn zigzag(value: u32) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn decode_value(buf: &[u8]) -> Result<String, String> {
+    let mut reader = Reader::new(buf);
+    let mut value = String::new();
+
+    while !reader.is_done() {
+        match reader.key()? {
+            (1, 2) => value = String::from_utf8_lossy(reader.bytes()?).into_owned(),
+            (2, 5) => value = f32::from_le_bytes(reader.fixed()?).to_string(),
+            (3, 1) => value = f64::from_le_bytes(reader.fixed()?).to_string(),
+            (4 | 5, 0) => value = reader.varint()?.to_string(),
+            (6, 0) => value = zigzag(reader.varint()? as u32).to_string(),
+            (7, 0) => value = (reader.varint()? != 0).to_string(),
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+
+    Ok(value)
+}
+
+fn decode_geometry(commands: &[u32]) -> Vec<Vec<(f64, f64)>> {
+    let mut parts: Vec<Vec<(f64, f64)>> = vec![];
+    let (mut x, mut y) = (0, 0);
+    let mut idx = 0;
+
+    while idx < commands.len() {
+        let (id, count) = (commands[idx] & 7, commands[idx] >> 3);
+        idx += 1;
+
+        match id {
+            MOVE_TO | LINE_TO => {
+                for _ in 0..count {
+                    if idx + 1 >= commands.len() {
+                        return parts;
+                    }
+
+                    x += zigzag(commands[idx]);
+                    y += zigzag(commands[idx + 1]);
+                    idx += 2;
+
+                    if id == MOVE_TO {
+                        parts.push(vec![]);
+                    }
+
+                    if let Some(part) = parts.last_mut() {
+                        part.push((x as f64, y as f64));
+                    }
+                }
+            }
+            CLOSE_PATH => {
+                if let Some(part) = parts.last_mut() {
+                    part.push(part[0]);
+                }
+            }
+            _ => return parts,
+        }
+    }
+
+    parts
+}
+
+fn decode_feature(buf: &[u8], keys: &[String], values: &[String]) -> Result<MvtFeature, String> {
+    let mut reader = Reader::new(buf);
+    let mut tags = HashMap::new();
+    let mut geom_type = GeomType::Point;
+    let mut geometry = vec![];
+
+    while !reader.is_done() {
+        match reader.key()? {
+            (2, 2) => {
+                for pair in reader.packed()?.chunks(2).filter(|p| p.len() == 2) {
+                    if let (Some(key), Some(value)) =
+                        (keys.get(pair[0] as usize), values.get(pair[1] as usize))
+                    {
+                        tags.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            (3, 0) => {
+                geom_type = match reader.varint()? {
+                    2 => GeomType::LineString,
+                    3 => GeomType::Polygon,
+                    _ => GeomType::Point,
+                }
+            }
+            (4, 2) => geometry = reader.packed()?,
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+
+    Ok(MvtFeature {
+        tags,
+        geom_type,
+        parts: decode_geometry(&geometry),
+    })
+}
+
+fn decode_layer(buf: &[u8]) -> Result<MvtLayer, String> {
+    let mut reader = Reader::new(buf);
+    let mut name = String::new();
+    let mut extent = 4096;
+    let mut keys = vec![];
+    let mut values = vec![];
+    let mut raw_features = vec![];
+
+    // Keys and values may come after the features, so those are decoded last
+    while !reader.is_done() {
+        match reader.key()? {
+            (1, 2) => name = String::from_utf8_lossy(reader.bytes()?).into_owned(),
+            (2, 2) => raw_features.push(reader.bytes()?),
+            (3, 2) => keys.push(String::from_utf8_lossy(reader.bytes()?).into_owned()),
+            (4, 2) => values.push(decode_value(reader.bytes()?)?),
+            (5, 0) => extent = reader.varint()? as u32,
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+
+    let features = raw_features
+        .into_iter()
+        .map(|f| decode_feature(f, &keys, &values))
+        .collect::<Result<_, _>>()?;
+
+    Ok(MvtLayer {
+        name,
+        extent,
+        features,
+    })
+}
+
+pub fn decode(buf: &[u8]) -> Result<Vec<MvtLayer>, String> {
+    let mut reader = Reader::new(buf);
+    let mut layers = vec![];
+
+    while !reader.is_done() {
+        match reader.key()? {
+            (3, 2) => layers.push(decode_layer(reader.bytes()?)?),
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+
+    Ok(layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn uint(number: u32, value: u64) -> Vec<u8> {
+        let mut out = vec![];
+        varint((number << 3) as u64, &mut out);
+        varint(value, &mut out);
+        out
+    }
+
+    fn bytes(number: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        varint(((number << 3) | 2) as u64, &mut out);
+        varint(bytes.len() as u64, &mut out);
+        out.extend_from_slice(bytes);
+        out
+    }
+
+    fn packed(number: u32, values: &[u32]) -> Vec<u8> {
+        let mut buf = vec![];
+        for value in values {
+            varint(*value as u64, &mut buf);
+        }
+        bytes(number, &buf)
+    }
+
+    fn command(id: u32, count: u32) -> u32 {
+        id | (count << 3)
+    }
+
+    fn zigzag_encode(value: i32) -> u32 {
+        ((value << 1) ^ (value >> 31)) as u32
+    }
+
+    #[test]
+    fn line_and_polygon() {
+        let z = zigzag_encode;
+
+        let line = [
+            packed(2, &[0, 0]),
+            uint(3, 2),
+            packed(
+                4,
+                &[
+                    command(MOVE_TO, 1),
+                    z(2),
+                    z(2),
+                    command(LINE_TO, 2),
+                    z(3),
+                    z(0),
+                    z(0),
+                    z(3),
+                ],
+            ),
+        ]
+        .concat();
+        let polygon = [
+            packed(2, &[1, 1]),
+            uint(3, 3),
+            packed(
+                4,
+                &[
+                    command(MOVE_TO, 1),
+                    z(0),
+                    z(0),
+                    command(LINE_TO, 2),
+                    z(10),
+                    z(0),
+                    z(0),
+                    z(-10),
+                    command(CLOSE_PATH, 1),
+                ],
+            ),
+        ]
+        .concat();
+        // Keys and values after the features
+        let layer = [
+            bytes(1, b"water"),
+            bytes(2, &line),
+            bytes(2, &polygon),
+            bytes(3, b"class"),
+            bytes(3, b"rank"),
+            bytes(4, &bytes(1, b"river")),
+            bytes(4, &uint(5, 5)),
+            uint(5, 512),
+        ]
+        .concat();
+        let tile = bytes(3, &layer);
+
+        let layers = decode(&tile).unwrap();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].name, "water");
+        assert_eq!(layers[0].extent, 512);
+
+        let features = &layers[0].features;
+        assert_eq!(features.len(), 2);
+
+        assert_eq!(features[0].geom_type, GeomType::LineString);
+        assert_eq!(features[0].tags["class"], "river");
+        assert!(!features[0].tags.contains_key("rank"));
+        assert_eq!(features[0].parts, vec![vec![(2., 2.), (5., 2.), (5., 5.)]]);
+
+        assert_eq!(features[1].geom_type, GeomType::Polygon);
+        assert_eq!(features[1].tags["rank"], "5");
+        // Closed back to its first point
+        assert_eq!(
+            features[1].parts,
+            vec![vec![(0., 0.), (10., 0.), (10., -10.), (0., 0.)]]
+        );
+    }
+
+    #[test]
+    fn truncated_tile_is_an_error() {
+        let tile = bytes(3, &bytes(1, b"water"));
+        assert!(decode(&tile[..tile.len() - 1]).is_err());
+    }
+}
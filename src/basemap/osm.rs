@@ -0,0 +1,107 @@
+use osmpbfreader::OsmObj;
+use osmpbfreader::OsmPbfReader;
+use osmpbfreader::Tags;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use super::stitch;
+use super::Basemap;
+use super::Feature;
+use super::Kind;
+use super::ROAD_CLASSES;
+
+// Budapest's districts are admin_level 9 in OpenStreetMap
+const DISTRICT_ADMIN_LEVEL: &str = "9";
+
+fn classify(tags: &Tags) -> Option<Kind> {
+    if let Some(class) = ROAD_CLASSES
+        .iter()
+        .position(|c| tags.contains("highway", c))
+    {
+        Some(Kind::Road(class))
+    } else if tags.contains("natural", "water") || tags.contains("waterway", "riverbank") {
+        Some(Kind::Water)
+    } else if tags.contains("leisure", "park") || tags.contains("landuse", "forest") {
+        Some(Kind::Land)
+    } else if tags.contains("waterway", "river") || tags.contains("waterway", "canal") {
+        Some(Kind::Waterway)
+    } else if tags.contains("boundary", "administrative")
+        && tags.contains("admin_level", DISTRICT_ADMIN_LEVEL)
+    {
+        Some(Kind::Boundary)
+    } else {
+        None
+    }
+}
+
+fn wanted(obj: &OsmObj) -> bool {
+    match obj {
+        OsmObj::Way(way) => classify(&way.tags).is_some(),
+        OsmObj::Relation(rel) => classify(&rel.tags).is_some(),
+        OsmObj::Node(_) => false,
+    }
+}
+
+fn way_coords(
+    objs: &BTreeMap<osmpbfreader::OsmId, OsmObj>,
+    way: &osmpbfreader::Way,
+) -> Vec<(f64, f64)> {
+    way.nodes
+        .iter()
+        .filter_map(|id| objs.get(&(*id).into()))
+        .filter_map(OsmObj::node)
+        .map(|node| (node.lon(), node.lat()))
+        .collect()
+}
+
+pub fn load(path: &Path) -> Result<Basemap, Box<dyn Error>> {
+    let mut pbf = OsmPbfReader::new(File::open(path)?);
+    let objs = pbf.get_objs_and_deps(wanted)?;
+
+    let mut features = vec![];
+
+    for obj in objs.values() {
+        match obj {
+            OsmObj::Way(way) => {
+                if let Some(kind) = classify(&way.tags) {
+                    let coords = way_coords(&objs, way);
+
+                    if coords.len() > 1 {
+                        features.push(Feature {
+                            kind,
+                            parts: vec![coords],
+                        });
+                    }
+                }
+            }
+            OsmObj::Relation(rel) => {
+                if let Some(kind) = classify(&rel.tags) {
+                    let parts = rel
+                        .refs
+                        .iter()
+                        .filter_map(|r| objs.get(&r.member))
+                        .filter_map(OsmObj::way)
+                        .map(|way| way_coords(&objs, way))
+                        .filter(|coords| coords.len() > 1)
+                        .collect::<Vec<_>>();
+
+                    let parts = if kind == Kind::Water {
+                        stitch(parts)
+                    } else {
+                        parts
+                    };
+
+                    features.push(Feature { kind, parts });
+                }
+            }
+            OsmObj::Node(_) => (),
+        }
+    }
+
+    Ok(Basemap {
+        features,
+        tiles: None,
+    })
+}
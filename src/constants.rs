@@ -5,3 +5,17 @@ pub const CANVAS_HEIGHT: u32 = 500;
 
 // Offline usage:
 pub const GTFS_URL: &str = "./data";
+
+pub const A4_FREQ: f32 = 440.;
+pub const PIANO_OCTAVES: std::ops::RangeInclusive<i8> = 0..=8;
+
+// Optional override of the generated note table
+pub const NOTES_CSV: &str = "./data/notes.csv";
+
+pub const EXPORT_DIR: &str = "./export";
+pub const RECORD_FPS: u32 = 30;
+
+pub const GIF_FPS: u32 = 20;
+// Playback seconds for every scheduled minute of the trip
+pub const GIF_SECONDS_PER_MINUTE: f32 = 0.25;
+pub const GIF_HOLD_SECONDS: f32 = 2.;
@@ -0,0 +1,18 @@
+pub mod geojson;
+pub mod gif;
+pub mod graph;
+
+use crate::args::Args;
+
+// `bp_viz export <format> ...`
+pub fn run(args: &Args) {
+    match args.positional.get(1).map(String::as_str) {
+        Some("geojson") => geojson::run(args),
+        Some("graph") => graph::run(args),
+        Some(format) => eprintln!("Unknown export format: {format}"),
+        None => {
+            eprintln!("Usage: bp_viz export geojson [--route=] [--date=] [--bbox=] [--out=]");
+            eprintln!("       bp_viz export graph [--format=graphml,dot,csv] [--stations] [--route=] [--date=] [--out=]");
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use ::geojson::Feature;
+use ::geojson::FeatureCollection;
+use ::geojson::Geometry;
+use ::geojson::Value;
+use chrono::NaiveDate;
+use gtfs_structures::Availability;
+use gtfs_structures::Gtfs;
+use gtfs_structures::LocationType;
+use gtfs_structures::Route;
+use gtfs_structures::Stop;
+use gtfs_structures::Trip;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::args::Args;
+use crate::constants::EXPORT_DIR;
+use crate::constants::GTFS_URL;
+use crate::utils::boundaries::Boundaries;
+use crate::utils::feed::load_gtfs;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::route_types::route_type_code;
+use crate::utils::service::parse_date;
+use crate::utils::service::runs_on;
+use crate::vizualizations::stops::start_id;
+use crate::vizualizations::stops::visit_order;
+
+// Which part of the feed gets exported, everything by default
+#[derive(Debug, Default)]
+struct Filter {
+    routes: RouteFilter,
+    date: Option<NaiveDate>,
+    bbox: Option<Boundaries>,
+}
+
+impl Filter {
+    fn from_args(args: &Args) -> Self {
+        let date = args.get("date").map(|date| {
+            parse_date(date).unwrap_or_else(|| panic!("Invalid value for --date: {date}"))
+        });
+
+        Self {
+            routes: RouteFilter::from_args(args),
+            date,
+            bbox: args.value("bbox"),
+        }
+    }
+
+    fn keeps_trip(&self, gtfs: &Gtfs, trip: &Trip) -> bool {
+        let route_ok = self.routes.keeps_trip(gtfs, trip);
+        let date_ok = self
+            .date
+            .map_or(true, |date| runs_on(gtfs, &trip.service_id, date));
+        let bbox_ok = trip.stop_times.iter().any(|st| self.keeps_stop(&st.stop));
+
+        route_ok && date_ok && bbox_ok
+    }
+
+    fn keeps_stop(&self, stop: &Stop) -> bool {
+        match (&self.bbox, stop.longitude, stop.latitude) {
+            (Some(bbox), Some(lng), Some(lat)) => bbox.contains(lng, lat),
+            (Some(_), ..) => false,
+            (None, ..) => true,
+        }
+    }
+
+    // Only stops served by the kept trips, unless trips aren't filtered at all
+    fn filters_trips(&self) -> bool {
+        self.routes.is_active() || self.date.is_some()
+    }
+}
+
+// `bp_viz export geojson [--route=9*,M2 and the other route filters] [--date=YYYYMMDD]
+// [--bbox=min_lng,min_lat,max_lng,max_lat] [--out=dir]`
+pub fn run(args: &Args) {
+    let gtfs = load_gtfs(GTFS_URL, args);
+
+    let filter = Filter::from_args(args);
+    let out = args
+        .get("out")
+        .map(String::from)
+        .unwrap_or_else(|| format!("{EXPORT_DIR}/geojson"));
+
+    export(&gtfs, &filter, Path::new(&out)).expect("Failed to export GeoJSON");
+}
+
+fn export(gtfs: &Gtfs, filter: &Filter, out: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(out)?;
+
+    let mut trips = gtfs
+        .trips
+        .values()
+        .filter(|trip| filter.keeps_trip(gtfs, trip))
+        .collect::<Vec<&Trip>>();
+    trips.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let served = trips
+        .iter()
+        .flat_map(|trip| trip.stop_times.iter().map(|st| st.stop.id.as_str()))
+        .collect::<HashSet<&str>>();
+
+    let stops = visit_order(&gtfs.stops, start_id(&gtfs.stops))
+        .into_iter()
+        .enumerate()
+        .filter(|(_, stop)| filter.keeps_stop(stop))
+        .filter(|(_, stop)| !filter.filters_trips() || served.contains(stop.id.as_str()))
+        .filter_map(|(order, stop)| {
+            let mut feature = point(&stop)?;
+            feature.set_property("order", order);
+            Some(feature)
+        });
+
+    write(out.join("stops.geojson"), stops.collect())?;
+
+    // A shape used by several routes is exported once for each of them
+    let mut shapes = HashMap::new();
+    for trip in trips.iter() {
+        if let Some(shape_id) = &trip.shape_id {
+            shapes.insert((shape_id, &trip.route_id), *trip);
+        }
+    }
+
+    let mut shapes = shapes.into_iter().collect::<Vec<_>>();
+    shapes.sort_by_key(|((shape_id, route_id), _)| (*shape_id, *route_id));
+
+    let shapes = shapes
+        .into_iter()
+        .filter_map(|((shape_id, route_id), _)| {
+            let points = gtfs.shapes.get(shape_id)?;
+            let coords = points
+                .iter()
+                .map(|p| vec![p.longitude, p.latitude])
+                .collect();
+
+            let mut feature = Feature::from(Geometry::new(Value::LineString(coords)));
+            feature.set_property("shape_id", shape_id.as_str());
+            set_route_properties(&mut feature, gtfs.routes.get(route_id)?);
+
+            Some(feature)
+        })
+        .collect();
+
+    write(out.join("shapes.geojson"), shapes)?;
+
+    let trips = trips
+        .iter()
+        .filter_map(|trip| {
+            let coords = trip
+                .stop_times
+                .iter()
+                .filter_map(|st| Some(vec![st.stop.longitude?, st.stop.latitude?]))
+                .collect::<Vec<_>>();
+
+            if coords.len() < 2 {
+                return None;
+            }
+
+            let mut feature = Feature::from(Geometry::new(Value::LineString(coords)));
+            feature.set_property("trip_id", trip.id.as_str());
+            feature.set_property("service_id", trip.service_id.as_str());
+            feature.set_property("headsign", trip.trip_headsign.clone());
+            feature.set_property("shape_id", trip.shape_id.clone());
+            feature.set_property(
+                "departure",
+                trip.stop_times.first().and_then(|st| st.departure_time),
+            );
+            set_route_properties(&mut feature, gtfs.routes.get(&trip.route_id)?);
+
+            Some(feature)
+        })
+        .collect();
+
+    write(out.join("trips.geojson"), trips)?;
+
+    eprintln!("Exported GeoJSON to {}", out.display());
+
+    Ok(())
+}
+
+fn write(path: impl AsRef<Path>, features: Vec<Feature>) -> Result<(), Box<dyn Error>> {
+    eprintln!(
+        "Writing {} features to {}...",
+        features.len(),
+        path.as_ref().display()
+    );
+
+    let collection = FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    };
+
+    fs::write(path, collection.to_string())?;
+
+    Ok(())
+}
+
+// None for stops without coordinates rather than a point at 0, 0
+fn point(stop: &Stop) -> Option<Feature> {
+    let coords = vec![stop.longitude?, stop.latitude?];
+    let mut feature = Feature::from(Geometry::new(Value::Point(coords)));
+
+    let location_type = match stop.location_type {
+        LocationType::StopPoint => "stop",
+        LocationType::StopArea => "station",
+        LocationType::StationEntrance => "entrance",
+        LocationType::GenericNode => "node",
+        LocationType::BoardingArea => "boarding_area",
+        _ => "unknown",
+    };
+
+    let wheelchair = match stop.wheelchair_boarding {
+        Availability::Available => Some(true),
+        Availability::NotAvailable => Some(false),
+        _ => None,
+    };
+
+    feature.set_property("stop_id", stop.id.as_str());
+    feature.set_property("code", stop.code.clone());
+    feature.set_property("name", stop.name.as_str());
+    feature.set_property("description", stop.description.clone());
+    feature.set_property("location_type", location_type);
+    feature.set_property("parent_station", stop.parent_station.clone());
+    feature.set_property("zone_id", stop.zone_id.clone());
+    feature.set_property("platform_code", stop.platform_code.clone());
+    feature.set_property("timezone", stop.timezone.clone());
+    feature.set_property("wheelchair_boarding", wheelchair);
+
+    Some(feature)
+}
+
+fn set_route_properties(feature: &mut Feature, route: &Route) {
+    let [color, text_color] = [route.route_color, route.route_text_color]
+        .map(|c| format!("#{:02X}{:02X}{:02X}", c.r, c.g, c.b));
+
+    feature.set_property("route_id", route.id.as_str());
+    feature.set_property("short_name", route.short_name.as_str());
+    feature.set_property("long_name", route.long_name.clone());
+    feature.set_property("route_type", route_type_code(route.route_type));
+    feature.set_property("route_color", color);
+    feature.set_property("route_text_color", text_color);
+    feature.set_property("agency_id", route.agency_id.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_without_coordinates_have_no_point() {
+        let stop = Stop {
+            id: String::from("X"),
+            longitude: Some(19.04),
+            latitude: Some(47.5),
+            ..Default::default()
+        };
+        let feature = point(&stop).unwrap();
+        assert_eq!(
+            feature.geometry.unwrap().value,
+            Value::Point(vec![19.04, 47.5])
+        );
+
+        let nowhere = Stop {
+            latitude: None,
+            ..stop
+        };
+        assert!(point(&nowhere).is_none());
+    }
+}
@@ -0,0 +1,312 @@
+use ::gif::Encoder;
+use ::gif::Frame;
+use ::gif::Repeat;
+use gtfs_structures::Gtfs;
+use gtfs_structures::Trip;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::args::Args;
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::constants::EXPORT_DIR;
+use crate::constants::GIF_FPS;
+use crate::constants::GIF_HOLD_SECONDS;
+use crate::constants::GIF_SECONDS_PER_MINUTE;
+use crate::constants::GTFS_URL;
+use crate::theme::Theme;
+use crate::utils::camera::Camera;
+use crate::utils::feed::load_gtfs;
+use crate::utils::math::distance;
+use crate::utils::meta::Meta;
+use crate::utils::raster::Canvas;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::stop_index::StopIndex;
+use crate::vizualizations::overlays::legend_entries;
+use crate::vizualizations::overlays::title_lines;
+use crate::vizualizations::overlays::OverlayContent;
+use crate::vizualizations::overlays::Overlays;
+use crate::vizualizations::trips::random_trip;
+use crate::vizualizations::trips::Poster;
+
+// How far past the previous stop's shape point the next one is looked for, in multiples of
+// the straight distance between the stops plus some pixels
+const SEARCH_STRETCH: f64 = 3.;
+const SEARCH_SLACK: f64 = 50.;
+
+// `bp_viz gif [trip_id] [--theme=name|path.toml] [--stop-names] [--overlays=...]
+// [--out=path.gif]`, a random trip passing the route filters without a trip id
+pub fn run(args: &Args) {
+    let gtfs = load_gtfs(GTFS_URL, args);
+
+    let trip = match args.positional.get(1) {
+        Some(id) => Arc::new(gtfs.get_trip(id).expect("Unknown trip").clone()),
+        None => {
+            random_trip(&gtfs, &RouteFilter::from_args(args)).expect("No trips match the filter")
+        }
+    };
+
+    let path = args
+        .get("out")
+        .map(String::from)
+        .unwrap_or_else(|| format!("{EXPORT_DIR}/trips/trip_{}.gif", trip.id));
+
+    eprintln!("Exporting trip {} to {path}...", trip.id);
+    let theme = Theme::from_args(args);
+    let poster = Poster::new(
+        &trip,
+        &gtfs,
+        &Meta::from_trip(&trip),
+        &StopIndex::from_gtfs(&gtfs),
+        &theme,
+        args.has("stop-names"),
+    );
+
+    let overlays = Overlays::from_args(args);
+    trip_gif(&trip, &poster, &gtfs, &theme, &overlays, &path).expect("Failed to export GIF");
+}
+
+// Scheduled seconds since the first departure, for every stop of the trip
+fn schedule(trip: &Trip) -> Vec<f32> {
+    let mut last = None;
+
+    let times = trip
+        .stop_times
+        .iter()
+        .map(|st| {
+            last = st.arrival_time.or(st.departure_time).or(last);
+            last
+        })
+        .collect::<Vec<Option<u32>>>();
+
+    match times.iter().flatten().next() {
+        Some(&start) => times
+            .iter()
+            .map(|t| t.map_or(0., |t| t.saturating_sub(start) as f32))
+            .collect(),
+        // No times at all, space the stops evenly a minute apart
+        None => (0..times.len()).map(|i| i as f32 * 60.).collect(),
+    }
+}
+
+// Index of the shape point of each stop, never going backwards along the shape. By
+// shape_dist_traveled when the feed has it, the closest point otherwise
+fn stop_shape_indices(
+    trip: &Trip,
+    gtfs: &Gtfs,
+    stops: &[(f32, f32)],
+    shape: &[(f32, f32)],
+) -> Vec<usize> {
+    if shape.is_empty() {
+        return vec![0; stops.len()];
+    }
+
+    indices_by_distance(trip, gtfs, shape.len()).unwrap_or_else(|| nearest_indices(stops, shape))
+}
+
+fn indices_by_distance(trip: &Trip, gtfs: &Gtfs, len: usize) -> Option<Vec<usize>> {
+    let points = gtfs.shapes.get(trip.shape_id.as_ref()?)?;
+
+    // The poster fell back to the stops
+    if points.len() != len {
+        return None;
+    }
+
+    let distances = points
+        .iter()
+        .map(|point| point.dist_traveled)
+        .collect::<Option<Vec<f32>>>()?;
+
+    trip.stop_times
+        .iter()
+        .map(|st| {
+            let traveled = st.shape_dist_traveled?;
+            Some(distances.partition_point(|d| *d < traveled).min(len - 1))
+        })
+        .collect()
+}
+
+// Closest point along a stretch of the shape past the previous match, a few times as long as
+// the straight line from the previous stop, so a loop isn't matched on its next pass
+fn nearest_indices(stops: &[(f32, f32)], shape: &[(f32, f32)]) -> Vec<usize> {
+    let dist =
+        |a: (f32, f32), b: (f32, f32)| distance(a.0 as f64, a.1 as f64, b.0 as f64, b.1 as f64);
+
+    let mut from = 0;
+    let mut previous = shape[0];
+
+    stops
+        .iter()
+        .map(|stop| {
+            let reach = dist(previous, *stop) * SEARCH_STRETCH + SEARCH_SLACK;
+            let mut traveled = 0.;
+            let mut best = (from, dist(shape[from], *stop));
+
+            for idx in from + 1..shape.len() {
+                traveled += dist(shape[idx - 1], shape[idx]);
+                if traveled > reach {
+                    break;
+                }
+
+                let d = dist(shape[idx], *stop);
+                if d < best.1 {
+                    best = (idx, d);
+                }
+            }
+
+            from = best.0;
+            previous = *stop;
+            from
+        })
+        .collect()
+}
+
+// The trip drawn stop by stop on its poster, under the overlays with the scheduled time
+pub fn trip_gif(
+    trip: &Trip,
+    poster: &Poster,
+    gtfs: &Gtfs,
+    theme: &Theme,
+    overlays: &Overlays,
+    path: impl AsRef<Path>,
+) -> Result<(), Box<dyn Error>> {
+    let font = theme.typeface();
+
+    let title = title_lines(gtfs);
+    let meta = Meta::from_trip(trip);
+    let camera = Camera::default();
+    let legend = legend_entries(gtfs.routes.get(&trip.route_id).into_iter());
+    let departure = trip
+        .stop_times
+        .iter()
+        .find_map(|st| st.departure_time.or(st.arrival_time));
+
+    let stops = poster
+        .stops
+        .iter()
+        .map(|stop| (stop.x, stop.y))
+        .collect::<Vec<_>>();
+
+    let shape = &poster.shape;
+    let indices = stop_shape_indices(trip, gtfs, &stops, shape);
+    let times = schedule(trip);
+    let total = times.last().copied().unwrap_or(0.);
+
+    let frame_count = ((total / 60. * GIF_SECONDS_PER_MINUTE * GIF_FPS as f32).ceil() as usize)
+        .max(stops.len())
+        .max(2);
+
+    if let Some(dir) = path.as_ref().parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut encoder = Encoder::new(
+        File::create(path)?,
+        CANVAS_WIDTH as u16,
+        CANVAS_HEIGHT as u16,
+        &[],
+    )?;
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    for n in 0..frame_count {
+        let time = total * n as f32 / (frame_count - 1) as f32;
+        let reached = times.iter().filter(|t| **t <= time).count().max(1);
+
+        // Fractional position along the shape between the last reached stop and the next one
+        let progress = match (times.get(reached - 1), times.get(reached)) {
+            (Some(t0), Some(t1)) if t1 > t0 => {
+                let (i0, i1) = (indices[reached - 1] as f32, indices[reached] as f32);
+                i0 + (i1 - i0) * (time - t0) / (t1 - t0)
+            }
+            _ => indices[reached - 1] as f32,
+        };
+
+        let whole = progress.floor() as usize;
+        let mut line = shape[..=whole.min(shape.len() - 1)].to_vec();
+
+        if let (Some(a), Some(b)) = (shape.get(whole), shape.get(whole + 1)) {
+            let frac = progress - whole as f32;
+            line.push((a.0 + (b.0 - a.0) * frac, a.1 + (b.1 - a.1) * frac));
+        }
+
+        let mut canvas = Canvas::new(CANVAS_WIDTH, CANVAS_HEIGHT, poster.bg_color);
+
+        canvas.polyline(&line, theme.line_weight, poster.color);
+
+        for stop in poster.stops.iter().take(reached) {
+            canvas.circle(
+                stop.x,
+                stop.y,
+                theme.stop_radius,
+                poster.bg_color,
+                poster.color,
+                theme.line_weight,
+            );
+
+            if stop.transfer {
+                canvas.circle(
+                    stop.x,
+                    stop.y,
+                    theme.stop_radius / 2.,
+                    poster.color,
+                    poster.color,
+                    0.,
+                );
+            }
+        }
+
+        for (start, end) in poster
+            .stops
+            .iter()
+            .take(reached)
+            .filter_map(|stop| stop.leader)
+        {
+            canvas.polyline(&[start, end], 1., poster.color);
+        }
+
+        canvas.polygon(&poster.badge.outline(), poster.badge.fill);
+
+        let stop_labels = poster
+            .stops
+            .iter()
+            .take(reached)
+            .filter_map(|stop| stop.label.as_ref());
+
+        for label in poster
+            .labels
+            .iter()
+            .chain([&poster.badge.label])
+            .chain(stop_labels)
+        {
+            canvas.text(label, &font);
+        }
+
+        let content = OverlayContent {
+            title: &title,
+            meta: &meta,
+            camera: &camera,
+            legend: &legend,
+            time: departure.map(|departure| departure as f64 + time as f64),
+        };
+        overlays.draw(&mut canvas, theme, &content);
+
+        let mut frame = Frame::from_rgba_speed(
+            CANVAS_WIDTH as u16,
+            CANVAS_HEIGHT as u16,
+            &mut canvas.pixels,
+            10,
+        );
+
+        frame.delay = if n == frame_count - 1 {
+            (GIF_HOLD_SECONDS * 100.) as u16
+        } else {
+            (100 / GIF_FPS) as u16
+        };
+
+        encoder.write_frame(&frame)?;
+    }
+
+    Ok(())
+}
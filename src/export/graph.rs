@@ -0,0 +1,191 @@
+use csv::Writer;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::args::Args;
+use crate::constants::EXPORT_DIR;
+use crate::constants::GTFS_URL;
+use crate::routing::graph::StopGraph;
+use crate::utils::feed::load_gtfs;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::parse_date;
+
+const FORMATS: [&str; 3] = ["graphml", "dot", "csv"];
+
+// `bp_viz export graph [--format=graphml,dot,csv] [--stations] [--date=YYYYMMDD]
+// [--route=9*,M2 and the other route filters] [--out=dir]`
+pub fn run(args: &Args) {
+    let gtfs = load_gtfs(GTFS_URL, args);
+
+    let date = args
+        .get("date")
+        .map(|date| parse_date(date).unwrap_or_else(|| panic!("Invalid value for --date: {date}")));
+    let formats = args.get("format").map_or_else(
+        || FORMATS.to_vec(),
+        |formats| formats.split(',').map(str::trim).collect(),
+    );
+    let out = args
+        .get("out")
+        .map(String::from)
+        .unwrap_or_else(|| format!("{EXPORT_DIR}/graph"));
+
+    let filter = RouteFilter::from_args(args);
+    let graph = StopGraph::from_gtfs(&gtfs, &filter, date, args.has("stations"));
+    eprintln!(
+        "Stop graph of {} nodes and {} edges",
+        graph.nodes.len(),
+        graph.edges.len()
+    );
+
+    fs::create_dir_all(&out).expect("Failed to create the output directory");
+    let out = Path::new(&out);
+
+    for format in formats {
+        let written = match format {
+            "graphml" => write_graphml(&graph, &out.join("stops.graphml")),
+            "dot" => write_dot(&graph, &out.join("stops.dot")),
+            "csv" => write_csv(&graph, &out.join("nodes.csv"), &out.join("edges.csv")),
+            _ => panic!("Invalid value for --format: {format}"),
+        };
+
+        written.unwrap_or_else(|e| panic!("Failed to export the {format} graph: {e}"));
+    }
+
+    eprintln!("Exported the stop graph to {}", out.display());
+}
+
+fn write_graphml(graph: &StopGraph, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="name" for="node" attr.name="name" attr.type="string"/>
+  <key id="lng" for="node" attr.name="lng" attr.type="double"/>
+  <key id="lat" for="node" attr.name="lat" attr.type="double"/>
+  <key id="trips" for="edge" attr.name="trips" attr.type="int"/>
+  <key id="travel_time" for="edge" attr.name="travel_time" attr.type="int"/>
+  <graph id="stops" edgedefault="directed">
+"#,
+    );
+
+    for node in graph.nodes.iter() {
+        writeln!(xml, r#"    <node id="{}">"#, escape_xml(&node.id))?;
+        writeln!(
+            xml,
+            r#"      <data key="name">{}</data>"#,
+            escape_xml(&node.name)
+        )?;
+        if let (Some(lng), Some(lat)) = (node.longitude, node.latitude) {
+            writeln!(xml, r#"      <data key="lng">{lng}</data>"#)?;
+            writeln!(xml, r#"      <data key="lat">{lat}</data>"#)?;
+        }
+        writeln!(xml, "    </node>")?;
+    }
+
+    for edge in graph.edges.iter() {
+        let (from, to) = (&graph.nodes[edge.from].id, &graph.nodes[edge.to].id);
+
+        writeln!(
+            xml,
+            r#"    <edge source="{}" target="{}">"#,
+            escape_xml(from),
+            escape_xml(to)
+        )?;
+        writeln!(xml, r#"      <data key="trips">{}</data>"#, edge.trips)?;
+        writeln!(
+            xml,
+            r#"      <data key="travel_time">{}</data>"#,
+            edge.travel_time
+        )?;
+        writeln!(xml, "    </edge>")?;
+    }
+
+    xml.push_str("  </graph>\n</graphml>\n");
+
+    eprintln!("Writing {}...", path.display());
+    fs::write(path, xml)?;
+
+    Ok(())
+}
+
+fn write_dot(graph: &StopGraph, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut dot = String::from("digraph stops {\n");
+
+    for node in graph.nodes.iter() {
+        write!(
+            dot,
+            "  \"{}\" [label=\"{}\"",
+            escape_dot(&node.id),
+            escape_dot(&node.name)
+        )?;
+        // Positions for `neato -n`, in degrees
+        if let (Some(lng), Some(lat)) = (node.longitude, node.latitude) {
+            write!(dot, ", pos=\"{lng},{lat}\"")?;
+        }
+        writeln!(dot, "];")?;
+    }
+
+    for edge in graph.edges.iter() {
+        writeln!(
+            dot,
+            "  \"{}\" -> \"{}\" [trips={}, travel_time={}, weight={}];",
+            escape_dot(&graph.nodes[edge.from].id),
+            escape_dot(&graph.nodes[edge.to].id),
+            edge.trips,
+            edge.travel_time,
+            edge.trips,
+        )?;
+    }
+
+    dot.push_str("}\n");
+
+    eprintln!("Writing {}...", path.display());
+    fs::write(path, dot)?;
+
+    Ok(())
+}
+
+fn write_csv(graph: &StopGraph, nodes: &Path, edges: &Path) -> Result<(), Box<dyn Error>> {
+    eprintln!("Writing {}...", nodes.display());
+    let mut writer = Writer::from_path(nodes)?;
+    writer.write_record(["id", "name", "lng", "lat"])?;
+
+    for node in graph.nodes.iter() {
+        let coord = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+        writer.write_record([
+            node.id.clone(),
+            node.name.clone(),
+            coord(node.longitude),
+            coord(node.latitude),
+        ])?;
+    }
+    writer.flush()?;
+
+    eprintln!("Writing {}...", edges.display());
+    let mut writer = Writer::from_path(edges)?;
+    writer.write_record(["source", "target", "trips", "travel_time"])?;
+
+    for edge in graph.edges.iter() {
+        writer.write_record([
+            graph.nodes[edge.from].id.clone(),
+            graph.nodes[edge.to].id.clone(),
+            edge.trips.to_string(),
+            edge.travel_time.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
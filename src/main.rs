@@ -1,28 +1,27 @@
+mod args;
+mod basemap;
 mod constants;
+mod export;
 mod piano;
+mod recorder;
+mod routing;
+mod theme;
 mod utils;
 mod vizualizations;
 
-use vizualizations::audio::AudioViz;
-use vizualizations::stops::StopsViz;
-use vizualizations::trips::TripsViz;
-use vizualizations::Vizualization;
+use args::Args;
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let args = Args::from_env();
 
-    let viz_type = args.get(1);
-
-    let viz: Box<dyn Vizualization> = if let Some(s) = viz_type {
-        match s.as_str() {
-            "stops" => Box::new(StopsViz::default()),
-            "trips" => Box::new(TripsViz::new()),
-            "audio" => Box::new(AudioViz::default()),
-            _ => Box::new(TripsViz::new()),
-        }
-    } else {
-        Box::new(TripsViz::new())
-    };
-
-    viz.run();
+    match args.positional.first().map(String::as_str) {
+        // Headless commands
+        Some("gif") => export::gif::run(&args),
+        Some("export") => export::run(&args),
+        Some("plan") => routing::plan::run(&args),
+        Some("centrality") => routing::centrality::run(&args),
+        Some("list") => vizualizations::shell::list(),
+        // `bp_viz [trips|stops|audio]` opens the window on that visualization
+        _ => vizualizations::shell::run(),
+    }
 }
@@ -1,66 +1,110 @@
+pub mod note;
+
 use csv::Reader;
-use csv::StringRecord;
+use hound::SampleFormat;
+use hound::WavSpec;
+use hound::WavWriter;
 use rodio::dynamic_mixer;
 use rodio::source::SineWave;
 use rodio::OutputStream;
 use rodio::Sink;
 use rodio::Source;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
-use std::fmt::Display;
+use std::f32::consts::TAU;
+use std::ops::RangeInclusive;
+use std::path::Path;
 use std::time::Duration;
 
-#[derive(Clone, Debug)]
-pub struct Note {
-    pub name: String,
-    pub freq: f32,
+use crate::constants::A4_FREQ;
+use crate::constants::NOTES_CSV;
+use crate::constants::PIANO_OCTAVES;
+
+pub use note::Note;
+
+const SAMPLE_RATE: u32 = 44100;
+
+#[derive(Debug)]
+pub struct Piano {
+    pub keys: BTreeMap<u8, Note>,
+    names: HashMap<String, u8>,
 }
 
-impl From<StringRecord> for Note {
-    fn from(rec: StringRecord) -> Self {
-        Self {
-            name: String::from(rec.get(0).unwrap()),
-            freq: rec.get(1).unwrap().parse().expect("Failed to parse note"),
+impl Piano {
+    pub fn new() -> Self {
+        let piano = Self::equal_temperament(A4_FREQ, PIANO_OCTAVES);
+
+        if !Path::new(NOTES_CSV).exists() {
+            return piano;
         }
+
+        Self::equal_temperament(A4_FREQ, PIANO_OCTAVES)
+            .with_csv(NOTES_CSV)
+            .unwrap_or_else(|e| {
+                eprintln!("Couldn't read {NOTES_CSV}, using equal temperament: {e}");
+                piano
+            })
     }
-}
 
-impl Default for Note {
-    fn default() -> Self {
-        Self {
-            name: String::from("A4"),
-            freq: 440.,
+    pub fn equal_temperament(a4_freq: f32, octaves: RangeInclusive<i8>) -> Self {
+        let mut piano = Self {
+            keys: BTreeMap::new(),
+            names: HashMap::new(),
+        };
+
+        for octave in octaves {
+            for class in 0..12 {
+                let midi = (octave as i16 + 1) * 12 + class;
+
+                if let Ok(midi @ 0..=127) = u8::try_from(midi) {
+                    piano.insert(Note::from_midi(midi, a4_freq));
+                }
+            }
         }
-    }
-}
 
-impl Display for Note {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("{} ({}Hz)", self.name, self.freq))
+        piano
     }
-}
 
-#[derive(Debug)]
-pub struct Piano {
-    pub keys: HashMap<String, Note>,
-}
+    // Overrides (or adds) the frequencies listed in a `name,freq` CSV file
+    pub fn with_csv(mut self, path: impl AsRef<Path>) -> Result<Self, csv::Error> {
+        let mut raw = Reader::from_path(path)?;
 
-impl Piano {
-    pub fn new() -> Self {
-        let mut raw = Reader::from_path("./data/notes.csv").expect("Couldn't read notes.csv");
-        let mut keys = HashMap::new();
+        for (idx, record) in raw.records().enumerate() {
+            match record.map_err(|e| e.to_string()).and_then(Note::try_from) {
+                Ok(note) => self.insert(note),
+                Err(e) => eprintln!("Skipping row {} of notes: {e}", idx + 1),
+            }
+        }
+
+        Ok(self)
+    }
 
-        while let Some(Ok(record)) = raw.records().next() {
-            keys.insert(record.get(0).unwrap().to_string(), Note::from(record));
+    fn insert(&mut self, note: Note) {
+        for alias in note.aliases() {
+            self.names.insert(alias, note.midi);
         }
 
-        Self { keys }
+        self.keys.insert(note.midi, note);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Note> {
+        let midi = match self.names.get(name) {
+            Some(midi) => *midi,
+            None => note::name_to_midi(name)?,
+        };
+
+        self.get_midi(midi)
     }
 
-    pub fn press_keys(&self, keys: impl IntoIterator<Item = &'static str>, duration: Duration) {
-        let (ctrl, mixer) = dynamic_mixer::mixer::<f32>(2, 44100);
+    pub fn get_midi(&self, midi: u8) -> Option<&Note> {
+        self.keys.get(&midi)
+    }
+
+    pub fn press_keys<'a>(&self, keys: impl IntoIterator<Item = &'a str>, duration: Duration) {
+        let (ctrl, mixer) = dynamic_mixer::mixer::<f32>(2, SAMPLE_RATE);
 
         for note in keys.into_iter() {
-            if let Some(note) = self.keys.get(note) {
+            if let Some(note) = self.get(note) {
                 let source = SineWave::new(note.freq)
                     .take_duration(duration)
                     .amplify(0.24);
@@ -76,6 +120,46 @@ impl Piano {
         sink.sleep_until_end();
         sink.detach();
     }
+
+    // Renders a sequence of chords, each held for `step`, into a mono WAV file
+    pub fn render_wav<'a, C>(
+        &self,
+        chords: impl IntoIterator<Item = C>,
+        step: Duration,
+        path: impl AsRef<Path>,
+    ) -> Result<(), hound::Error>
+    where
+        C: IntoIterator<Item = &'a str>,
+    {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(path, spec)?;
+        let samples_per_step = (step.as_secs_f64() * SAMPLE_RATE as f64).round() as u32;
+
+        for chord in chords {
+            let freqs = chord
+                .into_iter()
+                .filter_map(|name| self.get(name))
+                .map(|note| note.freq)
+                .collect::<Vec<f32>>();
+
+            for n in 0..samples_per_step {
+                let t = n as f32 / SAMPLE_RATE as f32;
+                let sample = freqs
+                    .iter()
+                    .map(|f| (TAU * f * t).sin() * 0.18)
+                    .sum::<f32>();
+
+                writer.write_sample((sample.clamp(-1., 1.) * i16::MAX as f32) as i16)?;
+            }
+        }
+
+        writer.finalize()
+    }
 }
 
 impl Default for Piano {
@@ -83,3 +167,36 @@ impl Default for Piano {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_overrides_rows_and_skips_bad_ones() {
+        let path = std::env::temp_dir().join(format!("bp_viz_notes_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "name,freq\nA4,432\nH9,100\nC4,not a number\nDb4,280\n",
+        )
+        .unwrap();
+
+        let piano = Piano::equal_temperament(A4_FREQ, PIANO_OCTAVES)
+            .with_csv(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(piano.get("A4").unwrap().freq, 432.);
+        // Both spellings of an overridden note
+        assert_eq!(piano.get("C#4").unwrap().freq, 280.);
+        assert_eq!(piano.get("C#4/Db4").unwrap().freq, 280.);
+        // Untouched by the bad row
+        assert!((piano.get("C4").unwrap().freq - 261.63).abs() < 0.01);
+    }
+
+    #[test]
+    fn missing_csv_is_an_error() {
+        let piano = Piano::equal_temperament(A4_FREQ, PIANO_OCTAVES);
+        assert!(piano.with_csv("./does/not/exist.csv").is_err());
+    }
+}
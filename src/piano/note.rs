@@ -0,0 +1,175 @@
+use csv::StringRecord;
+use std::fmt::Display;
+
+const PITCH_CLASSES: [&str; 12] = [
+    "C", "C#/Db", "D", "D#/Eb", "E", "F", "F#/Gb", "G", "G#/Ab", "A", "A#/Bb", "B",
+];
+
+const A4_MIDI: u8 = 69;
+
+#[derive(Clone, Debug)]
+pub struct Note {
+    pub name: String,
+    pub midi: u8,
+    pub freq: f32,
+}
+
+impl Note {
+    pub fn from_midi(midi: u8, a4_freq: f32) -> Self {
+        let freq = a4_freq * 2f32.powf((midi as f32 - A4_MIDI as f32) / 12.);
+
+        Self {
+            name: midi_to_name(midi),
+            midi,
+            freq,
+        }
+    }
+
+    pub fn octave(&self) -> i8 {
+        (self.midi / 12) as i8 - 1
+    }
+
+    // Every spelling of the note, e.g. "C#4/Db4", "C#4" and "Db4"
+    pub fn aliases(&self) -> Vec<String> {
+        let mut aliases = vec![self.name.clone()];
+
+        if self.name.contains('/') {
+            aliases.extend(self.name.split('/').map(String::from));
+        }
+
+        aliases
+    }
+}
+
+impl TryFrom<StringRecord> for Note {
+    type Error = String;
+
+    fn try_from(rec: StringRecord) -> Result<Self, Self::Error> {
+        let name = rec.get(0).ok_or("Missing note name")?.trim();
+        let freq = rec
+            .get(1)
+            .ok_or_else(|| format!("Missing frequency for {name}"))?
+            .trim()
+            .parse()
+            .map_err(|_| format!("Failed to parse frequency for {name}"))?;
+        let midi = name_to_midi(name).ok_or_else(|| format!("Unknown note name {name}"))?;
+
+        Ok(Self {
+            name: midi_to_name(midi),
+            midi,
+            freq,
+        })
+    }
+}
+
+impl Default for Note {
+    fn default() -> Self {
+        Self {
+            name: String::from("A4"),
+            midi: A4_MIDI,
+            freq: 440.,
+        }
+    }
+}
+
+impl Display for Note {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{} ({}Hz)", self.name, self.freq))
+    }
+}
+
+pub fn midi_to_name(midi: u8) -> String {
+    let octave = (midi / 12) as i8 - 1;
+
+    PITCH_CLASSES[(midi % 12) as usize]
+        .split('/')
+        .map(|class| format!("{class}{octave}"))
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+// Accepts "A4", "C#4", "Db4" and the combined "C#4/Db4" spelling
+pub fn name_to_midi(name: &str) -> Option<u8> {
+    let mut spellings = name.split('/').map(parse_spelling);
+    let midi = spellings.next()??;
+
+    if spellings.all(|other| other == Some(midi)) {
+        Some(midi)
+    } else {
+        None
+    }
+}
+
+fn parse_spelling(spelling: &str) -> Option<u8> {
+    let mut chars = spelling.trim().chars().peekable();
+
+    let mut class: i16 = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    match chars.peek() {
+        Some('#') => {
+            class += 1;
+            chars.next();
+        }
+        Some('b') => {
+            class -= 1;
+            chars.next();
+        }
+        _ => (),
+    }
+
+    let octave: i16 = chars.collect::<String>().parse().ok()?;
+    let midi = (octave + 1) * 12 + class;
+
+    u8::try_from(midi).ok().filter(|midi| *midi <= 127)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for midi in 0..=127 {
+            assert_eq!(name_to_midi(&midi_to_name(midi)), Some(midi));
+        }
+    }
+
+    #[test]
+    fn sharps_and_flats_are_aliases() {
+        assert_eq!(midi_to_name(61), "C#4/Db4");
+        assert_eq!(name_to_midi("C#4"), Some(61));
+        assert_eq!(name_to_midi("Db4"), Some(61));
+        assert_eq!(name_to_midi("C#4/Db4"), Some(61));
+        assert_eq!(name_to_midi("C#4/Eb4"), None);
+
+        let note = Note::from_midi(61, 440.);
+        assert_eq!(note.aliases(), vec!["C#4/Db4", "C#4", "Db4"]);
+    }
+
+    #[test]
+    fn a4_is_440_hz() {
+        let note = Note::from_midi(69, 440.);
+
+        assert_eq!(note.name, "A4");
+        assert_eq!(note.freq, 440.);
+        assert_eq!(note.octave(), 4);
+        assert_eq!(name_to_midi("A4"), Some(69));
+        assert!((Note::from_midi(81, 440.).freq - 880.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn invalid_names() {
+        assert_eq!(name_to_midi("H4"), None);
+        assert_eq!(name_to_midi("A"), None);
+        assert_eq!(name_to_midi("G#9"), None);
+    }
+}
@@ -0,0 +1,232 @@
+use nannou::window;
+use nannou::App;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::args::Args;
+use crate::constants::EXPORT_DIR;
+use crate::constants::RECORD_FPS;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Mp4,
+    Gif,
+}
+
+impl Encoding {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mp4" => Some(Self::Mp4),
+            "gif" => Some(Self::Gif),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Gif => "gif",
+        }
+    }
+}
+
+// Writes numbered frames at a fixed simulated frame rate, so the exported
+// footage plays at `fps` no matter how long each frame took to render. Frames go to disk
+// rather than through a pipe into ffmpeg: nannou only captures windows into image files,
+// written off the main thread, and the take keeps the frames and soundtrack for encoding
+// again with other settings
+#[derive(Debug, Default)]
+pub struct Recorder {
+    pub fps: u32,
+    active: bool,
+    name: String,
+    take: Option<PathBuf>,
+    frames: u64,
+    // Window the frames are captured from, set by the first capture
+    window: Option<window::Id>,
+    encoding: Option<Encoding>,
+    audio: Option<PathBuf>,
+}
+
+impl Recorder {
+    pub fn from_args(args: &Args, name: &str) -> Self {
+        let mut recorder = Self {
+            fps: args.value("fps").unwrap_or(RECORD_FPS),
+            name: String::from(name),
+            encoding: args.get("encode").map(|encoding| {
+                Encoding::from_name(encoding).unwrap_or_else(|| {
+                    panic!("Invalid value for --encode: {encoding} (expected mp4 or gif)")
+                })
+            }),
+            ..Default::default()
+        };
+
+        if args.has("record") {
+            recorder.try_start();
+        }
+
+        recorder
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f64(1. / self.fps.max(1) as f64)
+    }
+
+    // Simulated time elapsed in the current take
+    pub fn elapsed(&self) -> Duration {
+        self.frame_duration() * self.frames as u32
+    }
+
+    pub fn take_dir(&self) -> Option<&PathBuf> {
+        self.take.as_ref()
+    }
+
+    pub fn start(&mut self) -> std::io::Result<()> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let take = PathBuf::from(EXPORT_DIR)
+            .join(&self.name)
+            .join(stamp.to_string());
+
+        std::fs::create_dir_all(&take)?;
+        eprintln!("Recording to {}...", take.display());
+
+        self.take = Some(take);
+        self.frames = 0;
+        self.audio = None;
+        self.active = true;
+        Ok(())
+    }
+
+    // Starts a take, or reports why it couldn't and stays off
+    fn try_start(&mut self) {
+        if let Err(e) = self.start() {
+            eprintln!("Couldn't create export directory: {e}");
+        }
+    }
+
+    // Waits for the captures still being written, then encodes the take
+    pub fn stop(&mut self, app: &App) {
+        if !self.active {
+            return;
+        }
+
+        if let Some(window) = self.window.and_then(|id| app.window(id)) {
+            if let Err(e) = window.await_capture_frame_jobs() {
+                eprintln!("Failed to write captured frames: {e:?}");
+            }
+        }
+
+        self.end();
+    }
+
+    // Ends the take as the app exits, without touching the closing window, frames still
+    // being written are left out of the encoding
+    pub fn finish(&mut self) {
+        if self.active {
+            self.end();
+        }
+    }
+
+    fn end(&mut self) {
+        self.active = false;
+
+        eprintln!("Recorded {} frames", self.frames);
+        self.encode();
+    }
+
+    pub fn toggle(&mut self, app: &App) {
+        if self.active {
+            self.stop(app);
+        } else {
+            self.try_start();
+        }
+    }
+
+    // Soundtrack muxed into the encoded video, must start at the first frame of the take
+    pub fn attach_audio(&mut self, path: PathBuf) {
+        self.audio = Some(path);
+    }
+
+    // Captures the next rendered frame, call once per update
+    pub fn capture(&mut self, app: &App) {
+        if let Some(take) = self.take.as_ref().filter(|_| self.active) {
+            let filename = take.join(format!("frame_{:05}.png", self.frames));
+            let window = app.main_window();
+
+            window.capture_frame(filename);
+            self.window = Some(window.id());
+            self.frames += 1;
+        }
+    }
+
+    fn encode(&self) {
+        let (take, encoding) = match (&self.take, self.encoding) {
+            (Some(take), Some(encoding)) if self.frames > 0 => (take, encoding),
+            _ => return,
+        };
+
+        let output = take.with_extension(encoding.extension());
+        let mut ffmpeg = Command::new("ffmpeg");
+
+        ffmpeg
+            .args(["-y", "-loglevel", "error", "-framerate"])
+            .arg(self.fps.to_string())
+            .arg("-i")
+            .arg(take.join("frame_%05d.png"));
+
+        match encoding {
+            Encoding::Mp4 => {
+                if let Some(audio) = &self.audio {
+                    ffmpeg
+                        .arg("-i")
+                        .arg(audio)
+                        .args(["-c:a", "aac", "-shortest"]);
+                }
+
+                ffmpeg.args(["-c:v", "libx264", "-pix_fmt", "yuv420p"]);
+            }
+            Encoding::Gif => {
+                ffmpeg.args([
+                    "-vf",
+                    "split[a][b];[a]palettegen[p];[b][p]paletteuse",
+                    "-loop",
+                    "0",
+                ]);
+            }
+        }
+
+        eprintln!("Encoding {}...", output.display());
+
+        match ffmpeg.arg(&output).status() {
+            Ok(status) if status.success() => (),
+            Ok(status) => eprintln!("ffmpeg exited with {status}"),
+            Err(e) => eprintln!("Couldn't run ffmpeg: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_by_name() {
+        assert_eq!(Encoding::from_name("mp4"), Some(Encoding::Mp4));
+        assert_eq!(Encoding::from_name("gif"), Some(Encoding::Gif));
+        assert_eq!(Encoding::from_name("webm"), None);
+    }
+}
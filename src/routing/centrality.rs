@@ -0,0 +1,294 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::path::Path;
+
+use super::graph::StopGraph;
+use crate::args::Args;
+use crate::constants::GTFS_URL;
+use crate::utils::feed::load_gtfs;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::parse_date;
+
+const TOP: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    Betweenness,
+    Closeness,
+    Degree,
+}
+
+impl Measure {
+    pub const ALL: [Measure; 3] = [Measure::Betweenness, Measure::Closeness, Measure::Degree];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Measure::Betweenness => "betweenness",
+            Measure::Closeness => "closeness",
+            Measure::Degree => "degree",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|measure| measure.name() == name)
+    }
+}
+
+// Centrality of every node of a stop graph, by the same index. Shortest paths count hops, or
+// the median travel time of the edges if weighted
+#[derive(Debug, Default)]
+pub struct Centrality {
+    pub betweenness: Vec<f64>,
+    pub closeness: Vec<f64>,
+    // Neighbours in and out
+    pub degree: Vec<usize>,
+}
+
+impl Centrality {
+    // Brandes' algorithm, one Dijkstra search from every node
+    pub fn from_graph(graph: &StopGraph, weighted: bool) -> Self {
+        let n = graph.nodes.len();
+
+        let mut outgoing = vec![vec![]; n];
+        let mut degree = vec![0; n];
+        for edge in graph.edges.iter() {
+            let weight = if weighted {
+                edge.travel_time.max(1) as u64
+            } else {
+                1
+            };
+            outgoing[edge.from].push((edge.to, weight));
+            degree[edge.from] += 1;
+            degree[edge.to] += 1;
+        }
+
+        let mut betweenness = vec![0.; n];
+        let mut closeness = vec![0.; n];
+
+        let mut distance = vec![u64::MAX; n];
+        let mut paths = vec![0.; n];
+        let mut predecessors: Vec<Vec<usize>> = vec![vec![]; n];
+        let mut dependency = vec![0.; n];
+
+        for source in 0..n {
+            distance.fill(u64::MAX);
+            paths.fill(0.);
+            dependency.fill(0.);
+            predecessors.iter_mut().for_each(Vec::clear);
+
+            // Nodes by increasing distance as they're settled
+            let mut settled = vec![];
+            let mut heap = BinaryHeap::new();
+
+            distance[source] = 0;
+            paths[source] = 1.;
+            heap.push(Reverse((0, source)));
+
+            while let Some(Reverse((d, node))) = heap.pop() {
+                if d > distance[node] {
+                    continue;
+                }
+                settled.push(node);
+
+                for (to, weight) in outgoing[node].iter().copied() {
+                    let d = d + weight;
+
+                    if d < distance[to] {
+                        distance[to] = d;
+                        paths[to] = paths[node];
+                        predecessors[to].clear();
+                        predecessors[to].push(node);
+                        heap.push(Reverse((d, to)));
+                    } else if d == distance[to] {
+                        paths[to] += paths[node];
+                        predecessors[to].push(node);
+                    }
+                }
+            }
+
+            // Scaled by the share of the graph reached, so stops on small islands of the
+            // network don't come out on top
+            let reached = settled.len() - 1;
+            let total = settled.iter().map(|node| distance[*node]).sum::<u64>();
+            if reached > 0 && total > 0 && n > 1 {
+                closeness[source] =
+                    (reached as f64 / total as f64) * (reached as f64 / (n - 1) as f64);
+            }
+
+            for node in settled.into_iter().rev() {
+                for pred in predecessors[node].iter().copied() {
+                    dependency[pred] += paths[pred] / paths[node] * (1. + dependency[node]);
+                }
+                if node != source {
+                    betweenness[node] += dependency[node];
+                }
+            }
+        }
+
+        if n > 2 {
+            let scale = 1. / ((n - 1) * (n - 2)) as f64;
+            betweenness.iter_mut().for_each(|b| *b *= scale);
+        }
+
+        Self {
+            betweenness,
+            closeness,
+            degree,
+        }
+    }
+
+    pub fn get(&self, measure: Measure, node: usize) -> f64 {
+        match measure {
+            Measure::Betweenness => self.betweenness[node],
+            Measure::Closeness => self.closeness[node],
+            Measure::Degree => self.degree[node] as f64,
+        }
+    }
+
+    pub fn max(&self, measure: Measure) -> f64 {
+        (0..self.degree.len())
+            .map(|node| self.get(measure, node))
+            .fold(0., f64::max)
+    }
+
+    // Node indices from the most central down
+    pub fn ranking(&self, measure: Measure) -> Vec<usize> {
+        let mut nodes = (0..self.degree.len()).collect::<Vec<usize>>();
+        nodes.sort_by(|a, b| self.get(measure, *b).total_cmp(&self.get(measure, *a)));
+        nodes
+    }
+}
+
+// `bp_viz centrality [--measure=betweenness|closeness|degree] [--weighted] [--stations]
+// [--date=YYYYMMDD] [--top=N] [--out=file.csv] and the route filters`
+pub fn run(args: &Args) {
+    let measure = args.get("measure").map_or(Measure::Betweenness, |name| {
+        Measure::from_name(name).unwrap_or_else(|| panic!("Invalid value for --measure: {name}"))
+    });
+    let date = args
+        .get("date")
+        .map(|date| parse_date(date).unwrap_or_else(|| panic!("Invalid value for --date: {date}")));
+    let top = args.value("top").unwrap_or(TOP);
+
+    let gtfs = load_gtfs(GTFS_URL, args);
+    let filter = RouteFilter::from_args(args);
+    let graph = StopGraph::from_gtfs(&gtfs, &filter, date, args.has("stations"));
+
+    eprintln!(
+        "Computing the centrality of {} nodes and {} edges...",
+        graph.nodes.len(),
+        graph.edges.len()
+    );
+    let centrality = Centrality::from_graph(&graph, args.has("weighted"));
+    let ranking = centrality.ranking(measure);
+
+    println!(
+        "{:>4}  {:<32} {:>12} {:>10} {:>7}",
+        "#", "stop", "betweenness", "closeness", "degree"
+    );
+    for (rank, node) in ranking.iter().take(top).enumerate() {
+        println!(
+            "{:>4}  {:<32} {:>12.5} {:>10.5} {:>7}",
+            rank + 1,
+            graph.nodes[*node].name,
+            centrality.betweenness[*node],
+            centrality.closeness[*node],
+            centrality.degree[*node],
+        );
+    }
+
+    if let Some(out) = args.get("out") {
+        write_csv(&graph, &centrality, &ranking, Path::new(out))
+            .unwrap_or_else(|e| panic!("Failed to write {out}: {e}"));
+        eprintln!("Wrote the ranking to {out}");
+    }
+}
+
+fn write_csv(
+    graph: &StopGraph,
+    centrality: &Centrality,
+    ranking: &[usize],
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["rank", "id", "name", "betweenness", "closeness", "degree"])?;
+
+    for (rank, node) in ranking.iter().copied().enumerate() {
+        writer.write_record([
+            (rank + 1).to_string(),
+            graph.nodes[node].id.clone(),
+            graph.nodes[node].name.clone(),
+            centrality.betweenness[node].to_string(),
+            centrality.closeness[node].to_string(),
+            centrality.degree[node].to_string(),
+        ])?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routing::graph::Edge;
+    use crate::routing::graph::Node;
+
+    fn graph(n: usize, edges: &[(usize, usize, u32)]) -> StopGraph {
+        StopGraph {
+            nodes: (0..n)
+                .map(|idx| Node {
+                    id: idx.to_string(),
+                    name: idx.to_string(),
+                    longitude: None,
+                    latitude: None,
+                })
+                .collect(),
+            edges: edges
+                .iter()
+                .map(|(from, to, travel_time)| Edge {
+                    from: *from,
+                    to: *to,
+                    trips: 1,
+                    travel_time: *travel_time,
+                })
+                .collect(),
+        }
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{actual:?} != {expected:?}");
+        }
+    }
+
+    // 0 – 1 – 2 both ways
+    #[test]
+    fn path() {
+        let graph = graph(3, &[(0, 1, 60), (1, 0, 60), (1, 2, 60), (2, 1, 60)]);
+        let centrality = Centrality::from_graph(&graph, false);
+
+        // The middle is on both paths between the ends, out of the two pairs it isn't part of
+        assert_close(&centrality.betweenness, &[0., 1., 0.]);
+        assert_close(&centrality.closeness, &[2. / 3., 1., 2. / 3.]);
+        assert_eq!(centrality.degree, vec![2, 4, 2]);
+        assert_eq!(centrality.ranking(Measure::Degree)[0], 1);
+        assert_eq!(centrality.max(Measure::Closeness), 1.);
+    }
+
+    // 0 → 1 → 2 taking 20 minutes, or 0 → 2 straight in 30
+    #[test]
+    fn weights_pick_the_faster_detour() {
+        let graph = graph(3, &[(0, 1, 600), (1, 2, 600), (0, 2, 1800)]);
+
+        let hops = Centrality::from_graph(&graph, false);
+        assert_close(&hops.betweenness, &[0., 0., 0.]);
+
+        let weighted = Centrality::from_graph(&graph, true);
+        assert_close(&weighted.betweenness, &[0., 0.5, 0.]);
+        // Nothing is reachable from the last stop
+        assert_eq!(weighted.closeness[2], 0.);
+    }
+}
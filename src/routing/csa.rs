@@ -0,0 +1,234 @@
+use chrono::NaiveDate;
+use gtfs_structures::Gtfs;
+use gtfs_structures::Stop;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::footpaths::Footpaths;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::runs_on;
+
+const DAY: u32 = 24 * 60 * 60;
+const UNREACHED: u32 = u32::MAX;
+
+// A vehicle going from one stop to the next without stopping in between
+#[derive(Debug, Clone, Copy)]
+pub struct Connection {
+    pub trip: usize,
+    pub from: usize,
+    pub to: usize,
+    pub departure: u32,
+    pub arrival: u32,
+}
+
+// Every connection of the trips running on a date sorted by departure, for the
+// Connection Scan Algorithm, with stops and trips referred to by index
+#[derive(Debug, Default)]
+pub struct Timetable {
+    pub date: NaiveDate,
+    pub stops: Vec<Arc<Stop>>,
+    pub trip_ids: Vec<String>,
+    pub connections: Vec<Connection>,
+    index: HashMap<String, usize>,
+}
+
+impl Timetable {
+    // Trips passing `filter` running on `date`, and the previous day's running past midnight
+    // with their times shifted to `date`
+    pub fn from_gtfs(gtfs: &Gtfs, filter: &RouteFilter, date: NaiveDate) -> Self {
+        let mut stops = gtfs.stops.values().cloned().collect::<Vec<Arc<Stop>>>();
+        stops.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let index = stops
+            .iter()
+            .enumerate()
+            .map(|(idx, stop)| (stop.id.clone(), idx))
+            .collect::<HashMap<String, usize>>();
+
+        let mut trips = gtfs
+            .trips
+            .values()
+            .filter(|trip| filter.keeps_trip(gtfs, trip))
+            .collect::<Vec<_>>();
+        trips.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut trip_ids = vec![];
+        let mut connections = vec![];
+
+        for trip in trips {
+            let days = [(date, 0), (date.pred(), DAY)]
+                .into_iter()
+                .filter(|(date, _)| runs_on(gtfs, &trip.service_id, *date));
+
+            for (_, shift) in days {
+                let idx = trip_ids.len();
+                let mut kept = false;
+
+                for pair in trip.stop_times.windows(2) {
+                    let (a, b) = (&pair[0], &pair[1]);
+
+                    let departure = match a.departure_time.or(a.arrival_time) {
+                        Some(time) if time >= shift => time - shift,
+                        _ => continue,
+                    };
+                    let arrival = match b.arrival_time.or(b.departure_time) {
+                        Some(time) => time.saturating_sub(shift),
+                        None => continue,
+                    };
+
+                    connections.push(Connection {
+                        trip: idx,
+                        from: index[&a.stop.id],
+                        to: index[&b.stop.id],
+                        departure,
+                        arrival: arrival.max(departure),
+                    });
+                    kept = true;
+                }
+
+                if kept {
+                    trip_ids.push(trip.id.clone());
+                }
+            }
+        }
+
+        connections.sort_by_key(|c| (c.departure, c.arrival));
+
+        Self {
+            date,
+            stops,
+            trip_ids,
+            connections,
+            index,
+        }
+    }
+
+    pub fn stop_index(&self, stop_id: &str) -> Option<usize> {
+        self.index.get(stop_id).copied()
+    }
+
+    // Earliest arrival at every stop leaving `source` at `departure`, walking the footpaths
+    // between vehicles, looking at most `max_duration` seconds ahead
+    pub fn earliest_arrivals(
+        &self,
+        footpaths: &Footpaths,
+        source: usize,
+        departure: u32,
+        max_duration: u32,
+    ) -> Vec<Option<u32>> {
+        let mut arrivals = vec![UNREACHED; self.stops.len()];
+        let mut boarded = vec![false; self.trip_ids.len()];
+        let limit = departure.saturating_add(max_duration);
+
+        arrivals[source] = departure;
+        for transfer in footpaths.from(source) {
+            arrivals[transfer.to] = arrivals[transfer.to].min(departure + transfer.seconds);
+        }
+
+        let first = self
+            .connections
+            .partition_point(|c| c.departure < departure);
+
+        for c in self.connections[first..].iter() {
+            if c.departure > limit {
+                break;
+            }
+
+            if !boarded[c.trip] && arrivals[c.from] > c.departure {
+                continue;
+            }
+            boarded[c.trip] = true;
+
+            if c.arrival < arrivals[c.to] && c.arrival <= limit {
+                arrivals[c.to] = c.arrival;
+
+                for transfer in footpaths.from(c.to) {
+                    arrivals[transfer.to] = arrivals[transfer.to].min(c.arrival + transfer.seconds);
+                }
+            }
+        }
+
+        arrivals
+            .into_iter()
+            .map(|time| (time <= limit).then_some(time))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routing::footpaths::Walking;
+    use crate::routing::transfers::FeedTransfer;
+    use crate::utils::feed::fixture_gtfs;
+
+    fn timetable() -> Timetable {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        Timetable::from_gtfs(&fixture_gtfs(), &RouteFilter::default(), date)
+    }
+
+    // No walks but those of `transfers`
+    fn footpaths(timetable: &Timetable, transfers: &[FeedTransfer]) -> Footpaths {
+        let walking = Walking {
+            radius: 0.,
+            ..Walking::default()
+        };
+        Footpaths::new(&timetable.stops, &walking, transfers)
+    }
+
+    fn at(h: u32, m: u32) -> Option<u32> {
+        Some(h * 3600 + m * 60)
+    }
+
+    #[test]
+    fn direct_ride() {
+        let timetable = timetable();
+        let footpaths = footpaths(&timetable, &[]);
+        let stop = |id| timetable.stop_index(id).unwrap();
+
+        let arrivals = timetable.earliest_arrivals(&footpaths, stop("A"), 7 * 3600 + 55 * 60, 3600);
+
+        assert_eq!(arrivals[stop("B")], at(8, 5));
+        assert_eq!(arrivals[stop("C")], at(8, 10));
+        // The bus at 09:00 leaves after the hour looked at
+        assert_eq!(arrivals[stop("D")], None);
+    }
+
+    #[test]
+    fn ride_then_walk() {
+        let timetable = timetable();
+        let walk = FeedTransfer {
+            from_stop_id: String::from("C"),
+            to_stop_id: String::from("D"),
+            transfer_type: Some(2),
+            min_transfer_time: Some(120),
+        };
+        let footpaths = footpaths(&timetable, &[walk]);
+        let stop = |id| timetable.stop_index(id).unwrap();
+
+        let arrivals = timetable.earliest_arrivals(&footpaths, stop("A"), 7 * 3600 + 55 * 60, 3600);
+
+        // Before the direct bus at 09:10
+        assert_eq!(arrivals[stop("D")], Some(8 * 3600 + 12 * 60));
+    }
+
+    #[test]
+    fn yesterdays_trip_runs_past_midnight() {
+        let timetable = timetable();
+        let footpaths = footpaths(&timetable, &[]);
+        let stop = |id| timetable.stop_index(id).unwrap();
+
+        // b3 runs today and yesterday, from 47:55 on yesterday's service
+        assert_eq!(
+            timetable.trip_ids.iter().filter(|id| *id == "b3").count(),
+            2
+        );
+
+        let arrivals =
+            timetable.earliest_arrivals(&footpaths, stop("A"), 23 * 3600 + 50 * 60, 3600);
+
+        assert_eq!(arrivals[stop("B")], at(24, 5));
+        assert_eq!(arrivals[stop("D")], at(24, 15));
+        assert_eq!(arrivals[stop("C")], None);
+    }
+}
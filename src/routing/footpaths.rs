@@ -0,0 +1,249 @@
+use gtfs_structures::Stop;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::transfers::FeedTransfer;
+use super::transfers::Transfer;
+use super::transfers::TransferKind;
+use crate::args::Args;
+use crate::utils::math::haversine;
+
+const RADIUS: f64 = 400.;
+// Meters per second
+const SPEED: f64 = 1.2;
+const METERS_PER_DEGREE: f64 = 111_320.;
+
+// How far and how fast transfers are walked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Walking {
+    pub radius: f64,
+    pub speed: f64,
+}
+
+impl Default for Walking {
+    fn default() -> Self {
+        Self {
+            radius: RADIUS,
+            speed: SPEED,
+        }
+    }
+}
+
+impl Walking {
+    // `--walk-radius=400` in meters, 0 for no walks between separate stops, `--walk-speed=1.2`
+    // in meters per second
+    pub fn from_args(args: &Args) -> Self {
+        let radius: f64 = args.value("walk-radius").unwrap_or(RADIUS);
+        let speed: f64 = args.value("walk-speed").unwrap_or(SPEED);
+
+        if !(radius >= 0. && radius.is_finite()) {
+            panic!("Invalid value for --walk-radius: {radius}");
+        }
+        if !(speed > 0. && speed.is_finite()) {
+            panic!("Invalid value for --walk-speed: {speed}");
+        }
+
+        Self { radius, speed }
+    }
+
+    pub fn seconds(&self, meters: f64) -> u32 {
+        (meters / self.speed).ceil() as u32
+    }
+}
+
+// Transfers between stops by index into `stops`: walks shorter than the radius, between the
+// platforms of a station, and those of `transfers.txt` which take precedence
+#[derive(Debug, Default)]
+pub struct Footpaths {
+    edges: Vec<Vec<Transfer>>,
+}
+
+impl Footpaths {
+    pub fn new(stops: &[Arc<Stop>], walking: &Walking, feed: &[FeedTransfer]) -> Self {
+        let mut transfers: HashMap<(usize, usize), (u32, TransferKind)> = HashMap::new();
+        let between = |a: usize, b: usize| meters(&stops[a], &stops[b]);
+
+        if walking.radius > 0. {
+            for (from, to, distance) in walks(stops, walking.radius) {
+                transfers.insert((from, to), (walking.seconds(distance), TransferKind::Walk));
+            }
+        }
+
+        let index = stops
+            .iter()
+            .enumerate()
+            .map(|(idx, stop)| (stop.id.as_str(), idx))
+            .collect::<HashMap<&str, usize>>();
+
+        // Every platform of a station, and the station itself, reach each other however far
+        let mut stations: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (idx, stop) in stops.iter().enumerate() {
+            if let Some(parent) = stop.parent_station.as_deref().and_then(|id| index.get(id)) {
+                stations
+                    .entry(*parent)
+                    .or_insert_with(|| vec![*parent])
+                    .push(idx);
+            }
+        }
+
+        for members in stations.values() {
+            for from in members.iter().copied() {
+                for to in members.iter().copied().filter(|to| *to != from) {
+                    let seconds = walking.seconds(between(from, to));
+                    transfers.insert((from, to), (seconds, TransferKind::Station));
+                }
+            }
+        }
+
+        for transfer in feed {
+            let (from, to) = match (
+                index.get(transfer.from_stop_id.as_str()),
+                index.get(transfer.to_stop_id.as_str()),
+            ) {
+                (Some(from), Some(to)) if from != to => (*from, *to),
+                _ => continue,
+            };
+
+            if transfer.is_impossible() {
+                transfers.remove(&(from, to));
+                continue;
+            }
+
+            let seconds = transfer
+                .min_transfer_time
+                .unwrap_or_else(|| walking.seconds(between(from, to)));
+            transfers.insert((from, to), (seconds, TransferKind::Feed));
+        }
+
+        let mut edges = vec![vec![]; stops.len()];
+        for ((from, to), (seconds, kind)) in transfers {
+            edges[from].push(Transfer { to, seconds, kind });
+        }
+
+        // The same order every time, whatever the hashing
+        for transfers in edges.iter_mut() {
+            transfers.sort_by_key(|transfer| transfer.to);
+        }
+
+        Self { edges }
+    }
+
+    // Stops reachable from `stop` with the transfer time in seconds
+    pub fn from(&self, stop: usize) -> &[Transfer] {
+        self.edges.get(stop).map_or(&[], Vec::as_slice)
+    }
+
+    // Every transfer with the index of the stop it starts from
+    pub fn links(&self) -> impl Iterator<Item = (usize, &Transfer)> {
+        self.edges
+            .iter()
+            .enumerate()
+            .flat_map(|(from, transfers)| transfers.iter().map(move |transfer| (from, transfer)))
+    }
+}
+
+fn meters(a: &Stop, b: &Stop) -> f64 {
+    match (a.longitude, a.latitude, b.longitude, b.latitude) {
+        (Some(lng0), Some(lat0), Some(lng1), Some(lat1)) => haversine(lng0, lat0, lng1, lat1),
+        _ => 0.,
+    }
+}
+
+// Pairs of stops closer than `radius` meters, which must be positive, with their distance.
+// Stops are hashed into cells as large as the radius, only neighbouring cells are compared
+fn walks(stops: &[Arc<Stop>], radius: f64) -> Vec<(usize, usize, f64)> {
+    let lat0 = stops.iter().find_map(|stop| stop.latitude).unwrap_or(0.);
+    let cell = (
+        radius / (METERS_PER_DEGREE * lat0.to_radians().cos()),
+        radius / METERS_PER_DEGREE,
+    );
+
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (idx, stop) in stops.iter().enumerate() {
+        if let (Some(lng), Some(lat)) = (stop.longitude, stop.latitude) {
+            let key = ((lng / cell.0).floor() as i64, (lat / cell.1).floor() as i64);
+            grid.entry(key).or_default().push(idx);
+        }
+    }
+
+    let mut walks = vec![];
+    for ((cx, cy), members) in grid.iter() {
+        for from in members.iter().copied() {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for to in grid.get(&(cx + dx, cy + dy)).into_iter().flatten().copied() {
+                        let distance = meters(&stops[from], &stops[to]);
+
+                        if to != from && distance <= radius {
+                            walks.push((from, to, distance));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    walks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::feed::fixture_gtfs;
+
+    fn stops() -> Vec<Arc<Stop>> {
+        let mut stops = fixture_gtfs().stops.into_values().collect::<Vec<_>>();
+        stops.sort_by(|a, b| a.id.cmp(&b.id));
+        stops
+    }
+
+    fn walking(radius: f64) -> Walking {
+        Walking { radius, speed: 1. }
+    }
+
+    #[test]
+    fn walks_within_the_radius() {
+        let stops = stops();
+        let footpaths = Footpaths::new(&stops, &walking(1000.), &[]);
+
+        // Alpha and Beta are some 935 m apart, Gamma and Delta are farther from Alpha
+        let from_alpha = footpaths.from(0);
+        assert_eq!(from_alpha.len(), 1);
+        assert_eq!(from_alpha[0].to, 1);
+        assert_eq!(from_alpha[0].kind, TransferKind::Walk);
+        assert!((900..970).contains(&from_alpha[0].seconds));
+
+        assert!(Footpaths::new(&stops, &walking(0.), &[])
+            .links()
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn feed_transfers_take_precedence() {
+        let stops = stops();
+        let feed = [
+            FeedTransfer {
+                from_stop_id: String::from("A"),
+                to_stop_id: String::from("B"),
+                transfer_type: Some(3),
+                min_transfer_time: None,
+            },
+            FeedTransfer {
+                from_stop_id: String::from("C"),
+                to_stop_id: String::from("D"),
+                transfer_type: Some(2),
+                min_transfer_time: Some(90),
+            },
+        ];
+        let footpaths = Footpaths::new(&stops, &walking(1000.), &feed);
+
+        assert!(footpaths.from(0).is_empty());
+        assert_eq!(footpaths.from(1)[0].to, 0);
+
+        // Gamma also walks to Beta
+        let to_delta = footpaths.from(2).iter().find(|t| t.to == 3).unwrap();
+        assert_eq!(to_delta.seconds, 90);
+        assert_eq!(to_delta.kind, TransferKind::Feed);
+    }
+}
@@ -0,0 +1,151 @@
+use chrono::NaiveDate;
+use gtfs_structures::Gtfs;
+use gtfs_structures::Stop;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::runs_on;
+use crate::utils::stop_index::station_id;
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: String,
+    pub name: String,
+    pub longitude: Option<f64>,
+    pub latitude: Option<f64>,
+}
+
+// Vehicles going from one node straight to another
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    // Distinct trips running along the edge
+    pub trips: usize,
+    // Seconds from departure to arrival, the median of every trip
+    pub travel_time: u32,
+}
+
+// Directed graph of consecutive stops of the trips passing a filter, nodes sorted by id and
+// edges by their ends
+#[derive(Debug, Default)]
+pub struct StopGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl StopGraph {
+    // Trips running on `date` if given, with the platforms of a station merged into it if
+    // `stations` is set
+    pub fn from_gtfs(
+        gtfs: &Gtfs,
+        filter: &RouteFilter,
+        date: Option<NaiveDate>,
+        stations: bool,
+    ) -> Self {
+        let node_id = |stop: &Stop| {
+            if stations {
+                station_id(stop).to_string()
+            } else {
+                stop.id.clone()
+            }
+        };
+
+        // Trip ids and travel times of every pair of consecutive nodes
+        let mut pairs: HashMap<(String, String), (HashSet<&str>, Vec<u32>)> = HashMap::new();
+
+        for trip in gtfs.trips.values() {
+            if !filter.keeps_trip(gtfs, trip)
+                || !date.map_or(true, |date| runs_on(gtfs, &trip.service_id, date))
+            {
+                continue;
+            }
+
+            for pair in trip.stop_times.windows(2) {
+                let (a, b) = (&pair[0], &pair[1]);
+                let (from, to) = (node_id(&a.stop), node_id(&b.stop));
+
+                // Platforms of the same station
+                if from == to {
+                    continue;
+                }
+
+                let departure = a.departure_time.or(a.arrival_time);
+                let arrival = b.arrival_time.or(b.departure_time);
+
+                if let (Some(departure), Some(arrival)) = (departure, arrival) {
+                    let (trips, times) = pairs.entry((from, to)).or_default();
+                    trips.insert(&trip.id);
+                    times.push(arrival.saturating_sub(departure));
+                }
+            }
+        }
+
+        let mut ids = pairs
+            .keys()
+            .flat_map(|(from, to)| [from, to])
+            .cloned()
+            .collect::<Vec<String>>();
+        ids.sort();
+        ids.dedup();
+
+        let index = ids
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| (id.clone(), idx))
+            .collect::<HashMap<String, usize>>();
+
+        let nodes = ids
+            .into_iter()
+            .map(|id| {
+                let stop = gtfs.stops.get(&id);
+
+                Node {
+                    name: stop.map_or_else(|| id.clone(), |stop| stop.name.clone()),
+                    longitude: stop.and_then(|stop| stop.longitude),
+                    latitude: stop.and_then(|stop| stop.latitude),
+                    id,
+                }
+            })
+            .collect::<Vec<Node>>();
+
+        let mut edges = pairs
+            .into_iter()
+            .map(|((from, to), (trips, times))| Edge {
+                from: index[&from],
+                to: index[&to],
+                trips: trips.len(),
+                travel_time: median(times),
+            })
+            .collect::<Vec<Edge>>();
+        edges.sort_by_key(|edge| (edge.from, edge.to));
+
+        Self { nodes, edges }
+    }
+}
+
+// Middle value, the mean of the two middle ones for an even count
+fn median(mut values: Vec<u32>) -> u32 {
+    values.sort_unstable();
+
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        ((values[mid - 1] as u64 + values[mid] as u64 + 1) / 2) as u32
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(vec![300, 60, 120]), 120);
+        assert_eq!(median(vec![60, 120, 300, 90]), 105);
+        assert_eq!(median(vec![60, 61]), 61);
+        assert_eq!(median(vec![42]), 42);
+    }
+}
@@ -0,0 +1,313 @@
+use super::csa::Timetable;
+use super::footpaths::Footpaths;
+
+const UNREACHED: u32 = u32::MAX;
+
+// How the earliest arrival at a stop was reached
+#[derive(Debug, Clone, Copy)]
+enum Pointer {
+    // Boarded at the `enter` connection and got off after the `exit` one
+    Ride {
+        enter: usize,
+        exit: usize,
+    },
+    Walk {
+        from: usize,
+        departure: u32,
+        arrival: u32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Leg {
+    Ride {
+        trip_id: String,
+        // Stops from boarding to alighting, by timetable index
+        stops: Vec<usize>,
+        departure: u32,
+        arrival: u32,
+    },
+    Walk {
+        from: usize,
+        to: usize,
+        departure: u32,
+        arrival: u32,
+    },
+}
+
+impl Leg {
+    pub fn departure(&self) -> u32 {
+        match self {
+            Leg::Ride { departure, .. } | Leg::Walk { departure, .. } => *departure,
+        }
+    }
+
+    pub fn arrival(&self) -> u32 {
+        match self {
+            Leg::Ride { arrival, .. } | Leg::Walk { arrival, .. } => *arrival,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Journey {
+    pub legs: Vec<Leg>,
+}
+
+impl Journey {
+    pub fn departure(&self) -> u32 {
+        self.legs.first().map_or(0, Leg::departure)
+    }
+
+    pub fn arrival(&self) -> u32 {
+        self.legs.last().map_or(0, Leg::arrival)
+    }
+
+    pub fn rides(&self) -> impl Iterator<Item = &Leg> {
+        self.legs
+            .iter()
+            .filter(|leg| matches!(leg, Leg::Ride { .. }))
+    }
+
+    pub fn transfers(&self) -> usize {
+        self.rides().count().saturating_sub(1)
+    }
+}
+
+impl Timetable {
+    // Fastest journey from any of `sources` to any of `targets` leaving at `departure`, by the
+    // Connection Scan Algorithm with the footpaths walked between vehicles
+    pub fn journey(
+        &self,
+        footpaths: &Footpaths,
+        sources: &[usize],
+        targets: &[usize],
+        departure: u32,
+    ) -> Option<Journey> {
+        let mut arrivals = vec![UNREACHED; self.stops.len()];
+        let mut pointers: Vec<Option<Pointer>> = vec![None; self.stops.len()];
+        // Connection each trip was boarded at
+        let mut boarded: Vec<Option<usize>> = vec![None; self.trip_ids.len()];
+
+        let walk =
+            |arrivals: &mut [u32], pointers: &mut [Option<Pointer>], from: usize, time: u32| {
+                for transfer in footpaths.from(from) {
+                    let arrival = time + transfer.seconds;
+
+                    if arrival < arrivals[transfer.to] {
+                        arrivals[transfer.to] = arrival;
+                        pointers[transfer.to] = Some(Pointer::Walk {
+                            from,
+                            departure: time,
+                            arrival,
+                        });
+                    }
+                }
+            };
+
+        for source in sources.iter().copied() {
+            arrivals[source] = departure;
+        }
+        for source in sources.iter().copied() {
+            walk(&mut arrivals, &mut pointers, source, departure);
+        }
+
+        let best = |arrivals: &[u32]| {
+            targets
+                .iter()
+                .map(|t| arrivals[*t])
+                .min()
+                .unwrap_or(UNREACHED)
+        };
+        let first = self
+            .connections
+            .partition_point(|c| c.departure < departure);
+
+        for (idx, c) in self.connections.iter().enumerate().skip(first) {
+            // Nothing departing later can arrive earlier
+            if c.departure >= best(&arrivals) {
+                break;
+            }
+
+            let enter = match boarded[c.trip] {
+                Some(enter) => enter,
+                None if arrivals[c.from] <= c.departure => {
+                    boarded[c.trip] = Some(idx);
+                    idx
+                }
+                None => continue,
+            };
+
+            if c.arrival < arrivals[c.to] {
+                arrivals[c.to] = c.arrival;
+                pointers[c.to] = Some(Pointer::Ride { enter, exit: idx });
+                walk(&mut arrivals, &mut pointers, c.to, c.arrival);
+            }
+        }
+
+        let target = targets
+            .iter()
+            .copied()
+            .filter(|t| arrivals[*t] != UNREACHED)
+            .min_by_key(|t| arrivals[*t])?;
+
+        let mut legs = vec![];
+        let mut stop = target;
+
+        while !sources.contains(&stop) {
+            // Every step goes back in time, a cycle would mean corrupt pointers
+            if legs.len() > self.stops.len() {
+                return None;
+            }
+
+            match pointers[stop]? {
+                Pointer::Ride { enter, exit } => {
+                    let trip = self.connections[enter].trip;
+                    let mut stops = vec![self.connections[enter].from];
+                    stops.extend(
+                        self.connections[enter..=exit]
+                            .iter()
+                            .filter(|c| c.trip == trip)
+                            .map(|c| c.to),
+                    );
+
+                    legs.push(Leg::Ride {
+                        trip_id: self.trip_ids[trip].clone(),
+                        stops,
+                        departure: self.connections[enter].departure,
+                        arrival: self.connections[exit].arrival,
+                    });
+                    stop = self.connections[enter].from;
+                }
+                Pointer::Walk {
+                    from,
+                    departure,
+                    arrival,
+                } => {
+                    legs.push(Leg::Walk {
+                        from,
+                        to: stop,
+                        departure,
+                        arrival,
+                    });
+                    stop = from;
+                }
+            }
+        }
+
+        legs.reverse();
+        Some(Journey { legs })
+    }
+
+    // The stop with that id, or every stop of that name ignoring case
+    pub fn find_stops(&self, query: &str) -> Vec<usize> {
+        if let Some(idx) = self.stop_index(query) {
+            return vec![idx];
+        }
+
+        let query = query.to_lowercase();
+
+        self.stops
+            .iter()
+            .enumerate()
+            .filter(|(_, stop)| stop.name.to_lowercase() == query)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::routing::footpaths::Walking;
+    use crate::routing::transfers::FeedTransfer;
+    use crate::utils::feed::fixture_gtfs;
+    use crate::utils::route_filter::RouteFilter;
+
+    fn timetable() -> Timetable {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        Timetable::from_gtfs(&fixture_gtfs(), &RouteFilter::default(), date)
+    }
+
+    // No walks but those of `transfers`
+    fn footpaths(timetable: &Timetable, transfers: &[FeedTransfer]) -> Footpaths {
+        let walking = Walking {
+            radius: 0.,
+            ..Walking::default()
+        };
+        Footpaths::new(&timetable.stops, &walking, transfers)
+    }
+
+    fn at(h: u32, m: u32) -> u32 {
+        h * 3600 + m * 60
+    }
+
+    #[test]
+    fn ride_is_rebuilt_from_pointers() {
+        let timetable = timetable();
+        let footpaths = footpaths(&timetable, &[]);
+        let stop = |id| timetable.stop_index(id).unwrap();
+
+        let journey = timetable
+            .journey(&footpaths, &[stop("A")], &[stop("C")], at(7, 55))
+            .unwrap();
+
+        assert_eq!(journey.legs.len(), 1);
+        match &journey.legs[0] {
+            Leg::Ride {
+                trip_id,
+                stops,
+                departure,
+                arrival,
+            } => {
+                assert_eq!(trip_id, "t1");
+                assert_eq!(stops, &vec![stop("A"), stop("B"), stop("C")]);
+                assert_eq!(*departure, at(8, 0));
+                assert_eq!(*arrival, at(8, 10));
+            }
+            leg => panic!("Expected a ride, got {leg:?}"),
+        }
+        assert_eq!(journey.transfers(), 0);
+    }
+
+    #[test]
+    fn ride_then_walk() {
+        let timetable = timetable();
+        let walk = FeedTransfer {
+            from_stop_id: String::from("C"),
+            to_stop_id: String::from("D"),
+            transfer_type: Some(2),
+            min_transfer_time: Some(120),
+        };
+        let footpaths = footpaths(&timetable, &[walk]);
+        let stop = |id| timetable.stop_index(id).unwrap();
+
+        let journey = timetable
+            .journey(&footpaths, &[stop("A")], &[stop("D")], at(7, 55))
+            .unwrap();
+
+        assert_eq!(journey.legs.len(), 2);
+        assert!(matches!(&journey.legs[0], Leg::Ride { trip_id, .. } if trip_id == "t1"));
+        assert!(matches!(
+            journey.legs[1],
+            Leg::Walk { from, to, .. } if from == stop("C") && to == stop("D")
+        ));
+        assert_eq!(journey.departure(), at(8, 0));
+        assert_eq!(journey.arrival(), at(8, 12));
+    }
+
+    #[test]
+    fn source_is_target() {
+        let timetable = timetable();
+        let footpaths = footpaths(&timetable, &[]);
+        let a = timetable.stop_index("A").unwrap();
+
+        let journey = timetable
+            .journey(&footpaths, &[a], &[a], at(7, 55))
+            .unwrap();
+
+        assert!(journey.legs.is_empty());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod centrality;
+pub mod csa;
+pub mod footpaths;
+pub mod graph;
+pub mod journey;
+pub mod plan;
+pub mod transfers;
@@ -0,0 +1,147 @@
+use chrono::Local;
+use chrono::Timelike;
+use gtfs_structures::Gtfs;
+
+use super::csa::Timetable;
+use super::footpaths::Footpaths;
+use super::footpaths::Walking;
+use super::journey::Journey;
+use super::journey::Leg;
+use super::transfers::load_transfers;
+use crate::args::Args;
+use crate::constants::GTFS_URL;
+use crate::utils::feed::load_gtfs;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::format_time;
+use crate::utils::service::parse_date;
+use crate::utils::service::parse_time;
+
+// `bp_viz plan --from=<stop id or name> --to=<stop id or name> [--time=HH:MM] [--date=YYYYMMDD]
+// [--count=N] [--walk-radius=m] [--walk-speed=m/s] and the route filters`
+pub fn run(args: &Args) {
+    let (from, to) = match (args.get("from"), args.get("to")) {
+        (Some(from), Some(to)) => (from, to),
+        _ => {
+            eprintln!("Usage: bp_viz plan --from=<stop> --to=<stop> [--time=HH:MM] [--date=YYYYMMDD] [--count=N]");
+            return;
+        }
+    };
+
+    let date = args.get("date").map_or(Local::now().date_naive(), |date| {
+        parse_date(date).unwrap_or_else(|| panic!("Invalid value for --date: {date}"))
+    });
+    let mut time = args.get("time").map_or_else(
+        || Local::now().num_seconds_from_midnight(),
+        |time| parse_time(time).unwrap_or_else(|| panic!("Invalid value for --time: {time}")),
+    );
+    let count = args.value("count").unwrap_or(1);
+
+    let gtfs = load_gtfs(GTFS_URL, args);
+    let timetable = Timetable::from_gtfs(&gtfs, &RouteFilter::from_args(args), date);
+    let footpaths = Footpaths::new(
+        &timetable.stops,
+        &Walking::from_args(args),
+        &load_transfers(GTFS_URL),
+    );
+
+    let sources = timetable.find_stops(from);
+    let targets = timetable.find_stops(to);
+
+    if sources.is_empty() || targets.is_empty() {
+        let missing = if sources.is_empty() { from } else { to };
+        eprintln!("No stop with the id or name {missing}");
+        return;
+    }
+
+    for _ in 0..count {
+        let journey = match timetable.journey(&footpaths, &sources, &targets, time) {
+            Some(journey) => journey,
+            None => {
+                println!(
+                    "No connection from {from} to {to} after {}",
+                    format_time(time)
+                );
+                return;
+            }
+        };
+
+        if journey.legs.is_empty() {
+            println!("{from} and {to} are the same stop, already there");
+            return;
+        }
+
+        for line in itinerary(&gtfs, &timetable, &journey) {
+            println!("{line}");
+        }
+        println!();
+
+        // The next journey leaves after this one's first vehicle, a walk
+        // alone is the same whenever it starts
+        match journey.rides().next() {
+            Some(ride) => time = ride.departure() + 1,
+            None => break,
+        }
+    }
+}
+
+// Summary line followed by one line per leg
+pub fn itinerary(gtfs: &Gtfs, timetable: &Timetable, journey: &Journey) -> Vec<String> {
+    let name = |idx: usize| timetable.stops[idx].name.as_str();
+    let minutes = |seconds: u32| (seconds + 59) / 60;
+
+    let transfers = match journey.transfers() {
+        1 => String::from("1 transfer"),
+        n => format!("{n} transfers"),
+    };
+
+    let mut lines = vec![format!(
+        "{} – {} ({} min, {transfers})",
+        format_time(journey.departure()),
+        format_time(journey.arrival()),
+        minutes(journey.arrival() - journey.departure()),
+    )];
+
+    for leg in journey.legs.iter() {
+        let line = match leg {
+            Leg::Ride {
+                trip_id,
+                stops,
+                departure,
+                arrival,
+            } => {
+                let trip = gtfs.trips.get(trip_id);
+                let route = trip
+                    .and_then(|trip| gtfs.routes.get(&trip.route_id))
+                    .map_or("?", |route| route.short_name.as_str());
+                let headsign = trip
+                    .and_then(|trip| trip.trip_headsign.as_deref())
+                    .unwrap_or_else(|| name(*stops.last().unwrap()));
+
+                format!(
+                    "  {} {route} ► {headsign} from {} to {}, {} stops, arrives {}",
+                    format_time(*departure),
+                    name(stops[0]),
+                    name(*stops.last().unwrap()),
+                    stops.len() - 1,
+                    format_time(*arrival),
+                )
+            }
+            Leg::Walk {
+                from,
+                to,
+                departure,
+                arrival,
+            } => format!(
+                "  {} walk {} min from {} to {}",
+                format_time(*departure),
+                minutes(arrival - departure),
+                name(*from),
+                name(*to),
+            ),
+        };
+
+        lines.push(line);
+    }
+
+    lines
+}
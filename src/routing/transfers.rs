@@ -0,0 +1,64 @@
+use serde::Deserialize;
+use std::path::Path;
+
+// A row of the feed's optional `transfers.txt`
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedTransfer {
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    // 0 recommended, 1 timed, 2 minimum time, 3 impossible
+    #[serde(default)]
+    pub transfer_type: Option<u8>,
+    #[serde(default)]
+    pub min_transfer_time: Option<u32>,
+}
+
+impl FeedTransfer {
+    pub fn is_impossible(&self) -> bool {
+        self.transfer_type == Some(3)
+    }
+}
+
+// Where a transfer between two stops comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransferKind {
+    // Closer than the walking radius
+    Walk,
+    // Platforms of the same parent station, or a platform and its station
+    Station,
+    // Listed in `transfers.txt`
+    Feed,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Transfer {
+    pub to: usize,
+    pub seconds: u32,
+    pub kind: TransferKind,
+}
+
+// `transfers.txt` of an unzipped feed, empty if there is none
+pub fn load_transfers(dir: &str) -> Vec<FeedTransfer> {
+    let path = Path::new(dir).join("transfers.txt");
+
+    if !path.is_file() {
+        return vec![];
+    }
+
+    let mut reader = match csv::Reader::from_path(&path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            eprintln!("Couldn't read {}: {e}", path.display());
+            return vec![];
+        }
+    };
+
+    reader
+        .deserialize()
+        .enumerate()
+        .filter_map(|(idx, row)| {
+            row.map_err(|e| eprintln!("Skipping row {} of transfers: {e}", idx + 1))
+                .ok()
+        })
+        .collect()
+}
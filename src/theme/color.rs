@@ -0,0 +1,133 @@
+use nannou::color::Rgb8;
+use serde::Deserialize;
+use serde::Deserializer;
+
+pub fn parse_hex(hex: &str) -> Option<Rgb8> {
+    let hex = hex.trim().trim_start_matches('#');
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+
+    Some(Rgb8::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub fn deserialize_hex<'de, D>(deserializer: D) -> Result<Rgb8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let hex = String::deserialize(deserializer)?;
+    parse_hex(&hex).ok_or_else(|| serde::de::Error::custom(format!("invalid color {hex}")))
+}
+
+fn to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.;
+
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(c: f32) -> u8 {
+    let c = if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    };
+
+    (c.clamp(0., 1.) * 255.).round() as u8
+}
+
+// WCAG 2 relative luminance
+pub fn luminance(color: Rgb8) -> f32 {
+    0.2126 * to_linear(color.red) + 0.7152 * to_linear(color.green) + 0.0722 * to_linear(color.blue)
+}
+
+// WCAG 2 contrast ratio, from 1 (same color) to 21 (black on white)
+pub fn contrast(a: Rgb8, b: Rgb8) -> f32 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+// Perceptual lightness, chroma and hue (in radians), see https://bottosson.github.io/posts/oklab/
+#[derive(Debug, Clone, Copy)]
+pub struct Oklch {
+    pub l: f32,
+    pub c: f32,
+    pub h: f32,
+}
+
+impl Oklch {
+    pub fn from_rgb(color: Rgb8) -> Self {
+        let (r, g, b) = (
+            to_linear(color.red),
+            to_linear(color.green),
+            to_linear(color.blue),
+        );
+
+        let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+        let lightness = 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s;
+        let a = 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s;
+        let b = 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s;
+
+        Self {
+            l: lightness,
+            c: a.hypot(b),
+            h: b.atan2(a),
+        }
+    }
+
+    fn to_linear_rgb(self) -> [f32; 3] {
+        let (a, b) = (self.c * self.h.cos(), self.c * self.h.sin());
+
+        let l = (self.l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+        let m = (self.l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+        let s = (self.l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+        [
+            4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+            -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+            -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+        ]
+    }
+
+    // Reduces chroma until the color fits in sRGB, keeping lightness and hue
+    pub fn to_rgb(self) -> Rgb8 {
+        let in_gamut = |c: Oklch| {
+            c.to_linear_rgb()
+                .iter()
+                .all(|v| (-1e-4..=1. + 1e-4).contains(v))
+        };
+
+        let mut color = Self {
+            l: self.l.clamp(0., 1.),
+            ..self
+        };
+
+        if !in_gamut(color) {
+            let (mut lo, mut hi) = (0., color.c);
+
+            for _ in 0..24 {
+                let mid = (lo + hi) / 2.;
+
+                if in_gamut(Self { c: mid, ..color }) {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+
+            color.c = lo;
+        }
+
+        let [r, g, b] = color.to_linear_rgb();
+        Rgb8::new(from_linear(r), from_linear(g), from_linear(b))
+    }
+}
@@ -0,0 +1,245 @@
+pub mod color;
+
+use nannou::color::Rgb8;
+use nannou::text::font;
+use nannou::text::Font;
+use serde::Deserialize;
+use std::path::Path;
+use std::path::PathBuf;
+
+use self::color::deserialize_hex;
+use self::color::Oklch;
+use crate::args::Args;
+
+pub const PRESETS: [&str; 3] = ["dark", "light", "print"];
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub background: Rgb8,
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub foreground: Rgb8,
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub muted: Rgb8,
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub water: Rgb8,
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub land: Rgb8,
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub road: Rgb8,
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub boundary: Rgb8,
+    // OKLCH lightness of backgrounds derived from a route color
+    pub route_lightness: f32,
+    // Share of the route color's chroma kept in those backgrounds
+    pub route_chroma: f32,
+    // Minimum WCAG contrast ratio of text against its background
+    pub min_contrast: f32,
+    pub line_weight: f32,
+    pub stop_radius: f32,
+    pub dot_radius: f32,
+    pub font: Option<PathBuf>,
+    #[serde(skip)]
+    typeface: Option<Font>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    name: Option<String>,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            name: String::from("dark"),
+            background: Rgb8::new(0, 0, 0),
+            foreground: Rgb8::new(255, 255, 255),
+            muted: Rgb8::new(26, 26, 26),
+            water: Rgb8::new(14, 30, 46),
+            land: Rgb8::new(16, 22, 16),
+            road: Rgb8::new(40, 40, 40),
+            boundary: Rgb8::new(54, 54, 54),
+            route_lightness: 0.22,
+            route_chroma: 0.5,
+            min_contrast: 4.5,
+            line_weight: 2.,
+            stop_radius: 8.,
+            dot_radius: 1.,
+            font: None,
+            typeface: None,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            name: String::from("light"),
+            background: Rgb8::new(250, 250, 250),
+            foreground: Rgb8::new(17, 17, 17),
+            muted: Rgb8::new(220, 220, 220),
+            water: Rgb8::new(207, 227, 243),
+            land: Rgb8::new(232, 240, 226),
+            road: Rgb8::new(226, 226, 226),
+            boundary: Rgb8::new(200, 200, 200),
+            route_lightness: 0.96,
+            route_chroma: 0.2,
+            ..Self::dark()
+        }
+    }
+
+    pub fn print() -> Self {
+        Self {
+            name: String::from("print"),
+            background: Rgb8::new(255, 255, 255),
+            foreground: Rgb8::new(0, 0, 0),
+            muted: Rgb8::new(200, 200, 200),
+            water: Rgb8::new(221, 233, 243),
+            land: Rgb8::new(238, 242, 232),
+            road: Rgb8::new(215, 215, 215),
+            boundary: Rgb8::new(190, 190, 190),
+            route_lightness: 1.,
+            route_chroma: 0.,
+            min_contrast: 7.,
+            line_weight: 3.,
+            dot_radius: 1.5,
+            ..Self::dark()
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "print" => Some(Self::print()),
+            _ => None,
+        }
+    }
+
+    // A preset name or the path of a TOML file overriding the dark preset
+    pub fn load(spec: &str) -> Self {
+        let theme = Self::preset(spec).unwrap_or_else(|| {
+            let raw = std::fs::read_to_string(spec)
+                .unwrap_or_else(|e| panic!("Couldn't read theme {spec}: {e}"));
+
+            let mut theme = toml::from_str::<Self>(&raw)
+                .unwrap_or_else(|e| panic!("Invalid theme {spec}: {e}"));
+
+            // Named after the file unless it says otherwise, not the dark preset it overrides
+            let named = toml::from_str::<Named>(&raw)
+                .ok()
+                .and_then(|named| named.name);
+            theme.name = named.unwrap_or_else(|| {
+                Path::new(spec)
+                    .file_stem()
+                    .map_or(spec.to_string(), |stem| stem.to_string_lossy().into_owned())
+            });
+            theme
+        });
+
+        theme.with_typeface()
+    }
+
+    pub fn from_args(args: &Args) -> Self {
+        args.get("theme").map(Self::load).unwrap_or_default()
+    }
+
+    // The preset after this one, wrapping around
+    pub fn next_preset(&self) -> Self {
+        let idx = PRESETS
+            .iter()
+            .position(|p| *p == self.name)
+            .map_or(0, |i| i + 1);
+        Self::preset(PRESETS[idx % PRESETS.len()]).unwrap()
+    }
+
+    fn with_typeface(mut self) -> Self {
+        self.typeface = self.font.as_ref().map(|path| {
+            font::from_file(path)
+                .unwrap_or_else(|e| panic!("Couldn't load font {}: {e}", path.display()))
+        });
+
+        self
+    }
+
+    pub fn typeface(&self) -> Font {
+        self.typeface.clone().unwrap_or_else(font::default_notosans)
+    }
+
+    // Background tinted with the hue of a route color
+    pub fn route_background(&self, route_color: Rgb8) -> Rgb8 {
+        let color = Oklch::from_rgb(route_color);
+
+        Oklch {
+            l: self.route_lightness,
+            c: color.c * self.route_chroma,
+            h: color.h,
+        }
+        .to_rgb()
+    }
+
+    // Sequential scale for `t` in 0..1, from a dim blue close to the background to a
+    // bright orange close to the foreground
+    pub fn heat_color(&self, t: f32) -> Rgb8 {
+        let (background, foreground) = (
+            Oklch::from_rgb(self.background),
+            Oklch::from_rgb(self.foreground),
+        );
+        let t = t.clamp(0., 1.);
+
+        Oklch {
+            l: background.l + (foreground.l - background.l) * (0.3 + 0.7 * t),
+            c: 0.08 + 0.1 * t,
+            h: (260. - 200. * t).to_radians(),
+        }
+        .to_rgb()
+    }
+
+    // `preferred` if it's readable on `background`, otherwise black or white
+    pub fn text_color(&self, preferred: Rgb8, background: Rgb8) -> Rgb8 {
+        if color::contrast(preferred, background) >= self.min_contrast {
+            return preferred;
+        }
+
+        let black = Rgb8::new(0, 0, 0);
+        let white = Rgb8::new(255, 255, 255);
+
+        if color::contrast(black, background) > color::contrast(white, background) {
+            black
+        } else {
+            white
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_themes_are_named_apart_from_the_presets() {
+        let dir = std::env::temp_dir();
+        let stem = format!("bp_viz_theme_{}", std::process::id());
+        let unnamed = dir.join(format!("{stem}.toml"));
+        let named = dir.join(format!("{stem}_named.toml"));
+        std::fs::write(&unnamed, "background = \"#102030\"\n").unwrap();
+        std::fs::write(&named, "name = \"dusk\"\nbackground = \"#102030\"\n").unwrap();
+
+        let from_stem = Theme::load(unnamed.to_str().unwrap());
+        let from_key = Theme::load(named.to_str().unwrap());
+        std::fs::remove_file(&unnamed).unwrap();
+        std::fs::remove_file(&named).unwrap();
+
+        assert_eq!(from_stem.name, stem);
+        assert_eq!(from_key.name, "dusk");
+        // Still overriding the dark preset
+        assert_eq!(from_key.foreground, Theme::dark().foreground);
+        assert_eq!(from_stem.next_preset().name, "dark");
+    }
+}
@@ -1,3 +1,15 @@
 pub mod boundaries;
+pub mod camera;
+pub mod clip;
+pub mod extent;
+pub mod feed;
+pub mod frequency;
+pub mod input;
+pub mod labels;
 pub mod math;
 pub mod meta;
+pub mod raster;
+pub mod route_filter;
+pub mod route_types;
+pub mod service;
+pub mod stop_index;
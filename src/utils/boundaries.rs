@@ -1,7 +1,9 @@
+use std::str::FromStr;
+
 use crate::constants::CANVAS_HEIGHT;
 use crate::constants::CANVAS_WIDTH;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Boundaries {
     pub min_lng: f64,
     pub max_lng: f64,
@@ -42,6 +44,34 @@ impl Boundaries {
         }
     }
 
+    // Ignores `percentile` percent of the coordinates on each side, so a few stray stops
+    // don't blow up the extent
+    pub fn from_percentiles(coords: Vec<(f64, f64)>, percentile: f64) -> Self {
+        if coords.is_empty() {
+            return Self::default();
+        }
+
+        let mut lngs = coords.iter().map(|c| c.0).collect::<Vec<f64>>();
+        let mut lats = coords.iter().map(|c| c.1).collect::<Vec<f64>>();
+        lngs.sort_by(f64::total_cmp);
+        lats.sort_by(f64::total_cmp);
+
+        let last = coords.len() - 1;
+        let low = ((last as f64 * percentile.clamp(0., 50.) / 100.).round() as usize).min(last);
+        let high = last - low;
+
+        Self {
+            min_lng: lngs[low],
+            max_lng: lngs[high],
+            min_lat: lats[low],
+            max_lat: lats[high],
+        }
+    }
+
+    pub fn contains(&self, lng: f64, lat: f64) -> bool {
+        (self.min_lng..=self.max_lng).contains(&lng) && (self.min_lat..=self.max_lat).contains(&lat)
+    }
+
     pub fn width(&self) -> f64 {
         self.max_lng - self.min_lng
     }
@@ -80,3 +110,25 @@ impl Default for Boundaries {
         }
     }
 }
+
+// `min_lng,min_lat,max_lng,max_lat`, the usual bbox order
+impl FromStr for Boundaries {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>().map_err(|e| e.to_string()))
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        match values[..] {
+            [min_lng, min_lat, max_lng, max_lat] => Ok(Self {
+                min_lng: min_lng.min(max_lng),
+                max_lng: min_lng.max(max_lng),
+                min_lat: min_lat.min(max_lat),
+                max_lat: min_lat.max(max_lat),
+            }),
+            _ => Err(format!("Expected min_lng,min_lat,max_lng,max_lat, got {s}")),
+        }
+    }
+}
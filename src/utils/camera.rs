@@ -0,0 +1,114 @@
+use nannou::event::Key;
+use nannou::event::MouseButton;
+
+use crate::utils::input::Input;
+
+const ZOOM_STEP: f64 = 1.25;
+const PAN_STEP: f64 = 40.;
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 256.;
+
+// Pan and zoom on top of the fitted projection: screen = (canvas - center) * zoom
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub x: f64,
+    pub y: f64,
+    pub zoom: f64,
+    // Mouse position and camera center when the drag started
+    drag: Option<((f64, f64), (f64, f64))>,
+    // Whether the last press moved the view, to tell drags from clicks
+    dragged: bool,
+}
+
+impl Camera {
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        ((x - self.x) * self.zoom, (y - self.y) * self.zoom)
+    }
+
+    pub fn unapply(&self, x: f64, y: f64) -> (f64, f64) {
+        (x / self.zoom + self.x, y / self.zoom + self.y)
+    }
+
+    pub fn was_dragged(&self) -> bool {
+        self.dragged
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    // Zooms by `factor` keeping the point under (x, y) on screen in place
+    pub fn zoom_at(&mut self, x: f64, y: f64, factor: f64) {
+        let (wx, wy) = self.unapply(x, y);
+
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.x = wx - x / self.zoom;
+        self.y = wy - y / self.zoom;
+    }
+
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.x -= dx / self.zoom;
+        self.y -= dy / self.zoom;
+    }
+
+    // Mouse drag to pan, wheel to zoom around the cursor at `mouse`, arrows and +/- on the
+    // keyboard and 0 to fit again, returns whether the view changed
+    pub fn event(&mut self, input: &Input, mouse: (f64, f64)) -> bool {
+        match *input {
+            Input::MousePressed(MouseButton::Left) => {
+                self.drag = Some((mouse, (self.x, self.y)));
+                self.dragged = false;
+                false
+            }
+            Input::MouseReleased(MouseButton::Left) => {
+                self.drag = None;
+                false
+            }
+            Input::MouseMoved(x, y) => match self.drag {
+                Some(((mx, my), (cx, cy))) => {
+                    self.x = cx - (x - mx) / self.zoom;
+                    self.y = cy - (y - my) / self.zoom;
+                    self.dragged = true;
+                    true
+                }
+                None => false,
+            },
+            Input::Scroll(amount) => {
+                self.zoom_at(mouse.0, mouse.1, ZOOM_STEP.powf(amount));
+                true
+            }
+            Input::Key(key) => {
+                match key {
+                    Key::Left => self.pan(PAN_STEP, 0.),
+                    Key::Right => self.pan(-PAN_STEP, 0.),
+                    Key::Up => self.pan(0., -PAN_STEP),
+                    Key::Down => self.pan(0., PAN_STEP),
+                    _ => return false,
+                }
+                true
+            }
+            Input::Char(c) => {
+                match c {
+                    '+' | '=' => self.zoom_at(0., 0., ZOOM_STEP),
+                    '-' => self.zoom_at(0., 0., 1. / ZOOM_STEP),
+                    '0' => self.reset(),
+                    _ => return false,
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            x: 0.,
+            y: 0.,
+            zoom: 1.,
+            drag: None,
+            dragged: false,
+        }
+    }
+}
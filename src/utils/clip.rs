@@ -0,0 +1,377 @@
+use ::geojson::GeoJson;
+use ::geojson::Value;
+use gtfs_structures::Gtfs;
+use gtfs_structures::Shape;
+use gtfs_structures::StopTime;
+use gtfs_structures::Trip;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+
+use crate::utils::boundaries::Boundaries;
+
+// Rough outlines of well known areas, as (lng, lat) rings
+const PRESETS: [(&str, &[(f64, f64)]); 2] = [
+    // Inside the Hungária körút ring, closed through Buda along the Budai körút
+    (
+        "hungaria",
+        &[
+            (19.0577, 47.5350),
+            (19.0690, 47.5310),
+            (19.1030, 47.5060),
+            (19.1050, 47.4770),
+            (19.0690, 47.4660),
+            (19.0600, 47.4640),
+            (19.0300, 47.4680),
+            (19.0080, 47.4900),
+            (19.0050, 47.5100),
+            (19.0250, 47.5300),
+            (19.0365, 47.5405),
+        ],
+    ),
+    // Inside the Nagykörút, closed through Buda from Petőfi bridge to Margit bridge
+    (
+        "nagykorut",
+        &[
+            (19.0485, 47.5135),
+            (19.0570, 47.5105),
+            (19.0640, 47.5050),
+            (19.0705, 47.4965),
+            (19.0710, 47.4860),
+            (19.0670, 47.4790),
+            (19.0595, 47.4780),
+            (19.0470, 47.4775),
+            (19.0245, 47.5005),
+            (19.0230, 47.5075),
+            (19.0400, 47.5150),
+        ],
+    ),
+];
+
+// Area the feed gets clipped to before anything is drawn
+#[derive(Debug)]
+pub enum Clip {
+    Bbox(Boundaries),
+    // Even-odd rings, so holes work
+    Polygon(Vec<Vec<(f64, f64)>>),
+}
+
+impl Clip {
+    // A preset name, `min_lng,min_lat,max_lng,max_lat` or the path of a GeoJSON polygon
+    pub fn load(spec: &str) -> Result<Self, Box<dyn Error>> {
+        if let Some((_, ring)) = PRESETS.iter().find(|(name, _)| *name == spec) {
+            return Ok(Self::Polygon(vec![ring.to_vec()]));
+        }
+
+        if let Ok(bbox) = spec.parse::<Boundaries>() {
+            return Ok(Self::Bbox(bbox));
+        }
+
+        let geojson = std::fs::read_to_string(spec)?.parse::<GeoJson>()?;
+
+        let geometries = match geojson {
+            GeoJson::FeatureCollection(collection) => collection
+                .features
+                .into_iter()
+                .filter_map(|f| f.geometry)
+                .collect(),
+            GeoJson::Feature(feature) => feature.geometry.into_iter().collect(),
+            GeoJson::Geometry(geometry) => vec![geometry],
+        };
+
+        let ring = |positions: &Vec<Vec<f64>>| positions.iter().map(|p| (p[0], p[1])).collect();
+
+        let rings = geometries
+            .iter()
+            .flat_map(|geometry| match &geometry.value {
+                Value::Polygon(rings) => rings.iter().map(ring).collect(),
+                Value::MultiPolygon(polygons) => polygons.iter().flatten().map(ring).collect(),
+                _ => vec![],
+            })
+            .collect::<Vec<Vec<(f64, f64)>>>();
+
+        if rings.is_empty() {
+            return Err(format!("No polygon in {spec}").into());
+        }
+
+        Ok(Self::Polygon(rings))
+    }
+
+    pub fn contains(&self, lng: f64, lat: f64) -> bool {
+        match self {
+            Self::Bbox(bbox) => bbox.contains(lng, lat),
+            Self::Polygon(rings) => {
+                let crossings = rings
+                    .iter()
+                    .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)))
+                    .filter(|(a, b)| (a.1 > lat) != (b.1 > lat))
+                    .filter(|(a, b)| lng < a.0 + (lat - a.1) / (b.1 - a.1) * (b.0 - a.0))
+                    .count();
+
+                crossings % 2 == 1
+            }
+        }
+    }
+
+    // Outline of the area as rings of (lng, lat)
+    fn rings(&self) -> Vec<Vec<(f64, f64)>> {
+        match self {
+            Self::Bbox(b) => vec![vec![
+                (b.min_lng, b.min_lat),
+                (b.max_lng, b.min_lat),
+                (b.max_lng, b.max_lat),
+                (b.min_lng, b.max_lat),
+            ]],
+            Self::Polygon(rings) => rings.clone(),
+        }
+    }
+
+    // Where the segment from `a` to `b` crosses the outline, as sorted fractions of its length
+    fn crossings(&self, rings: &[Vec<(f64, f64)>], a: (f64, f64), b: (f64, f64)) -> Vec<f64> {
+        let cross = |(x0, y0): (f64, f64), (x1, y1): (f64, f64)| x0 * y1 - y0 * x1;
+        let r = (b.0 - a.0, b.1 - a.1);
+
+        let mut crossings = rings
+            .iter()
+            .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)))
+            .filter_map(|(c, d)| {
+                let s = (d.0 - c.0, d.1 - c.1);
+                let denom = cross(r, s);
+                if denom == 0. {
+                    return None;
+                }
+
+                let ca = (c.0 - a.0, c.1 - a.1);
+                let (t, u) = (cross(ca, s) / denom, cross(ca, r) / denom);
+
+                // Half open, so a line through a corner only crosses once
+                (t > 0. && t <= 1. && (0. ..1.).contains(&u)).then_some(t)
+            })
+            .collect::<Vec<f64>>();
+
+        crossings.sort_by(f64::total_cmp);
+        crossings
+    }
+
+    // Parts of a polyline inside the area, each point as the index of the segment it's on and
+    // how far along, so the points where the line crosses the outline are kept too
+    fn pieces(&self, points: &[(f64, f64)]) -> Vec<Vec<(usize, f64)>> {
+        let rings = self.rings();
+        let mut pieces = vec![];
+        let mut piece = vec![];
+        let mut inside = points.first().map_or(false, |p| self.contains(p.0, p.1));
+
+        for (idx, point) in points.iter().enumerate() {
+            if idx > 0 {
+                for t in self.crossings(&rings, points[idx - 1], *point) {
+                    piece.push((idx - 1, t));
+                    if inside {
+                        pieces.push(std::mem::take(&mut piece));
+                    }
+                    inside = !inside;
+                }
+            }
+
+            // Crossings right on a vertex may be counted wrong, the point itself decides
+            let contained = self.contains(point.0, point.1);
+            if inside && !contained {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            inside = contained;
+
+            if inside {
+                piece.push((idx, 0.));
+            }
+        }
+
+        pieces.push(piece);
+        pieces.retain(|piece| piece.len() > 1);
+        pieces
+    }
+
+    // Keeps the parts of trips and shapes inside the area, split where they leave it and come
+    // back, with shapes running up to the outline. Then drops trips left with fewer than two
+    // stops, routes left without trips and stops outside, unless they're the parent station
+    // of a stop inside, and stations without any platform left
+    pub fn apply(&self, gtfs: &mut Gtfs) {
+        let inside = |lng: Option<f64>, lat: Option<f64>| match (lng, lat) {
+            (Some(lng), Some(lat)) => self.contains(lng, lat),
+            _ => false,
+        };
+
+        let mut shapes = HashMap::new();
+        for (id, shape) in gtfs.shapes.drain() {
+            let points = shape
+                .iter()
+                .map(|p| (p.longitude, p.latitude))
+                .collect::<Vec<(f64, f64)>>();
+
+            let pieces = self
+                .pieces(&points)
+                .into_iter()
+                .map(|piece| {
+                    piece
+                        .into_iter()
+                        .map(|(idx, t)| match shape.get(idx + 1) {
+                            Some(next) if t > 0. => {
+                                let a = &shape[idx];
+                                let dist_traveled = a
+                                    .dist_traveled
+                                    .zip(next.dist_traveled)
+                                    .map(|(d0, d1)| d0 + (d1 - d0) * t as f32);
+
+                                Shape {
+                                    longitude: a.longitude + (next.longitude - a.longitude) * t,
+                                    latitude: a.latitude + (next.latitude - a.latitude) * t,
+                                    dist_traveled,
+                                    ..a.clone()
+                                }
+                            }
+                            _ => shape[idx].clone(),
+                        })
+                        .collect::<Vec<Shape>>()
+                })
+                .collect::<Vec<Vec<Shape>>>();
+
+            shapes.insert(id, pieces);
+        }
+
+        let mut trips = HashMap::new();
+        for (id, trip) in gtfs.trips.drain() {
+            let mut runs = vec![vec![]];
+            for st in trip.stop_times.iter() {
+                if inside(st.stop.longitude, st.stop.latitude) {
+                    runs.last_mut().unwrap().push(st.clone());
+                } else if !runs.last().unwrap().is_empty() {
+                    runs.push(vec![]);
+                }
+            }
+            runs.retain(|run| run.len() > 1);
+
+            let pieces = trip.shape_id.as_ref().and_then(|id| shapes.get(id));
+            let count = runs.len();
+
+            for (n, run) in runs.into_iter().enumerate() {
+                let shape_id = match (&trip.shape_id, pieces) {
+                    (Some(shape_id), Some(pieces)) if pieces.len() > 1 => {
+                        Some(format!("{shape_id}#{}", closest_piece(pieces, &run) + 1))
+                    }
+                    (shape_id, _) => shape_id.clone(),
+                };
+                let id = if count > 1 {
+                    format!("{id}#{}", n + 1)
+                } else {
+                    id.clone()
+                };
+
+                let trip = Trip {
+                    id: id.clone(),
+                    shape_id,
+                    stop_times: run,
+                    ..trip.clone()
+                };
+                trips.insert(id, trip);
+            }
+        }
+        gtfs.trips = trips;
+
+        // A shape cut in several pieces gets one id for each
+        for (id, pieces) in shapes {
+            if pieces.len() == 1 {
+                gtfs.shapes.insert(id, pieces.into_iter().next().unwrap());
+            } else {
+                for (n, piece) in pieces.into_iter().enumerate() {
+                    gtfs.shapes.insert(format!("{id}#{}", n + 1), piece);
+                }
+            }
+        }
+
+        // Stops outside are still kept as the parent station of a stop inside, stations
+        // only while one of their platforms is
+        let kept = gtfs
+            .stops
+            .values()
+            .filter(|stop| inside(stop.longitude, stop.latitude))
+            .map(|stop| stop.id.clone())
+            .collect::<HashSet<String>>();
+        let stations = gtfs
+            .stops
+            .values()
+            .filter_map(|stop| stop.parent_station.clone())
+            .collect::<HashSet<String>>();
+        let parents = gtfs
+            .stops
+            .values()
+            .filter(|stop| kept.contains(&stop.id))
+            .filter_map(|stop| stop.parent_station.clone())
+            .collect::<HashSet<String>>();
+        gtfs.stops
+            .retain(|id, _| parents.contains(id) || (kept.contains(id) && !stations.contains(id)));
+
+        let routes = gtfs
+            .trips
+            .values()
+            .map(|trip| trip.route_id.clone())
+            .collect::<HashSet<String>>();
+        gtfs.routes.retain(|id, _| routes.contains(id));
+
+        eprintln!(
+            "Clipped the feed to {} stops, {} trips and {} routes",
+            gtfs.stops.len(),
+            gtfs.trips.len(),
+            gtfs.routes.len()
+        );
+    }
+}
+
+// Index of the shape piece running closest to both ends of a part of a trip
+fn closest_piece(pieces: &[Vec<Shape>], run: &[StopTime]) -> usize {
+    let ends = [run.first(), run.last()]
+        .into_iter()
+        .flatten()
+        .filter_map(|st| Some((st.stop.longitude?, st.stop.latitude?)))
+        .collect::<Vec<(f64, f64)>>();
+
+    let gap = |piece: &Vec<Shape>| {
+        ends.iter()
+            .map(|(lng, lat)| {
+                piece
+                    .iter()
+                    .map(|p| (p.longitude - lng).powi(2) + (p.latitude - lat).powi(2))
+                    .fold(f64::MAX, f64::min)
+            })
+            .sum::<f64>()
+    };
+
+    (0..pieces.len())
+        .min_by(|a, b| gap(&pieces[*a]).total_cmp(&gap(&pieces[*b])))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_end_on_the_outline() {
+        let clip = Clip::Polygon(vec![vec![(0., 0.), (10., 0.), (10., 10.), (0., 10.)]]);
+        // Out and back in, out again, then across the whole area from outside
+        let points = [
+            (5., 5.),
+            (15., 5.),
+            (5., 6.),
+            (5., 15.),
+            (-5., 5.),
+            (20., 5.),
+        ];
+
+        assert_eq!(
+            clip.pieces(&points),
+            vec![
+                vec![(0, 0.), (0, 0.5)],
+                vec![(1, 0.5), (2, 0.), (2, 4. / 9.)],
+                vec![(4, 0.2), (4, 0.6)],
+            ]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use gtfs_structures::Gtfs;
+use gtfs_structures::Stop;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::args::Args;
+use crate::utils::boundaries::Boundaries;
+use crate::utils::math::haversine;
+use crate::utils::route_types::basic_route_type;
+use crate::utils::route_types::parse_route_type;
+use crate::utils::route_types::route_type_code;
+
+// A stop with no other stop this close is probably mis-geocoded
+const ISOLATED_DISTANCE: f64 = 15_000.;
+
+// Rough extents of the countries an agency's timezone points to
+const COUNTRIES: [(&str, &str, (f64, f64, f64, f64)); 6] = [
+    ("Europe/Budapest", "Hungary", (16.11, 45.74, 22.90, 48.59)),
+    ("Europe/Vienna", "Austria", (9.53, 46.37, 17.16, 49.02)),
+    (
+        "Europe/Bratislava",
+        "Slovakia",
+        (16.83, 47.73, 22.57, 49.61),
+    ),
+    ("Europe/Prague", "Czechia", (12.09, 48.55, 18.86, 51.06)),
+    ("Europe/Berlin", "Germany", (5.87, 47.27, 15.04, 55.06)),
+    ("Europe/Warsaw", "Poland", (14.12, 49.00, 24.15, 54.84)),
+];
+
+#[derive(Debug)]
+pub enum Reason {
+    // 0, 0 in the Gulf of Guinea, the usual placeholder for an unknown location
+    NullIsland,
+    // Meters to the closest other stop
+    Isolated(f64),
+    OutsideCountry(&'static str),
+}
+
+#[derive(Debug)]
+pub struct Outlier {
+    pub stop: Arc<Stop>,
+    pub reason: Reason,
+}
+
+// Stops left out of the extent, checked in order: 0, 0, outside the country of the agency's
+// timezone, far away from every other stop. Stops without coordinates, like generic nodes and
+// boarding areas, are never part of it and aren't reported
+pub fn find_outliers(gtfs: &Gtfs) -> Vec<Outlier> {
+    let country = gtfs.agencies.first().and_then(|agency| {
+        COUNTRIES
+            .iter()
+            .find(|(timezone, ..)| *timezone == agency.timezone)
+            .map(|(_, name, (min_lng, min_lat, max_lng, max_lat))| {
+                let bounds = Boundaries {
+                    min_lng: *min_lng,
+                    max_lng: *max_lng,
+                    min_lat: *min_lat,
+                    max_lat: *max_lat,
+                };
+
+                (*name, bounds)
+            })
+    });
+
+    let mut outliers = vec![];
+    let mut located = vec![];
+
+    for stop in gtfs.stops.values() {
+        let reason = match (stop.longitude, stop.latitude) {
+            (Some(lng), Some(lat)) if lng.abs() < 0.01 && lat.abs() < 0.01 => {
+                Some(Reason::NullIsland)
+            }
+            (Some(lng), Some(lat)) => match &country {
+                Some((name, bounds)) if !bounds.contains(lng, lat) => {
+                    Some(Reason::OutsideCountry(name))
+                }
+                _ => {
+                    located.push((lng, lat, stop));
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        if let Some(reason) = reason {
+            outliers.push(Outlier {
+                stop: Arc::clone(stop),
+                reason,
+            });
+        }
+    }
+
+    // Sweep along longitude so only stops within reach get compared
+    located.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    for (idx, (lng, lat, stop)) in located.iter().enumerate() {
+        let reach = ISOLATED_DISTANCE / (111_320. * lat.to_radians().cos().max(0.1));
+
+        let before = located[..idx]
+            .iter()
+            .rev()
+            .take_while(|o| lng - o.0 <= reach);
+        let after = located[idx + 1..].iter().take_while(|o| o.0 - lng <= reach);
+
+        let closest = before
+            .chain(after)
+            .map(|o| haversine(*lng, *lat, o.0, o.1))
+            .fold(f64::INFINITY, f64::min);
+
+        // Stops alone in the feed have nothing to be compared to
+        if closest > ISOLATED_DISTANCE && located.len() > 1 {
+            outliers.push(Outlier {
+                stop: Arc::clone(stop),
+                reason: Reason::Isolated(closest),
+            });
+        }
+    }
+
+    outliers
+}
+
+// How the map extent is fitted to the feed
+#[derive(Debug, Default)]
+pub struct Fit {
+    // Share of stops, in percent, ignored at each side of the extent
+    pub percentile: f64,
+    // Only fit the stops of routes of this route_type, extended codes folded into their basic
+    // type like the feed's are
+    pub route_type: Option<i16>,
+}
+
+impl Fit {
+    pub fn from_args(args: &Args) -> Self {
+        let route_type = args.get("fit-route-type").map(|s| {
+            parse_route_type(s)
+                .map(basic_route_type)
+                .unwrap_or_else(|| panic!("Invalid value for --fit-route-type: {s}"))
+        });
+
+        Self {
+            percentile: args.value("fit-percentile").unwrap_or(0.),
+            route_type,
+        }
+    }
+
+    // Coordinates the extent is fitted to, with outliers reported and left out
+    pub fn coords(&self, gtfs: &Gtfs) -> Vec<(f64, f64)> {
+        let outliers = find_outliers(gtfs);
+
+        for outlier in outliers.iter() {
+            let stop = &outlier.stop;
+            let reason = match &outlier.reason {
+                Reason::NullIsland => String::from("at 0, 0"),
+                Reason::Isolated(d) => format!("{:.1} km from any other stop", d / 1000.),
+                Reason::OutsideCountry(country) => format!("outside {country}"),
+            };
+
+            eprintln!(
+                "Outlier stop {} ({}) at {:?}, {:?}: {reason}",
+                stop.id, stop.name, stop.latitude, stop.longitude
+            );
+        }
+
+        let outliers = outliers
+            .iter()
+            .map(|o| o.stop.id.as_str())
+            .collect::<HashSet<&str>>();
+
+        let served = self.route_type.map(|route_type| {
+            gtfs.trips
+                .values()
+                .filter(|trip| {
+                    gtfs.routes
+                        .get(&trip.route_id)
+                        .map_or(false, |r| route_type_code(r.route_type) == route_type)
+                })
+                .flat_map(|trip| trip.stop_times.iter().map(|st| st.stop.id.as_str()))
+                .collect::<HashSet<&str>>()
+        });
+
+        let coords = |served: Option<&HashSet<&str>>| {
+            gtfs.stops
+                .values()
+                .filter(|stop| !outliers.contains(stop.id.as_str()))
+                .filter(|stop| served.map_or(true, |s| s.contains(stop.id.as_str())))
+                .filter_map(|stop| Some((stop.longitude?, stop.latitude?)))
+                .collect::<Vec<_>>()
+        };
+
+        let fitted = coords(served.as_ref());
+        if fitted.is_empty() && served.is_some() {
+            eprintln!(
+                "No stops served by route_type {}, fitting the map to every stop",
+                self.route_type.unwrap()
+            );
+            return coords(None);
+        }
+
+        fitted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::feed::fixture_gtfs;
+
+    #[test]
+    fn stops_without_coordinates_are_not_outliers() {
+        let mut gtfs = fixture_gtfs();
+        let node = Stop {
+            id: String::from("N"),
+            ..Default::default()
+        };
+        gtfs.stops.insert(node.id.clone(), Arc::new(node));
+
+        assert!(find_outliers(&gtfs).is_empty());
+        assert_eq!(Fit::default().coords(&gtfs).len(), 4);
+    }
+
+    #[test]
+    fn extended_route_type_fits_its_basic_type() {
+        let gtfs = fixture_gtfs();
+        // 900 is the extended code of trams
+        let args = Args::parse(["--fit-route-type=900".to_string()].into_iter());
+        let fit = Fit::from_args(&args);
+        assert_eq!(fit.route_type, Some(0));
+
+        let mut coords = fit.coords(&gtfs);
+        coords.sort_by(|a, b| a.0.total_cmp(&b.0));
+        assert_eq!(coords, vec![(19.04, 47.5), (19.05, 47.505), (19.06, 47.51)]);
+    }
+}
@@ -0,0 +1,25 @@
+use gtfs_structures::Gtfs;
+
+use crate::args::Args;
+use crate::utils::clip::Clip;
+
+// Loads the feed, clipped to `--clip` if given
+pub fn load_gtfs(url: &str, args: &Args) -> Gtfs {
+    eprintln!("Parsing GTFS at {url}...");
+    let mut gtfs = Gtfs::new(url).expect("Failed to load GTFS");
+
+    if let Some(spec) = args.get("clip") {
+        let clip = Clip::load(spec).unwrap_or_else(|e| panic!("Invalid value for --clip: {e}"));
+        clip.apply(&mut gtfs);
+    }
+
+    gtfs
+}
+
+// The small feed of `tests/fixtures/gtfs`: a tram with a wheelchair accessible trip and a
+// bus with night trips, one of them running past 48:00
+#[cfg(test)]
+pub fn fixture_gtfs() -> Gtfs {
+    Gtfs::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/gtfs"))
+        .expect("Failed to load the test feed")
+}
@@ -0,0 +1,123 @@
+use chrono::NaiveDate;
+use gtfs_structures::Gtfs;
+use std::collections::HashMap;
+
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::runs_on;
+
+const DAY: u32 = 24 * 60 * 60;
+
+// Service at a stop during one time of day bucket
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bucket {
+    pub departures: usize,
+    // Average seconds between departures, none with less than two
+    pub headway: Option<f64>,
+}
+
+// Departures from every stop on a date, split into buckets of `bucket` seconds
+#[derive(Debug, Default)]
+pub struct Frequencies {
+    pub bucket: u32,
+    // Sorted departure times of every bucket, by stop id
+    stops: HashMap<String, Vec<Vec<u32>>>,
+}
+
+impl Frequencies {
+    // Trips passing `filter` running on `date`, or the day before and past midnight
+    pub fn from_gtfs(gtfs: &Gtfs, filter: &RouteFilter, date: NaiveDate, bucket: u32) -> Self {
+        let buckets = ((DAY + bucket - 1) / bucket) as usize;
+        let yesterday = date.pred();
+
+        let mut services = HashMap::new();
+        let mut runs = |service_id: &str, date: NaiveDate| {
+            *services
+                .entry((service_id.to_string(), date))
+                .or_insert_with(|| runs_on(gtfs, service_id, date))
+        };
+
+        let mut stops: HashMap<String, Vec<Vec<u32>>> = HashMap::new();
+
+        for trip in gtfs.trips.values() {
+            if !filter.keeps_trip(gtfs, trip) {
+                continue;
+            }
+
+            let today = runs(&trip.service_id, date);
+            let past_midnight = runs(&trip.service_id, yesterday);
+
+            if !today && !past_midnight {
+                continue;
+            }
+
+            // The last stop only has an arrival
+            let stop_times = &trip.stop_times[..trip.stop_times.len().saturating_sub(1)];
+
+            for st in stop_times.iter() {
+                let time = match st.departure_time.or(st.arrival_time) {
+                    Some(time) => time,
+                    None => continue,
+                };
+
+                // Times of yesterday's trips from 48:00 on fall after the day shown
+                let time = if today && time < DAY {
+                    time
+                } else if past_midnight && (DAY..2 * DAY).contains(&time) {
+                    time - DAY
+                } else {
+                    continue;
+                };
+
+                stops
+                    .entry(st.stop.id.clone())
+                    .or_insert_with(|| vec![vec![]; buckets])[(time / bucket) as usize]
+                    .push(time);
+            }
+        }
+
+        for buckets in stops.values_mut() {
+            for times in buckets.iter_mut() {
+                times.sort_unstable();
+            }
+        }
+
+        Self { bucket, stops }
+    }
+
+    pub fn buckets(&self) -> usize {
+        ((DAY + self.bucket - 1) / self.bucket.max(1)) as usize
+    }
+
+    pub fn stop_ids(&self) -> impl Iterator<Item = &String> {
+        self.stops.keys()
+    }
+
+    pub fn get(&self, stop_id: &str, idx: usize) -> Bucket {
+        let times = match self.stops.get(stop_id).and_then(|buckets| buckets.get(idx)) {
+            Some(times) => times,
+            None => return Bucket::default(),
+        };
+
+        let headway = match (times.first(), times.last()) {
+            (Some(first), Some(last)) if times.len() > 1 => {
+                Some((last - first) as f64 / (times.len() - 1) as f64)
+            }
+            _ => None,
+        };
+
+        Bucket {
+            departures: times.len(),
+            headway,
+        }
+    }
+
+    // Most departures from a single stop in the bucket
+    pub fn max_departures(&self, idx: usize) -> usize {
+        self.stops
+            .values()
+            .filter_map(|buckets| buckets.get(idx))
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+    }
+}
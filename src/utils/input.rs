@@ -0,0 +1,44 @@
+use nannou::event::Key;
+use nannou::event::MouseButton;
+use nannou::event::MouseScrollDelta;
+use nannou::event::WindowEvent;
+use nannou::Event;
+
+// Window events the visualizations react to, decoupled from the window so they can be
+// driven without one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    MousePressed(MouseButton),
+    MouseReleased(MouseButton),
+    // Position in nannou's coordinates, origin in the middle and y up
+    MouseMoved(f64, f64),
+    // Wheel steps, positive away from the user
+    Scroll(f64),
+    Key(Key),
+    Char(char),
+}
+
+impl Input {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let simple = match event {
+            Event::WindowEvent {
+                simple: Some(simple),
+                ..
+            } => simple,
+            _ => return None,
+        };
+
+        match simple {
+            WindowEvent::MousePressed(button) => Some(Self::MousePressed(*button)),
+            WindowEvent::MouseReleased(button) => Some(Self::MouseReleased(*button)),
+            WindowEvent::MouseMoved(pos) => Some(Self::MouseMoved(pos.x as f64, pos.y as f64)),
+            WindowEvent::MouseWheel(delta, _) => Some(Self::Scroll(match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                MouseScrollDelta::PixelDelta(pos) => pos.y / 50.,
+            })),
+            WindowEvent::KeyPressed(key) => Some(Self::Key(*key)),
+            WindowEvent::ReceivedCharacter(c) => Some(Self::Char(*c)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rect {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl Rect {
+    pub fn centered(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            min_x: x - width / 2.,
+            min_y: y - height / 2.,
+            max_x: x + width / 2.,
+            max_y: y + height / 2.,
+        }
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (
+            (self.min_x + self.max_x) / 2.,
+            (self.min_y + self.max_y) / 2.,
+        )
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max_x - self.min_x
+    }
+
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.min_x < other.max_x
+            && other.min_x < self.max_x
+            && self.min_y < other.max_y
+            && other.min_y < self.max_y
+    }
+
+    pub fn inside(&self, bounds: &Rect) -> bool {
+        self.min_x >= bounds.min_x
+            && self.max_x <= bounds.max_x
+            && self.min_y >= bounds.min_y
+            && self.max_y <= bounds.max_y
+    }
+
+    // Point of the rectangle closest to (x, y)
+    pub fn closest(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x.clamp(self.min_x, self.max_x),
+            y.clamp(self.min_y, self.max_y),
+        )
+    }
+}
+
+// A label of the given size to be placed next to the point (x, y), in canvas coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct LabelRequest {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    // Gap between the point and the label, e.g. the radius of the marker
+    pub offset: f32,
+    // More important labels are placed first and dropped last
+    pub priority: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    pub rect: Rect,
+    // Line from the point to a label placed further away
+    pub leader: Option<((f32, f32), (f32, f32))>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    Greedy,
+    // Greedy placement refined by simulated annealing, seeded for stable output
+    Annealing { iterations: usize, seed: u64 },
+}
+
+// Candidate directions around the point, in order of preference
+const POSITIONS: [(f32, f32); 8] = [
+    (1., 0.),
+    (-1., 0.),
+    (0., 1.),
+    (0., -1.),
+    (1., 1.),
+    (-1., 1.),
+    (1., -1.),
+    (-1., -1.),
+];
+
+// Distance of the outer candidate ring, which gets a leader line, in label heights
+const LEADER_DISTANCE: f32 = 2.5;
+
+const OVERLAP_COST: f32 = 1000.;
+const RANK_COST: f32 = 1.;
+const LEADER_COST: f32 = 10.;
+const DROP_COST: f32 = 100.;
+
+impl LabelRequest {
+    fn candidates(&self) -> Vec<Placement> {
+        let near = POSITIONS
+            .iter()
+            .map(|position| (position, self.offset, false));
+        let far = POSITIONS
+            .iter()
+            .map(|position| (position, self.offset + self.height * LEADER_DISTANCE, true));
+
+        near.chain(far)
+            .map(|((dx, dy), offset, leader)| {
+                let rect = Rect::centered(
+                    self.x + dx * (offset + self.width / 2.),
+                    self.y + dy * (offset + self.height / 2.),
+                    self.width,
+                    self.height,
+                );
+
+                Placement {
+                    rect,
+                    leader: leader.then(|| {
+                        let (ex, ey) = rect.closest(self.x, self.y);
+                        let len = ((ex - self.x).powi(2) + (ey - self.y).powi(2))
+                            .sqrt()
+                            .max(1.);
+                        let start = (
+                            self.x + (ex - self.x) / len * self.offset,
+                            self.y + (ey - self.y) / len * self.offset,
+                        );
+
+                        (start, (ex, ey))
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+struct Layout<'a> {
+    requests: &'a [LabelRequest],
+    candidates: Vec<Vec<Placement>>,
+    obstacles: &'a [Rect],
+    bounds: Rect,
+}
+
+impl Layout<'_> {
+    fn fits(&self, label: usize, candidate: usize) -> bool {
+        let rect = self.candidates[label][candidate].rect;
+        rect.inside(&self.bounds) && !self.obstacles.iter().any(|o| o.overlaps(&rect))
+    }
+
+    fn rect(&self, label: usize, choice: Option<usize>) -> Option<Rect> {
+        choice.map(|c| self.candidates[label][c].rect)
+    }
+
+    // Cost of a single label's choice given the others
+    fn cost(&self, label: usize, choice: Option<usize>, choices: &[Option<usize>]) -> f32 {
+        let priority = self.requests[label].priority.max(0.) + 1.;
+
+        let candidate = match choice {
+            Some(candidate) => candidate,
+            None => return DROP_COST * priority,
+        };
+
+        let rect = self.candidates[label][candidate].rect;
+        let overlaps = choices
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != label)
+            .filter_map(|(other, choice)| self.rect(other, *choice))
+            .filter(|other| other.overlaps(&rect))
+            .count();
+
+        let leader = self.candidates[label][candidate].leader.is_some() as u8 as f32;
+
+        OVERLAP_COST * overlaps as f32
+            + (RANK_COST * candidate as f32 + LEADER_COST * leader) * priority
+    }
+
+    fn greedy(&self) -> Vec<Option<usize>> {
+        let mut order = (0..self.requests.len()).collect::<Vec<usize>>();
+        order.sort_by(|a, b| {
+            self.requests[*b]
+                .priority
+                .total_cmp(&self.requests[*a].priority)
+        });
+
+        let mut choices = vec![None; self.requests.len()];
+        let mut placed: Vec<Rect> = vec![];
+
+        for label in order {
+            choices[label] = (0..self.candidates[label].len()).find(|c| {
+                let rect = self.candidates[label][*c].rect;
+                self.fits(label, *c) && !placed.iter().any(|p| p.overlaps(&rect))
+            });
+
+            if let Some(rect) = self.rect(label, choices[label]) {
+                placed.push(rect);
+            }
+        }
+
+        choices
+    }
+
+    fn anneal(
+        &self,
+        mut choices: Vec<Option<usize>>,
+        iterations: usize,
+        seed: u64,
+    ) -> Vec<Option<usize>> {
+        if self.requests.is_empty() {
+            return choices;
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut temperature = LEADER_COST;
+        let cooling = (0.01f32).powf(1. / iterations.max(1) as f32);
+
+        for _ in 0..iterations {
+            let label = rng.gen_range(0..self.requests.len());
+            let count = self.candidates[label].len();
+
+            // Dropping is a move too, so crowded labels can give way to important ones
+            let choice = Some(rng.gen_range(0..=count))
+                .filter(|c| *c < count)
+                .filter(|c| self.fits(label, *c));
+
+            let delta =
+                self.cost(label, choice, &choices) - self.cost(label, choices[label], &choices);
+
+            if delta <= 0. || rng.gen::<f32>() < (-delta / temperature).exp() {
+                choices[label] = choice;
+            }
+
+            temperature *= cooling;
+        }
+
+        // Annealing may leave some overlaps behind, drop the less important label of each
+        let mut order = (0..self.requests.len()).collect::<Vec<usize>>();
+        order.sort_by(|a, b| {
+            self.requests[*b]
+                .priority
+                .total_cmp(&self.requests[*a].priority)
+        });
+
+        let mut placed: Vec<Rect> = vec![];
+        for label in order {
+            match self.rect(label, choices[label]) {
+                Some(rect) if placed.iter().any(|p| p.overlaps(&rect)) => choices[label] = None,
+                Some(rect) => placed.push(rect),
+                None => (),
+            }
+        }
+
+        choices
+    }
+}
+
+// Places labels around their points without overlapping each other or the obstacles,
+// labels without a free spot are dropped, least important first
+pub fn place(
+    requests: &[LabelRequest],
+    obstacles: &[Rect],
+    bounds: Rect,
+    strategy: Strategy,
+) -> Vec<Option<Placement>> {
+    let layout = Layout {
+        requests,
+        candidates: requests.iter().map(LabelRequest::candidates).collect(),
+        obstacles,
+        bounds,
+    };
+
+    let choices = match strategy {
+        Strategy::Greedy => layout.greedy(),
+        Strategy::Annealing { iterations, seed } => {
+            layout.anneal(layout.greedy(), iterations, seed)
+        }
+    };
+
+    choices
+        .into_iter()
+        .enumerate()
+        .map(|(label, choice)| choice.map(|c| layout.candidates[label][c]))
+        .collect()
+}
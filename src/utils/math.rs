@@ -1,8 +1,11 @@
 use nannou::math;
 
+use crate::utils::camera::Camera;
 use crate::utils::meta::Meta;
 
-pub fn coordinate_to_xy(lng: f64, lat: f64, meta: &Meta) -> (f64, f64) {
+const EARTH_RADIUS: f64 = 6_371_000.;
+
+pub fn coordinate_to_xy(lng: f64, lat: f64, meta: &Meta, camera: &Camera) -> (f64, f64) {
     let x = math::map_range(
         lng,
         meta.boundaries.min_lng,
@@ -19,9 +22,102 @@ pub fn coordinate_to_xy(lng: f64, lat: f64, meta: &Meta) -> (f64, f64) {
         meta.height / 2.,
     );
 
-    (x, y)
+    camera.apply(x, y)
+}
+
+// Inverse of `coordinate_to_xy`, from screen to (lng, lat)
+pub fn xy_to_coordinate(x: f64, y: f64, meta: &Meta, camera: &Camera) -> (f64, f64) {
+    let (x, y) = camera.unapply(x, y);
+
+    let lng = math::map_range(
+        x,
+        -meta.width / 2.,
+        meta.width / 2.,
+        meta.boundaries.min_lng,
+        meta.boundaries.max_lng,
+    );
+
+    let lat = math::map_range(
+        y,
+        -meta.height / 2.,
+        meta.height / 2.,
+        meta.boundaries.min_lat,
+        meta.boundaries.max_lat,
+    );
+
+    (lng, lat)
 }
 
 pub fn distance(x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
     ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt()
 }
+
+// Great-circle distance in meters
+pub fn haversine(lng0: f64, lat0: f64, lng1: f64, lat1: f64) -> f64 {
+    let (phi0, phi1) = (lat0.to_radians(), lat1.to_radians());
+    let d_phi = phi1 - phi0;
+    let d_lambda = (lng1 - lng0).to_radians();
+
+    let a = (d_phi / 2.).sin().powi(2) + phi0.cos() * phi1.cos() * (d_lambda / 2.).sin().powi(2);
+
+    2. * EARTH_RADIUS * a.sqrt().asin()
+}
+
+// Ground distance covered by a pixel in the middle of the view
+pub fn meters_per_px(meta: &Meta, camera: &Camera) -> f64 {
+    let (lng0, lat0) = xy_to_coordinate(0., 0., meta, camera);
+    let (lng1, lat1) = xy_to_coordinate(100., 0., meta, camera);
+
+    haversine(lng0, lat0, lng1, lat1) / 100.
+}
+
+// The item closest to (x, y), if it's within `radius`
+pub fn nearest<T>(
+    items: impl Iterator<Item = (T, (f64, f64))>,
+    x: f64,
+    y: f64,
+    radius: f64,
+) -> Option<T> {
+    items
+        .map(|(item, (ix, iy))| (item, distance(x, y, ix, iy)))
+        .filter(|(_, d)| *d <= radius)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(item, _)| item)
+}
+
+// Outline of a rectangle centered at (x, y) with quarter circle corners
+pub fn rounded_rect(x: f32, y: f32, w: f32, h: f32, r: f32) -> Vec<(f32, f32)> {
+    let r = r.min(w / 2.).min(h / 2.);
+    let (dx, dy) = (w / 2. - r, h / 2. - r);
+    let corners = [(dx, dy), (-dx, dy), (-dx, -dy), (dx, -dy)];
+
+    corners
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (cx, cy))| {
+            (0..=8).map(move |step| {
+                let angle = (i as f32 + step as f32 / 8.) * std::f32::consts::FRAC_PI_2;
+                (x + cx + r * angle.cos(), y + cy + r * angle.sin())
+            })
+        })
+        .collect()
+}
+
+// Distance from (x, y) to the closest segment of the polyline
+pub fn polyline_distance(points: &[(f64, f64)], x: f64, y: f64) -> f64 {
+    points
+        .windows(2)
+        .map(|segment| {
+            let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+            let len = (x1 - x0).powi(2) + (y1 - y0).powi(2);
+
+            let t = if len == 0. {
+                0.
+            } else {
+                (((x - x0) * (x1 - x0) + (y - y0) * (y1 - y0)) / len).clamp(0., 1.)
+            };
+
+            distance(x, y, x0 + t * (x1 - x0), y0 + t * (y1 - y0))
+        })
+        .fold(f64::INFINITY, f64::min)
+}
@@ -2,8 +2,9 @@ use gtfs_structures::Gtfs;
 use gtfs_structures::Trip;
 
 use crate::utils::boundaries::Boundaries;
+use crate::utils::extent::Fit;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Meta {
     pub boundaries: Boundaries,
     pub width: f64,
@@ -11,13 +12,10 @@ pub struct Meta {
 }
 
 impl Meta {
-    pub fn from_gtfs(gtfs: &Gtfs) -> Self {
-        let coords = gtfs
-            .stops
-            .iter()
-            .map(|(_, s)| (s.longitude.unwrap(), s.latitude.unwrap()));
+    pub fn from_gtfs(gtfs: &Gtfs, fit: &Fit) -> Self {
+        let coords = fit.coords(gtfs);
 
-        Self::from_coords(coords)
+        Self::from_boundaries(Boundaries::from_percentiles(coords, fit.percentile))
     }
 
     pub fn from_trip(trip: &Trip) -> Self {
@@ -29,8 +27,11 @@ impl Meta {
         Self::from_coords(coords)
     }
 
-    fn from_coords(coords: impl Iterator<Item = (f64, f64)>) -> Self {
-        let boundaries = Boundaries::from_coords(coords);
+    pub fn from_coords(coords: impl Iterator<Item = (f64, f64)>) -> Self {
+        Self::from_boundaries(Boundaries::from_coords(coords))
+    }
+
+    fn from_boundaries(boundaries: Boundaries) -> Self {
         let (width, height) = boundaries.canvas_size();
 
         Self {
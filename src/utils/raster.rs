@@ -0,0 +1,257 @@
+use nannou::color::Rgb8;
+use nannou::image::DynamicImage;
+use nannou::image::RgbaImage;
+use nannou::text::Font;
+use rusttype::point;
+use rusttype::Scale;
+
+use crate::vizualizations::trips::Justify;
+use crate::vizualizations::trips::Label;
+
+// Software RGBA canvas with nannou's coordinate system (origin in the middle, y up),
+// for rendering without a window
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: Rgb8) -> Self {
+        let pixels = (0..width * height)
+            .flat_map(|_| [background.red, background.green, background.blue, 255])
+            .collect();
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn transparent(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    // Scales every pixel's alpha, for drawing the canvas semi-transparent
+    pub fn fade(&mut self, opacity: f32) {
+        if opacity >= 1. {
+            return;
+        }
+
+        for alpha in self.pixels.iter_mut().skip(3).step_by(4) {
+            *alpha = (*alpha as f32 * opacity.max(0.)).round() as u8;
+        }
+    }
+
+    fn to_px(&self, x: f32, y: f32) -> (f32, f32) {
+        (x + self.width as f32 / 2., self.height as f32 / 2. - y)
+    }
+
+    fn blend(&mut self, px: i32, py: i32, color: Rgb8, alpha: f32) {
+        if px < 0 || py < 0 || px >= self.width as i32 || py >= self.height as i32 || alpha <= 0. {
+            return;
+        }
+
+        let idx = ((py as u32 * self.width + px as u32) * 4) as usize;
+        let alpha = alpha.min(1.);
+
+        // Source over compositing, so transparent canvases can be layered too
+        let old_alpha = self.pixels[idx + 3] as f32 / 255.;
+        let new_alpha = alpha + old_alpha * (1. - alpha);
+
+        for (channel, value) in [color.red, color.green, color.blue].into_iter().enumerate() {
+            let old = self.pixels[idx + channel] as f32;
+            let mixed = (value as f32 * alpha + old * old_alpha * (1. - alpha)) / new_alpha;
+            self.pixels[idx + channel] = mixed.round() as u8;
+        }
+
+        self.pixels[idx + 3] = (new_alpha * 255.).round() as u8;
+    }
+
+    pub fn polyline(&mut self, points: &[(f32, f32)], weight: f32, color: Rgb8) {
+        let points = points
+            .iter()
+            .map(|(x, y)| self.to_px(*x, *y))
+            .collect::<Vec<_>>();
+
+        let reach = weight / 2. + 1.;
+        let min_x = points.iter().map(|p| p.0).fold(f32::INFINITY, f32::min) - reach;
+        let max_x = points.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max) + reach;
+        let min_y = points.iter().map(|p| p.1).fold(f32::INFINITY, f32::min) - reach;
+        let max_y = points.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max) + reach;
+
+        if points.len() < 2 || max_x < 0. || max_y < 0. {
+            return;
+        }
+
+        let (x0, y0) = (min_x.floor().max(0.) as u32, min_y.floor().max(0.) as u32);
+        let x1 = max_x.ceil().min(self.width as f32 - 1.) as u32;
+        let y1 = max_y.ceil().min(self.height as f32 - 1.) as u32;
+
+        if x0 > x1 || y0 > y1 {
+            return;
+        }
+
+        // Distance to the closest segment within the bounding box of the line,
+        // so overlapping joints are only blended once
+        let box_width = x1 - x0 + 1;
+        let mut distances = vec![f32::INFINITY; (box_width * (y1 - y0 + 1)) as usize];
+
+        for segment in points.windows(2) {
+            let ((ax, ay), (bx, by)) = (segment[0], segment[1]);
+
+            let from_x = (ax.min(bx) - reach).floor().max(x0 as f32) as u32;
+            let to_x = (ax.max(bx) + reach).ceil().min(x1 as f32) as u32;
+            let from_y = (ay.min(by) - reach).floor().max(y0 as f32) as u32;
+            let to_y = (ay.max(by) + reach).ceil().min(y1 as f32) as u32;
+
+            for py in from_y..=to_y {
+                for px in from_x..=to_x {
+                    let d =
+                        segment_distance((px as f32 + 0.5, py as f32 + 0.5), (ax, ay), (bx, by));
+                    let idx = ((py - y0) * box_width + px - x0) as usize;
+                    distances[idx] = distances[idx].min(d);
+                }
+            }
+        }
+
+        for (idx, d) in distances.into_iter().enumerate() {
+            if d.is_finite() {
+                let (px, py) = (x0 + idx as u32 % box_width, y0 + idx as u32 / box_width);
+                self.blend(px as i32, py as i32, color, weight / 2. - d + 0.5);
+            }
+        }
+    }
+
+    pub fn circle(&mut self, x: f32, y: f32, radius: f32, fill: Rgb8, stroke: Rgb8, weight: f32) {
+        let (cx, cy) = self.to_px(x, y);
+        let reach = radius + weight / 2. + 1.;
+
+        for py in (cy - reach).floor() as i32..=(cy + reach).ceil() as i32 {
+            for px in (cx - reach).floor() as i32..=(cx + reach).ceil() as i32 {
+                let d = ((px as f32 + 0.5 - cx).powi(2) + (py as f32 + 0.5 - cy).powi(2)).sqrt();
+
+                self.blend(px, py, fill, radius - d + 0.5);
+                self.blend(px, py, stroke, weight / 2. - (d - radius).abs() + 0.5);
+            }
+        }
+    }
+
+    pub fn polygon(&mut self, points: &[(f32, f32)], fill: Rgb8) {
+        self.polygon_rings(&[points.to_vec()], fill);
+    }
+
+    // Even-odd fill of several rings, so inner rings cut holes
+    pub fn polygon_rings(&mut self, rings: &[Vec<(f32, f32)>], fill: Rgb8) {
+        let rings = rings
+            .iter()
+            .map(|ring| {
+                ring.iter()
+                    .map(|(x, y)| self.to_px(*x, *y))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let ys = rings.iter().flatten().map(|p| p.1);
+        let min_y = ys.clone().fold(f32::INFINITY, f32::min).floor().max(0.);
+        let max_y = ys.fold(f32::NEG_INFINITY, f32::max).ceil();
+
+        if max_y < 0. {
+            return;
+        }
+
+        // Scanline fill with 4x vertical supersampling for smoother edges
+        for py in min_y as i32..=max_y.min(self.height as f32 - 1.) as i32 {
+            let mut coverage = vec![0f32; self.width as usize];
+
+            for sub in 0..4 {
+                let y = py as f32 + (sub as f32 + 0.5) / 4.;
+                let mut crossings = rings
+                    .iter()
+                    .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)))
+                    .filter(|(a, b)| (a.1 <= y) != (b.1 <= y))
+                    .map(|(a, b)| a.0 + (y - a.1) / (b.1 - a.1) * (b.0 - a.0))
+                    .collect::<Vec<f32>>();
+
+                crossings.sort_by(f32::total_cmp);
+
+                for span in crossings.chunks(2).filter(|span| span.len() == 2) {
+                    let from = span[0].max(0.).round() as usize;
+                    let to = span[1].min(self.width as f32).max(0.).round() as usize;
+
+                    for px in from..to {
+                        coverage[px] += 0.25;
+                    }
+                }
+            }
+
+            for (px, alpha) in coverage.into_iter().enumerate() {
+                self.blend(px as i32, py, fill, alpha);
+            }
+        }
+    }
+
+    pub fn text(&mut self, label: &Label, font: &Font) {
+        let color = label.color;
+        let scale = Scale::uniform(label.font_size as f32);
+        let v_metrics = font.v_metrics(scale);
+
+        let glyphs = font
+            .layout(&label.text, scale, point(0., 0.))
+            .collect::<Vec<_>>();
+        let text_width = text_width(font, &label.text, label.font_size as f32);
+
+        let (left, center_y) = self.to_px(label.x - label.width / 2., label.y);
+        let x = match label.justify {
+            Justify::Left => left,
+            Justify::Center => left + (label.width - text_width) / 2.,
+            Justify::Right => left + label.width - text_width,
+        };
+        let baseline = center_y + (v_metrics.ascent + v_metrics.descent) / 2.;
+
+        for glyph in glyphs {
+            let offset = glyph.position().x;
+            let glyph = glyph
+                .into_unpositioned()
+                .positioned(point(x + offset, baseline));
+
+            if let Some(bb) = glyph.pixel_bounding_box() {
+                glyph.draw(|gx, gy, coverage| {
+                    self.blend(bb.min.x + gx as i32, bb.min.y + gy as i32, color, coverage);
+                });
+            }
+        }
+    }
+}
+
+fn segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len = dx * dx + dy * dy;
+
+    let t = if len == 0. {
+        0.
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len).clamp(0., 1.)
+    };
+
+    ((p.0 - a.0 - t * dx).powi(2) + (p.1 - a.1 - t * dy).powi(2)).sqrt()
+}
+
+impl From<Canvas> for DynamicImage {
+    fn from(canvas: Canvas) -> Self {
+        let image = RgbaImage::from_raw(canvas.width, canvas.height, canvas.pixels).unwrap();
+        DynamicImage::ImageRgba8(image)
+    }
+}
+
+pub fn text_width(font: &Font, text: &str, size: f32) -> f32 {
+    font.layout(text, Scale::uniform(size), point(0., 0.))
+        .last()
+        .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+        .unwrap_or(0.)
+}
@@ -0,0 +1,184 @@
+use gtfs_structures::Availability;
+use gtfs_structures::Gtfs;
+use gtfs_structures::Route;
+use gtfs_structures::Trip;
+use std::collections::HashSet;
+
+use crate::args::Args;
+use crate::utils::route_types::parse_route_type;
+use crate::utils::route_types::route_type_code;
+use crate::utils::route_types::route_type_name;
+
+// Trips departing in this window, in seconds past midnight, count as night service
+const NIGHT_START: u32 = 23 * 60 * 60;
+const NIGHT_END: u32 = 4 * 60 * 60 + 30 * 60;
+
+// Route types cycled through interactively, `None` for all of them
+const ROUTE_TYPE_CYCLE: [Option<&str>; 7] = [
+    None,
+    Some("tram"),
+    Some("metro"),
+    Some("rail"),
+    Some("bus"),
+    Some("trolleybus"),
+    Some("ferry"),
+];
+
+// `*` matches any run of characters, `?` a single one
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text) = (pattern.as_bytes(), text.as_bytes());
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+fn list(args: &Args, key: &str) -> Vec<String> {
+    args.get(key)
+        .map(|v| v.split(',').map(|s| s.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+// Which routes and trips the visualizations show, everything when empty
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RouteFilter {
+    pub route_types: HashSet<i16>,
+    pub agencies: HashSet<String>,
+    // Globs on the route id or short name
+    pub routes: Vec<String>,
+    pub wheelchair: bool,
+    pub night: bool,
+}
+
+impl RouteFilter {
+    // `--route-type=tram,bus --agency=BKK --route=9*,M? --wheelchair --night`
+    pub fn from_args(args: &Args) -> Self {
+        let route_types = list(args, "route-type")
+            .iter()
+            .map(|s| {
+                parse_route_type(s).unwrap_or_else(|| panic!("Invalid value for --route-type: {s}"))
+            })
+            .collect();
+
+        Self {
+            route_types,
+            agencies: list(args, "agency").into_iter().collect(),
+            routes: list(args, "route"),
+            wheelchair: args.has("wheelchair"),
+            night: args.has("night"),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.route_types.is_empty()
+            || !self.agencies.is_empty()
+            || !self.routes.is_empty()
+            || self.wheelchair
+            || self.night
+    }
+
+    pub fn keeps_route(&self, route: &Route) -> bool {
+        let agency = route.agency_id.as_deref().unwrap_or_default();
+
+        (self.route_types.is_empty()
+            || self
+                .route_types
+                .contains(&route_type_code(route.route_type)))
+            && (self.agencies.is_empty() || self.agencies.contains(agency))
+            && (self.routes.is_empty()
+                || self
+                    .routes
+                    .iter()
+                    .any(|g| glob_match(g, &route.id) || glob_match(g, &route.short_name)))
+    }
+
+    pub fn keeps_trip(&self, gtfs: &Gtfs, trip: &Trip) -> bool {
+        let departure = trip
+            .stop_times
+            .first()
+            .and_then(|st| st.departure_time.or(st.arrival_time))
+            .map(|t| t % (24 * 60 * 60));
+
+        gtfs.routes
+            .get(&trip.route_id)
+            .map_or(false, |r| self.keeps_route(r))
+            && (!self.wheelchair || trip.wheelchair_accessible == Availability::Available)
+            && (!self.night || departure.map_or(false, |t| t >= NIGHT_START || t < NIGHT_END))
+    }
+
+    // Steps through all, tram, metro... keeping only one route type at a time, past the ones
+    // `has_trips` turns down. Stays put if none pass
+    pub fn next_route_type(&mut self, has_trips: impl Fn(&RouteFilter) -> bool) {
+        let codes = ROUTE_TYPE_CYCLE.map(|entry| entry.map(|name| parse_route_type(name).unwrap()));
+        let current = codes
+            .iter()
+            .position(|entry| match entry {
+                None => self.route_types.is_empty(),
+                Some(code) => self.route_types.len() == 1 && self.route_types.contains(code),
+            })
+            .unwrap_or(0);
+
+        for step in 1..codes.len() {
+            let next = Self {
+                route_types: codes[(current + step) % codes.len()].into_iter().collect(),
+                ..self.clone()
+            };
+
+            if has_trips(&next) {
+                *self = next;
+                return;
+            }
+        }
+    }
+
+    // Short summary for the corner of the window
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+
+        if !self.route_types.is_empty() {
+            let mut types = self
+                .route_types
+                .iter()
+                .map(|code| route_type_name(*code))
+                .collect::<Vec<_>>();
+            types.sort();
+            parts.push(types.join(", "));
+        }
+
+        if !self.agencies.is_empty() {
+            let mut agencies = self.agencies.iter().cloned().collect::<Vec<_>>();
+            agencies.sort();
+            parts.push(agencies.join(", "));
+        }
+
+        if !self.routes.is_empty() {
+            parts.push(self.routes.join(", "));
+        }
+
+        if self.wheelchair {
+            parts.push(String::from("wheelchair accessible"));
+        }
+
+        if self.night {
+            parts.push(String::from("night"));
+        }
+
+        parts.join(" · ")
+    }
+}
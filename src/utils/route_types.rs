@@ -0,0 +1,103 @@
+use gtfs_structures::RouteType;
+use serde::de::value::Error;
+use serde::de::value::I16Deserializer;
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+
+// Names accepted on the command line, besides numeric route_type codes
+const NAMES: [(&str, i16); 11] = [
+    ("tram", 0),
+    ("metro", 1),
+    ("subway", 1),
+    ("rail", 2),
+    ("bus", 3),
+    ("ferry", 4),
+    ("cablecar", 5),
+    ("gondola", 6),
+    ("funicular", 7),
+    ("coach", 200),
+    // Extended 8xx trolleybus codes are read as buses by gtfs-structures
+    ("trolleybus", 11),
+];
+
+// The numeric route_type of routes.txt
+pub fn route_type_code(route_type: RouteType) -> i16 {
+    match route_type {
+        RouteType::Tramway => 0,
+        RouteType::Subway => 1,
+        RouteType::Rail => 2,
+        RouteType::Bus => 3,
+        RouteType::Ferry => 4,
+        RouteType::CableCar => 5,
+        RouteType::Gondola => 6,
+        RouteType::Funicular => 7,
+        RouteType::Coach => 200,
+        RouteType::Air => 1100,
+        RouteType::Taxi => 1500,
+        RouteType::Other(code) => code,
+    }
+}
+
+// The code a route of `code` has once loaded, gtfs-structures folding extended codes like
+// `109` into their basic route_type
+pub fn basic_route_type(code: i16) -> i16 {
+    let deserializer: I16Deserializer<Error> = code.into_deserializer();
+    RouteType::deserialize(deserializer).map_or(code, route_type_code)
+}
+
+// `tram`, `bus`... or a route_type code like `109`
+pub fn parse_route_type(s: &str) -> Option<i16> {
+    NAMES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        .map(|(_, code)| *code)
+        .or_else(|| s.parse().ok())
+}
+
+// Command line name of a route_type code, extended codes named after their group
+pub fn route_type_name(code: i16) -> String {
+    let group = match code / 100 {
+        1 => Some(2),
+        2 => Some(200),
+        4 => Some(1),
+        7 => Some(3),
+        9 => Some(0),
+        10 | 12 => Some(4),
+        13 => Some(6),
+        14 => Some(7),
+        _ => None,
+    };
+
+    [Some(code), group]
+        .into_iter()
+        .flatten()
+        .find_map(|code| NAMES.iter().find(|(_, c)| *c == code))
+        .map_or(code.to_string(), |(name, _)| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trolleybuses_are_route_type_11() {
+        assert_eq!(parse_route_type("trolleybus"), Some(11));
+        assert_eq!(route_type_code(RouteType::Other(11)), 11);
+        assert_eq!(route_type_name(11), "trolleybus");
+    }
+
+    #[test]
+    fn extended_codes_are_named_after_their_group() {
+        assert_eq!(route_type_name(109), "rail");
+        assert_eq!(route_type_name(900), "tram");
+        assert_eq!(route_type_name(1700), "1700");
+    }
+
+    #[test]
+    fn extended_codes_fold_like_the_feed() {
+        assert_eq!(basic_route_type(109), 2);
+        assert_eq!(basic_route_type(700), 3);
+        assert_eq!(basic_route_type(3), 3);
+        assert_eq!(basic_route_type(11), 11);
+    }
+}
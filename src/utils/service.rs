@@ -0,0 +1,58 @@
+use chrono::Datelike;
+use chrono::NaiveDate;
+use chrono::Weekday;
+use gtfs_structures::Exception;
+use gtfs_structures::Gtfs;
+
+// Whether the service runs on `date`, per calendar.txt and the calendar_dates.txt exceptions
+pub fn runs_on(gtfs: &Gtfs, service_id: &str, date: NaiveDate) -> bool {
+    let exception = gtfs
+        .calendar_dates
+        .get(service_id)
+        .into_iter()
+        .flatten()
+        .find(|cd| cd.date == date);
+
+    if let Some(exception) = exception {
+        return exception.exception_type == Exception::Added;
+    }
+
+    gtfs.calendar.get(service_id).map_or(false, |c| {
+        let weekday = match date.weekday() {
+            Weekday::Mon => c.monday,
+            Weekday::Tue => c.tuesday,
+            Weekday::Wed => c.wednesday,
+            Weekday::Thu => c.thursday,
+            Weekday::Fri => c.friday,
+            Weekday::Sat => c.saturday,
+            Weekday::Sun => c.sunday,
+        };
+
+        weekday && c.start_date <= date && date <= c.end_date
+    })
+}
+
+// `YYYYMMDD` like GTFS itself, or `YYYY-MM-DD`
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+        .ok()
+}
+
+pub fn format_time(seconds: u32) -> String {
+    format!("{:02}:{:02}", seconds / 3600 % 24, seconds / 60 % 60)
+}
+
+// `HH:MM` or `HH:MM:SS` to seconds past midnight, hours past 24 allowed like in GTFS
+pub fn parse_time(s: &str) -> Option<u32> {
+    let parts = s
+        .split(':')
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<u32>>>()?;
+
+    match parts[..] {
+        [h, m] if m < 60 => Some(h * 3600 + m * 60),
+        [h, m, s] if m < 60 && s < 60 => Some(h * 3600 + m * 60 + s),
+        _ => None,
+    }
+}
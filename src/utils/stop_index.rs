@@ -0,0 +1,140 @@
+use chrono::NaiveDate;
+use gtfs_structures::Gtfs;
+use gtfs_structures::Stop;
+use gtfs_structures::Trip;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::utils::service::runs_on;
+
+const DAY: u32 = 24 * 60 * 60;
+
+// Routes and trips serving each station, with the platforms of a parent station merged together
+#[derive(Debug, Default)]
+pub struct StopIndex {
+    routes: HashMap<String, HashSet<String>>,
+    trips: HashMap<String, Vec<String>>,
+}
+
+pub fn station_id(stop: &Stop) -> &str {
+    stop.parent_station.as_deref().unwrap_or(&stop.id)
+}
+
+impl StopIndex {
+    pub fn from_gtfs(gtfs: &Gtfs) -> Self {
+        let mut routes: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut trips: HashMap<String, Vec<String>> = HashMap::new();
+
+        for trip in gtfs.trips.values() {
+            for st in trip.stop_times.iter() {
+                let station = station_id(&st.stop);
+
+                routes
+                    .entry(station.to_string())
+                    .or_default()
+                    .insert(trip.route_id.clone());
+                trips
+                    .entry(station.to_string())
+                    .or_default()
+                    .push(trip.id.clone());
+            }
+        }
+
+        // Loop routes and platforms of the same station list a trip more than once
+        for ids in trips.values_mut() {
+            ids.sort();
+            ids.dedup();
+        }
+
+        Self { routes, trips }
+    }
+
+    pub fn routes(&self, stop: &Stop) -> impl Iterator<Item = &String> {
+        self.routes.get(station_id(stop)).into_iter().flatten()
+    }
+
+    pub fn trips(&self, stop: &Stop) -> &[String] {
+        self.trips.get(station_id(stop)).map_or(&[], Vec::as_slice)
+    }
+
+    // Number of routes serving the station, a rough measure of its importance
+    pub fn count(&self, stop: &Stop) -> usize {
+        self.routes.get(station_id(stop)).map_or(0, HashSet::len)
+    }
+
+    // Whether any route other than `route_id` stops here
+    pub fn is_transfer(&self, stop: &Stop, route_id: &str) -> bool {
+        self.routes(stop).any(|id| id != route_id)
+    }
+
+    // Departures from the station on `date` at or after `after` seconds past midnight,
+    // including the previous service day's trips running past midnight
+    pub fn departures<'a>(
+        &self,
+        gtfs: &'a Gtfs,
+        stop: &Stop,
+        date: NaiveDate,
+        after: u32,
+    ) -> Vec<(u32, &'a Trip)> {
+        let station = station_id(stop);
+        let yesterday = date.pred();
+
+        let mut departures = self
+            .trips(stop)
+            .iter()
+            .filter_map(|id| gtfs.trips.get(id))
+            .flat_map(|trip| {
+                trip.stop_times
+                    .iter()
+                    .filter(|st| station_id(&st.stop) == station)
+                    .filter_map(|st| st.departure_time.or(st.arrival_time))
+                    .flat_map(move |time| {
+                        // A trip running every day can leave both today and, past midnight,
+                        // as yesterday's instance
+                        let today = (time >= after && runs_on(gtfs, &trip.service_id, date))
+                            .then_some((time, trip));
+                        let overnight = (time >= DAY + after
+                            && runs_on(gtfs, &trip.service_id, yesterday))
+                        .then(|| (time - DAY, trip));
+                        today.into_iter().chain(overnight)
+                    })
+            })
+            .collect::<Vec<(u32, &Trip)>>();
+
+        departures.sort_by_key(|(time, _)| *time);
+        departures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::feed::fixture_gtfs;
+
+    #[test]
+    fn overnight_trip_leaves_today_and_as_yesterdays() {
+        let gtfs = fixture_gtfs();
+        let index = StopIndex::from_gtfs(&gtfs);
+        let stop = &gtfs.stops["A"];
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+
+        let departures = index
+            .departures(&gtfs, stop, date, 23 * 3600)
+            .into_iter()
+            .map(|(time, trip)| (time, trip.id.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            departures,
+            vec![(23 * 3600 + 55 * 60, "b3"), (47 * 3600 + 55 * 60, "b3")]
+        );
+    }
+
+    #[test]
+    fn trips_are_listed_once() {
+        let gtfs = fixture_gtfs();
+        let index = StopIndex::from_gtfs(&gtfs);
+
+        assert_eq!(index.trips(&gtfs.stops["B"]), ["b2", "b3", "t1", "t2"]);
+    }
+}
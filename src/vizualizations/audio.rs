@@ -1,144 +1,153 @@
-use gtfs_structures::Gtfs;
-use nannou::color;
+use gtfs_structures::Stop;
 use nannou::math::map_range;
-use nannou::Event;
-use rodio::dynamic_mixer;
-use rodio::source::SineWave;
-use rodio::OutputStream;
-use rodio::Sink;
-use rodio::Source;
+use nannou::Draw;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::constants::{CANVAS_HEIGHT, CANVAS_WIDTH, GTFS_URL};
+use crate::constants::CANVAS_WIDTH;
 use crate::piano::Piano;
+use crate::utils::input::Input;
 
-use super::Model;
+use super::Context;
 use super::Viz;
-use super::Vizualization;
 
 const NOTES: [&'static str; 9] = [
     "C#4/Db4", "D4", "E4", "F4", "G4", "A4", "A#4/Bb4", "C#5/Db5", "D5",
 ];
 
-#[derive(Default)]
-pub struct AudioViz {
-    piano: Arc<Piano>,
+fn stop_keys(name: &str) -> Vec<&'static str> {
+    name.split(" ")
+        .map(|part| NOTES[part.len() % NOTES.len()])
+        .collect()
 }
 
-impl Model<Box<AudioViz>> {
-    fn music(&self, gtfs: &Gtfs) {
-        let piano = Piano::new();
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        let sink = Sink::try_new(&stream_handle).unwrap();
-
-        for (_, stop) in &gtfs.stops {
-            let keys = stop
-                .name
-                .split(" ")
-                .map(|part| NOTES[part.len() % NOTES.len()])
-                .collect::<Vec<&'static str>>();
-
-            let (ctrl, mixer) = dynamic_mixer::mixer::<f32>(2, 44100);
-
-            for note in keys.into_iter() {
-                if let Some(note) = piano.keys.get(note) {
-                    let source = SineWave::new(note.freq)
-                        .take_duration(Duration::from_millis(120))
-                        .amplify(0.18);
-
-                    ctrl.add(source);
-                }
-            }
+pub struct AudioViz {
+    // Loaded once and reused every time the visualization is entered
+    piano: Piano,
+    // Stops passing the route filter, played in order
+    stops: Vec<Arc<Stop>>,
+    // Frame the current stops started playing at
+    started: u64,
+    // Stop being played, none until the first update and past the end once all of them were
+    position: Option<usize>,
+}
 
-            sink.append(mixer);
+impl AudioViz {
+    pub fn new() -> Self {
+        Self {
+            piano: Piano::new(),
+            stops: vec![],
+            started: 0,
+            position: None,
         }
+    }
 
-        sink.sleep_until_end();
-        sink.detach();
+    // Plays the stops passing the filter from the start
+    fn restart(&mut self, ctx: &Context) {
+        self.stops = ctx.kept_stops().into_values().collect();
+        self.started = ctx.clock.frame;
+        self.position = None;
+    }
+
+    // Renders the stops from `from` onwards, one frame each, as the take's soundtrack
+    fn record_music(&self, ctx: &mut Context, from: usize) {
+        let take = match ctx.recorder.take_dir() {
+            Some(take) => take.join("audio.wav"),
+            None => return,
+        };
+
+        let chords = self
+            .stops
+            .iter()
+            .skip(from)
+            .map(|stop| stop_keys(&stop.name));
+
+        match self
+            .piano
+            .render_wav(chords, ctx.recorder.frame_duration(), &take)
+        {
+            Ok(()) => ctx.recorder.attach_audio(take),
+            Err(e) => eprintln!("Couldn't render audio: {e}"),
+        }
     }
 }
 
 impl Viz for AudioViz {
-    fn model(&self) -> nannou::app::ModelFn<Model<Box<Self>>> {
-        |app| {
-            let model = Model::from_url(GTFS_URL).context(|_| {
-                Box::new(Self {
-                    piano: Arc::new(Piano::new()),
-                })
-            });
+    fn enter(&mut self, ctx: &mut Context) {
+        self.restart(ctx);
+    }
 
-            app.set_loop_mode(nannou::LoopMode::loop_ntimes(model.gtfs.stops.len()));
+    fn update(&mut self, ctx: &mut Context) {
+        let position = ctx.clock.frame.saturating_sub(self.started) as usize;
 
-            model.music(&model.gtfs);
+        if ctx.recorder.is_active() && ctx.recorder.frames() == 0 {
+            self.record_music(ctx, position);
+        }
 
-            model
+        // While recording the soundtrack is rendered to the take instead
+        if self.position != Some(position) && !ctx.recorder.is_active() {
+            if let Some(stop) = self.stops.get(position) {
+                self.piano
+                    .press_keys(stop_keys(&stop.name), Duration::from_millis(50));
+            }
         }
-    }
 
-    fn update(&self) -> nannou::app::UpdateFn<Model<Box<Self>>> {
-        |_, _, _| {}
+        self.position = Some(position);
     }
 
-    fn event(&self) -> nannou::app::EventFn<Model<Box<Self>>, Event> {
-        |_, _, _| {}
+    fn event(&mut self, ctx: &mut Context, input: &Input) {
+        if ctx.filter_event(input) {
+            self.restart(ctx);
+        }
     }
 
-    fn view(&self) -> nannou::app::ViewFn<Model<Box<Self>>> {
-        |app, model, frame| {
-            let piano = Arc::clone(&model.context.piano);
-            let draw = app.draw();
-            frame.clear(color::BLACK);
-
-            let stop_idx = frame.nth() as usize;
-            let stop = model.gtfs.stops.values().nth(stop_idx).unwrap();
-
-            let keys = stop
-                .name
-                .split(" ")
-                .map(|part| NOTES[part.len() % NOTES.len()])
-                .collect::<Vec<&'static str>>();
-
-            for key in keys.iter() {
-                let r = map_range(
-                    NOTES.iter().position(|n| n == key).unwrap_or(0),
-                    0,
-                    8,
-                    50,
-                    200,
-                );
-
-                draw.ellipse()
-                    .radius(r as f32)
-                    .stroke_color(color::gray(0.1))
-                    .stroke_weight(2.)
-                    .no_fill();
-            }
-
-            draw.text(&keys.join(" "))
-                .x_y(0., -50.)
-                .color(color::WHITE)
-                .font_size(24)
-                .w(CANVAS_WIDTH as f32);
-
-            draw.text(&stop.name)
-                .x_y(0., 50.)
-                .color(color::WHITE)
-                .font_size(32)
-                .w(CANVAS_WIDTH as f32);
-
-            piano.press_keys(keys, Duration::from_millis(50));
-
-            draw.to_frame(app, &frame).unwrap();
+    fn draw(&self, ctx: &Context, draw: &Draw) {
+        let theme = &ctx.theme;
+        draw.background().color(theme.background);
+
+        let stop = match self.position.and_then(|position| self.stops.get(position)) {
+            Some(stop) => stop,
+            None => return,
+        };
+
+        let keys = stop_keys(&stop.name);
+
+        for key in keys.iter() {
+            let r = map_range(
+                NOTES.iter().position(|n| n == key).unwrap_or(0),
+                0,
+                8,
+                50,
+                200,
+            );
+
+            draw.ellipse()
+                .radius(r as f32)
+                .stroke_color(theme.muted)
+                .stroke_weight(theme.line_weight)
+                .no_fill();
         }
+
+        draw.text(&keys.join(" "))
+            .x_y(0., -50.)
+            .color(theme.foreground)
+            .font(theme.typeface())
+            .font_size(24)
+            .w(CANVAS_WIDTH as f32);
+
+        draw.text(&stop.name)
+            .x_y(0., 50.)
+            .color(theme.foreground)
+            .font(theme.typeface())
+            .font_size(32)
+            .w(CANVAS_WIDTH as f32);
+
+        ctx.draw_filter(draw);
     }
-}
 
-impl Vizualization for AudioViz {
-    fn run(&self) {
-        nannou::app(self.model())
-            .simple_window(self.view())
-            .size(CANVAS_WIDTH, CANVAS_HEIGHT)
-            .run()
+    // One stop per frame until all of them were played
+    fn is_animating(&self, _: &Context) -> bool {
+        self.position
+            .map_or(true, |position| position < self.stops.len())
     }
 }
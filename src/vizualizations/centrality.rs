@@ -0,0 +1,440 @@
+use chrono::NaiveDate;
+use gtfs_structures::Stop;
+use nannou::prelude::pt2;
+use nannou::Draw;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
+use std::thread;
+
+use super::layers::faded;
+use super::overlays::LegendEntry;
+use super::tooltip::draw_tooltip;
+use super::Context;
+use super::Viz;
+use crate::constants::CANVAS_HEIGHT;
+use crate::routing::centrality::Centrality;
+use crate::routing::centrality::Measure;
+use crate::routing::graph::StopGraph;
+use crate::utils::input::Input;
+use crate::utils::route_filter::RouteFilter;
+
+// Most central stops named on the map
+const NAMED: usize = 8;
+const LEGEND_STEPS: usize = 5;
+
+// Filter, date and weighting a centrality is computed for
+type Key = (RouteFilter, NaiveDate, bool);
+
+// Stop graph of a key with its centrality
+struct Computed {
+    graph: StopGraph,
+    centrality: Centrality,
+    // Stop of every graph node, if it has a position
+    stops: Vec<Option<Arc<Stop>>>,
+}
+
+// Betweenness, closeness or degree of every stop in the network of the trips running on the
+// shared date, as dots growing and warming with the value. The centrality is computed on a
+// background thread and kept for every key seen
+pub struct CentralityViz {
+    cache: Vec<(Key, Arc<Computed>)>,
+    // Computations still running
+    pending: Vec<(Key, Receiver<Computed>)>,
+    key: Option<Key>,
+    // Result of `key` once computed
+    current: Option<Arc<Computed>>,
+    measure: Measure,
+    weighted: bool,
+    stations: bool,
+    // Nodes from the most central down by the current measure
+    ranking: Vec<usize>,
+    max: f64,
+}
+
+impl CentralityViz {
+    pub fn new() -> Self {
+        Self {
+            cache: vec![],
+            pending: vec![],
+            key: None,
+            current: None,
+            measure: Measure::Betweenness,
+            weighted: false,
+            stations: false,
+            ranking: vec![],
+            max: 0.,
+        }
+    }
+
+    // Picks the result of the current key from the cache, or starts computing it
+    fn refresh(&mut self, ctx: &Context) {
+        let key = (ctx.filter.clone(), ctx.date, self.weighted);
+
+        let cached = self.cache.iter().find(|(k, _)| *k == key);
+        self.current = cached.map(|(_, computed)| Arc::clone(computed));
+
+        if self.current.is_none() && !self.pending.iter().any(|(k, _)| *k == key) {
+            self.pending.push((key.clone(), self.spawn(ctx)));
+        }
+
+        self.key = Some(key);
+        self.rank();
+    }
+
+    // The graph is built here, only the shortest paths searches run in the background
+    fn spawn(&self, ctx: &Context) -> Receiver<Computed> {
+        let graph = StopGraph::from_gtfs(&ctx.gtfs, &ctx.filter, Some(ctx.date), self.stations);
+        let stops = positioned_stops(ctx, &graph);
+        let weighted = self.weighted;
+
+        eprintln!(
+            "Computing the centrality of {} nodes and {} edges...",
+            graph.nodes.len(),
+            graph.edges.len()
+        );
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // The receiver is gone if the viz was dropped
+            let _ = sender.send(compute(graph, stops, weighted));
+        });
+
+        receiver
+    }
+
+    // Moves finished computations to the cache, `receive` waiting for them or not
+    fn collect(&mut self, receive: impl Fn(&Receiver<Computed>) -> Result<Computed, TryRecvError>) {
+        let mut finished = vec![];
+
+        self.pending
+            .retain(|(key, receiver)| match receive(receiver) {
+                Ok(computed) => {
+                    finished.push((key.clone(), Arc::new(computed)));
+                    false
+                }
+                Err(TryRecvError::Empty) => true,
+                Err(TryRecvError::Disconnected) => {
+                    eprintln!("Centrality computation failed");
+                    false
+                }
+            });
+
+        for (key, computed) in finished {
+            if self.key.as_ref() == Some(&key) {
+                self.current = Some(Arc::clone(&computed));
+                self.rank();
+            }
+            self.cache.push((key, computed));
+        }
+    }
+
+    fn rank(&mut self) {
+        match &self.current {
+            Some(computed) => {
+                self.ranking = computed.centrality.ranking(self.measure);
+                self.max = computed.centrality.max(self.measure);
+            }
+            None => {
+                self.ranking = vec![];
+                self.max = 0.;
+            }
+        }
+    }
+
+    // Until the first result is in
+    fn is_computing(&self) -> bool {
+        self.current.is_none() && !self.pending.is_empty()
+    }
+
+    fn share(&self, computed: &Computed, node: usize) -> f32 {
+        if self.max > 0. {
+            (computed.centrality.get(self.measure, node) / self.max) as f32
+        } else {
+            0.
+        }
+    }
+
+    fn format(&self, value: f64) -> String {
+        match self.measure {
+            Measure::Degree => format!("{value:.0}"),
+            _ => format!("{value:.3}"),
+        }
+    }
+
+    fn legend(&self, ctx: &Context) -> Vec<LegendEntry> {
+        (0..LEGEND_STEPS)
+            .rev()
+            .map(|step| {
+                let t = step as f32 / (LEGEND_STEPS - 1) as f32;
+
+                LegendEntry {
+                    label: format!(
+                        "{} {}",
+                        self.measure.name(),
+                        self.format(self.max * t as f64)
+                    ),
+                    color: ctx.theme.heat_color(t),
+                }
+            })
+            .collect()
+    }
+}
+
+// Stop of every graph node, if it has a position
+fn positioned_stops(ctx: &Context, graph: &StopGraph) -> Vec<Option<Arc<Stop>>> {
+    graph
+        .nodes
+        .iter()
+        .map(|node| {
+            ctx.gtfs
+                .stops
+                .get(&node.id)
+                .filter(|stop| stop.longitude.is_some() && stop.latitude.is_some())
+                .cloned()
+        })
+        .collect()
+}
+
+// What the background thread runs
+fn compute(graph: StopGraph, stops: Vec<Option<Arc<Stop>>>, weighted: bool) -> Computed {
+    let centrality = Centrality::from_graph(&graph, weighted);
+
+    Computed {
+        graph,
+        centrality,
+        stops,
+    }
+}
+
+impl Viz for CentralityViz {
+    // `--measure=betweenness|closeness|degree`, `--weighted` shortest paths by travel time
+    // and `--stations` to merge platforms
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.meta = ctx.extent.clone();
+
+        if self.key.is_none() {
+            self.measure = ctx
+                .args
+                .get("measure")
+                .map_or(Measure::Betweenness, |name| {
+                    Measure::from_name(name)
+                        .unwrap_or_else(|| panic!("Invalid value for --measure: {name}"))
+                });
+            self.weighted = ctx.args.has("weighted");
+            self.stations = ctx.args.has("stations");
+        }
+
+        self.refresh(ctx);
+    }
+
+    fn update(&mut self, _: &mut Context) {
+        self.collect(Receiver::try_recv);
+    }
+
+    // `m` cycles the measures, `w` switches between hops and travel times
+    fn event(&mut self, ctx: &mut Context, input: &Input) {
+        ctx.camera_event(input);
+
+        if ctx.filter_event(input) {
+            self.refresh(ctx);
+        }
+
+        match input {
+            Input::Char('m') => {
+                let idx = Measure::ALL
+                    .iter()
+                    .position(|m| *m == self.measure)
+                    .unwrap();
+                self.measure = Measure::ALL[(idx + 1) % Measure::ALL.len()];
+                self.rank();
+            }
+            Input::Char('w') => {
+                self.weighted = !self.weighted;
+                self.refresh(ctx);
+            }
+            _ => (),
+        }
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw) {
+        let theme = &ctx.theme;
+
+        draw.background().color(theme.background);
+        ctx.draw_basemap(draw);
+
+        let computed = match &self.current {
+            Some(computed) => computed,
+            None => {
+                if self.is_computing() {
+                    draw.text("Computing…")
+                        .x_y(0., CANVAS_HEIGHT as f32 / 4.)
+                        .color(theme.foreground)
+                        .font(theme.typeface())
+                        .font_size(14);
+                }
+                ctx.draw_overlays(draw, &[], None);
+                return ctx.draw_filter(draw);
+            }
+        };
+        let stops = &computed.stops;
+
+        for edge in computed.graph.edges.iter() {
+            if let (Some(a), Some(b)) = (&stops[edge.from], &stops[edge.to]) {
+                let ((x0, y0), (x1, y1)) = (ctx.project(a), ctx.project(b));
+
+                draw.line()
+                    .start(pt2(x0 as f32, y0 as f32))
+                    .end(pt2(x1 as f32, y1 as f32))
+                    .weight(0.5)
+                    .color(faded(theme.foreground, 0.15));
+            }
+        }
+
+        // The most central on top
+        for node in self.ranking.iter().rev().copied() {
+            if let Some(stop) = &stops[node] {
+                let (x, y) = ctx.project(stop);
+                let t = self.share(computed, node);
+
+                draw.ellipse()
+                    .x_y(x as f32, y as f32)
+                    .radius(theme.dot_radius * (1. + 5. * t.sqrt()))
+                    .color(theme.heat_color(t));
+            }
+        }
+
+        for node in self.ranking.iter().take(NAMED).copied() {
+            if let Some(stop) = &stops[node] {
+                let (x, y) = ctx.project(stop);
+                let offset = theme.dot_radius * (1. + 5. * self.share(computed, node).sqrt()) + 4.;
+
+                draw.text(&stop.name)
+                    .x_y(x as f32 + offset + 75., y as f32)
+                    .w(150.)
+                    .left_justify()
+                    .color(theme.foreground)
+                    .font(theme.typeface())
+                    .font_size(10);
+            }
+        }
+
+        ctx.draw_overlays(draw, &self.legend(ctx), None);
+        ctx.draw_filter(draw);
+
+        let hovered = ctx.hovered_stop(stops.iter().flatten());
+        if let Some(stop) = hovered {
+            let mut lines = ctx.stop_details(stop);
+            let node = stops
+                .iter()
+                .position(|s| s.as_ref().map_or(false, |s| Arc::ptr_eq(s, stop)));
+
+            if let Some(node) = node {
+                let rank = self.ranking.iter().position(|n| *n == node).unwrap();
+                let centrality = &computed.centrality;
+
+                lines.insert(
+                    1,
+                    format!(
+                        "#{} by {} · betweenness {:.3} · closeness {:.3} · degree {}",
+                        rank + 1,
+                        self.measure.name(),
+                        centrality.betweenness[node],
+                        centrality.closeness[node],
+                        centrality.degree[node],
+                    ),
+                );
+            }
+
+            let (x, y) = ctx.mouse;
+            draw_tooltip(draw, theme, x as f32, y as f32, &lines);
+        }
+    }
+
+    // Polls the computations still running
+    fn is_animating(&self, _: &Context) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Like `update`, waiting for the background computations to be done
+    fn settle(viz: &mut CentralityViz) {
+        viz.collect(|receiver| receiver.recv().map_err(|_| TryRecvError::Disconnected));
+        assert!(viz.pending.is_empty());
+    }
+
+    #[test]
+    fn compute_keeps_the_graph_and_positions() {
+        let ctx = Context::fixture(&["--date=20240501"]);
+        let graph = StopGraph::from_gtfs(&ctx.gtfs, &ctx.filter, Some(ctx.date), false);
+        let stops = positioned_stops(&ctx, &graph);
+
+        let computed = compute(graph, stops, false);
+
+        assert_eq!(computed.graph.nodes.len(), 4);
+        assert_eq!(computed.stops.len(), 4);
+        assert!(computed.stops.iter().all(Option::is_some));
+        assert_eq!(computed.centrality.degree.len(), 4);
+    }
+
+    #[test]
+    fn measures_cycle_without_recomputing() {
+        let mut ctx = Context::fixture(&["--date=20240501"]);
+        let mut viz = CentralityViz::new();
+        viz.enter(&mut ctx);
+        settle(&mut viz);
+
+        let computed = viz.current.clone().unwrap();
+        assert_eq!(computed.graph.nodes.len(), 4);
+        assert_eq!(viz.measure, Measure::Betweenness);
+
+        for measure in [Measure::Closeness, Measure::Degree, Measure::Betweenness] {
+            viz.event(&mut ctx, &Input::Char('m'));
+            assert_eq!(viz.measure, measure);
+            assert!(viz.pending.is_empty());
+            assert_eq!(viz.ranking, computed.centrality.ranking(measure));
+        }
+
+        // Beta is on most paths and has the most neighbours
+        let beta = computed
+            .graph
+            .nodes
+            .iter()
+            .position(|node| node.id == "B")
+            .unwrap();
+        assert_eq!(viz.ranking[0], beta);
+        viz.event(&mut ctx, &Input::Char('m'));
+        viz.event(&mut ctx, &Input::Char('m'));
+        assert_eq!(viz.measure, Measure::Degree);
+        assert_eq!(viz.ranking[0], beta);
+    }
+
+    #[test]
+    fn results_are_cached_per_key() {
+        let mut ctx = Context::fixture(&["--date=20240501"]);
+        let mut viz = CentralityViz::new();
+        viz.enter(&mut ctx);
+        settle(&mut viz);
+
+        viz.event(&mut ctx, &Input::Char('w'));
+        settle(&mut viz);
+        assert!(viz.weighted);
+        assert_eq!(viz.cache.len(), 2);
+
+        // Back to hops, already computed
+        viz.event(&mut ctx, &Input::Char('w'));
+        assert!(viz.current.is_some());
+        assert!(viz.pending.is_empty());
+
+        viz.event(&mut ctx, &Input::Char('f'));
+        assert!(viz.is_computing());
+        settle(&mut viz);
+        assert_eq!(viz.cache.len(), 3);
+        assert_eq!(viz.current.as_ref().unwrap().graph.nodes.len(), 3);
+    }
+}
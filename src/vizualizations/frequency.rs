@@ -0,0 +1,422 @@
+use chrono::NaiveDate;
+use gtfs_structures::Stop;
+use nannou::event::MouseButton;
+use nannou::prelude::pt2;
+use nannou::Draw;
+use std::sync::Arc;
+
+use super::layers::faded;
+use super::tooltip::draw_tooltip;
+use super::Context;
+use super::Viz;
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::utils::frequency::Frequencies;
+use crate::utils::input::Input;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::format_time;
+
+const BUCKET_MINUTES: u32 = 60;
+// Size of a surface cell on screen, in pixels
+const CELL: f64 = 10.;
+// Surface smoothing, in cells
+const SIGMA: f64 = 1.5;
+const FRAMES_PER_BUCKET: u64 = 15;
+// Headways at or above this many seconds get the coldest color
+const MAX_HEADWAY: f64 = 30. * 60.;
+const SLIDER_Y: f32 = -(CANVAS_HEIGHT as f32) / 2. + 40.;
+const SLIDER_WIDTH: f32 = CANVAS_WIDTH as f32 - 80.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Dots,
+    Surface,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    Departures,
+    Headway,
+}
+
+// Departures and headways of every stop in one time of day bucket, as colored dots or a
+// smoothed density surface, with a slider to move through the day
+pub struct FrequencyViz {
+    frequencies: Frequencies,
+    stops: Vec<Arc<Stop>>,
+    // Filter and date the frequencies were computed for
+    key: Option<(RouteFilter, NaiveDate)>,
+    bucket: usize,
+    mode: Mode,
+    metric: Metric,
+    // Frame playback started at and the bucket it started from
+    playing: Option<(u64, usize)>,
+    dragging: bool,
+}
+
+impl FrequencyViz {
+    pub fn new() -> Self {
+        Self {
+            frequencies: Frequencies::default(),
+            stops: vec![],
+            key: None,
+            bucket: 0,
+            mode: Mode::Dots,
+            metric: Metric::Departures,
+            playing: None,
+            dragging: false,
+        }
+    }
+
+    fn refresh(&mut self, ctx: &Context) {
+        let key = (ctx.filter.clone(), ctx.date);
+        if self.key.as_ref() == Some(&key) {
+            return;
+        }
+
+        self.frequencies =
+            Frequencies::from_gtfs(&ctx.gtfs, &ctx.filter, ctx.date, self.frequencies.bucket);
+        self.stops = self
+            .frequencies
+            .stop_ids()
+            .filter_map(|id| ctx.gtfs.stops.get(id))
+            .filter(|stop| stop.longitude.is_some() && stop.latitude.is_some())
+            .cloned()
+            .collect();
+        self.key = Some(key);
+    }
+
+    // Also moves the shared time of day, so tooltips list the departures of the bucket
+    fn set_bucket(&mut self, ctx: &mut Context, bucket: usize) {
+        self.bucket = bucket.min(self.frequencies.buckets() - 1);
+        ctx.time = (self.bucket as u32 * self.frequencies.bucket) as f64;
+    }
+
+    fn is_on_slider(&self, (x, y): (f64, f64)) -> bool {
+        (x as f32).abs() <= SLIDER_WIDTH / 2. + 8. && (y as f32 - SLIDER_Y).abs() <= 10.
+    }
+
+    fn slide_to(&mut self, ctx: &mut Context, x: f64) {
+        let t = (x as f32 + SLIDER_WIDTH / 2.) / SLIDER_WIDTH;
+        let bucket = (t.clamp(0., 1.) * self.frequencies.buckets() as f32) as usize;
+        self.set_bucket(ctx, bucket);
+    }
+
+    // Heat of a stop in the current bucket, none without departures
+    fn heat(&self, stop: &Stop, max: usize) -> Option<f32> {
+        let bucket = self.frequencies.get(&stop.id, self.bucket);
+        if bucket.departures == 0 {
+            return None;
+        }
+
+        match self.metric {
+            Metric::Departures => Some((bucket.departures as f32 / max.max(1) as f32).sqrt()),
+            Metric::Headway => {
+                let headway = bucket.headway.unwrap_or(MAX_HEADWAY);
+                Some((1. - headway.min(MAX_HEADWAY) / MAX_HEADWAY) as f32)
+            }
+        }
+    }
+
+    fn draw_dots(&self, ctx: &Context, draw: &Draw) {
+        let max = self.frequencies.max_departures(self.bucket);
+
+        for stop in self.stops.iter() {
+            if let Some(heat) = self.heat(stop, max) {
+                let (x, y) = ctx.project(stop);
+
+                draw.ellipse()
+                    .x_y(x as f32, y as f32)
+                    .radius(ctx.theme.dot_radius + 3. * heat)
+                    .color(ctx.theme.heat_color(heat));
+            }
+        }
+    }
+
+    // Departures binned into screen cells and blurred with a gaussian kernel
+    fn draw_surface(&self, ctx: &Context, draw: &Draw) {
+        let (cols, rows) = (
+            (CANVAS_WIDTH as f64 / CELL).ceil() as usize,
+            (CANVAS_HEIGHT as f64 / CELL).ceil() as usize,
+        );
+        let mut grid = vec![0.; cols * rows];
+
+        for stop in self.stops.iter() {
+            let departures = self.frequencies.get(&stop.id, self.bucket).departures;
+            let (x, y) = ctx.project(stop);
+            let col = ((x + CANVAS_WIDTH as f64 / 2.) / CELL).floor();
+            let row = ((y + CANVAS_HEIGHT as f64 / 2.) / CELL).floor();
+
+            if (0. ..cols as f64).contains(&col) && (0. ..rows as f64).contains(&row) {
+                grid[row as usize * cols + col as usize] += departures as f64;
+            }
+        }
+
+        let radius = (SIGMA * 3.).ceil() as i64;
+        let kernel = (-radius..=radius)
+            .map(|d| (-(d * d) as f64 / (2. * SIGMA * SIGMA)).exp())
+            .collect::<Vec<f64>>();
+
+        let blur = |grid: &[f64], step: (i64, i64)| {
+            let mut out = vec![0.; grid.len()];
+
+            for row in 0..rows as i64 {
+                for col in 0..cols as i64 {
+                    out[row as usize * cols + col as usize] = (-radius..=radius)
+                        .zip(kernel.iter())
+                        .map(|(d, weight)| {
+                            let (c, r) = (col + d * step.0, row + d * step.1);
+
+                            if c < 0 || r < 0 || c >= cols as i64 || r >= rows as i64 {
+                                0.
+                            } else {
+                                grid[r as usize * cols + c as usize] * weight
+                            }
+                        })
+                        .sum();
+                }
+            }
+
+            out
+        };
+
+        let grid = blur(&blur(&grid, (1, 0)), (0, 1));
+        let max = grid.iter().copied().fold(0., f64::max);
+
+        if max <= 0. {
+            return;
+        }
+
+        for (idx, value) in grid.iter().enumerate() {
+            let heat = (value / max).sqrt() as f32;
+
+            if heat < 0.05 {
+                continue;
+            }
+
+            let (col, row) = ((idx % cols) as f64, (idx / cols) as f64);
+
+            draw.rect()
+                .x_y(
+                    ((col + 0.5) * CELL - CANVAS_WIDTH as f64 / 2.) as f32,
+                    ((row + 0.5) * CELL - CANVAS_HEIGHT as f64 / 2.) as f32,
+                )
+                .w_h(CELL as f32, CELL as f32)
+                .color(faded(ctx.theme.heat_color(heat), heat));
+        }
+    }
+
+    fn draw_slider(&self, ctx: &Context, draw: &Draw) {
+        let theme = &ctx.theme;
+        let buckets = self.frequencies.buckets().max(1);
+        let left = -SLIDER_WIDTH / 2.;
+        let x = left + SLIDER_WIDTH * (self.bucket as f32 + 0.5) / buckets as f32;
+
+        draw.line()
+            .start(pt2(left, SLIDER_Y))
+            .end(pt2(-left, SLIDER_Y))
+            .weight(2.)
+            .color(theme.foreground);
+
+        draw.ellipse()
+            .x_y(x, SLIDER_Y)
+            .radius(6.)
+            .color(theme.background)
+            .stroke(theme.foreground)
+            .stroke_weight(2.);
+
+        let start = self.bucket as u32 * self.frequencies.bucket;
+        let label = format!(
+            "{}–{}",
+            format_time(start),
+            format_time(start + self.frequencies.bucket)
+        );
+
+        draw.text(&label)
+            .x_y(x, SLIDER_Y + 14.)
+            .w(100.)
+            .color(theme.foreground)
+            .font(theme.typeface())
+            .font_size(10);
+    }
+
+    fn bucket_details(&self, stop: &Stop) -> String {
+        let bucket = self.frequencies.get(&stop.id, self.bucket);
+
+        match bucket.headway {
+            Some(headway) => format!(
+                "{} departures, every {:.0} min",
+                bucket.departures,
+                headway / 60.
+            ),
+            None => format!("{} departures", bucket.departures),
+        }
+    }
+}
+
+impl Viz for FrequencyViz {
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.meta = ctx.extent.clone();
+
+        if self.key.is_none() {
+            let minutes = ctx
+                .args
+                .value("bucket")
+                .unwrap_or(BUCKET_MINUTES)
+                .clamp(1, 24 * 60);
+            self.frequencies.bucket = minutes * 60;
+
+            if ctx.args.has("surface") {
+                self.mode = Mode::Surface;
+            }
+        }
+
+        self.refresh(ctx);
+        let bucket = (ctx.time as u32 / self.frequencies.bucket) as usize;
+        self.set_bucket(ctx, bucket);
+    }
+
+    fn update(&mut self, ctx: &mut Context) {
+        self.refresh(ctx);
+
+        if let Some((started, from)) = self.playing {
+            let steps = ((ctx.clock.frame - started) / FRAMES_PER_BUCKET) as usize;
+            self.set_bucket(ctx, (from + steps) % self.frequencies.buckets());
+        }
+    }
+
+    // The slider or `,` and `.` pick the bucket, `p` plays through the day, `m` switches
+    // between dots and surface and `h` between departures and headways
+    fn event(&mut self, ctx: &mut Context, input: &Input) {
+        ctx.filter_event(input);
+
+        match input {
+            Input::MousePressed(MouseButton::Left) if self.is_on_slider(ctx.mouse) => {
+                self.dragging = true;
+                self.playing = None;
+                let x = ctx.mouse.0;
+                self.slide_to(ctx, x);
+                return;
+            }
+            Input::MouseMoved(x, _) if self.dragging => {
+                self.slide_to(ctx, *x);
+                return;
+            }
+            Input::MouseReleased(MouseButton::Left) if self.dragging => {
+                self.dragging = false;
+                return;
+            }
+            Input::Char(',') => {
+                let buckets = self.frequencies.buckets();
+                self.set_bucket(ctx, (self.bucket + buckets - 1) % buckets);
+            }
+            Input::Char('.') => {
+                self.set_bucket(ctx, (self.bucket + 1) % self.frequencies.buckets())
+            }
+            Input::Char('p') => {
+                self.playing = match self.playing {
+                    Some(_) => None,
+                    None => Some((ctx.clock.frame, self.bucket)),
+                };
+            }
+            Input::Char('m') => {
+                self.mode = match self.mode {
+                    Mode::Dots => Mode::Surface,
+                    Mode::Surface => Mode::Dots,
+                };
+            }
+            Input::Char('h') => {
+                self.metric = match self.metric {
+                    Metric::Departures => Metric::Headway,
+                    Metric::Headway => Metric::Departures,
+                };
+            }
+            _ => (),
+        }
+
+        ctx.camera_event(input);
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw) {
+        let theme = &ctx.theme;
+
+        draw.background().color(theme.background);
+        ctx.draw_basemap(draw);
+
+        match self.mode {
+            Mode::Dots => self.draw_dots(ctx, draw),
+            Mode::Surface => self.draw_surface(ctx, draw),
+        }
+
+        self.draw_slider(ctx, draw);
+        ctx.draw_overlays(draw, &[], None);
+        ctx.draw_filter(draw);
+
+        if let Some(stop) = ctx.hovered_stop(self.stops.iter()) {
+            let mut lines = ctx.stop_details(stop);
+            lines.insert(1, self.bucket_details(stop));
+
+            let (x, y) = ctx.mouse;
+            draw_tooltip(draw, theme, x as f32, y as f32, &lines);
+        }
+    }
+
+    fn is_animating(&self, _: &Context) -> bool {
+        self.playing.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_step_and_wrap_around_the_day() {
+        let mut ctx = Context::fixture(&["--date=20240501", "--time=08:15"]);
+        let mut viz = FrequencyViz::new();
+        viz.enter(&mut ctx);
+
+        assert_eq!(viz.bucket, 8);
+        assert_eq!(viz.frequencies.get("A", 8).departures, 2);
+        assert_eq!(viz.frequencies.get("A", 8).headway, Some(30. * 60.));
+
+        viz.event(&mut ctx, &Input::Char('.'));
+        assert_eq!(viz.bucket, 9);
+        assert_eq!(ctx.time, 9. * 60. * 60.);
+
+        for _ in 0..10 {
+            viz.event(&mut ctx, &Input::Char(','));
+        }
+        assert_eq!(viz.bucket, 23);
+        viz.update(&mut ctx);
+        assert_eq!(viz.bucket, 23);
+
+        viz.event(&mut ctx, &Input::Char('.'));
+        assert_eq!(viz.bucket, 0);
+    }
+
+    #[test]
+    fn yesterdays_trips_count_until_midnight() {
+        let mut ctx = Context::fixture(&["--date=20240501"]);
+        let mut viz = FrequencyViz::new();
+        viz.enter(&mut ctx);
+
+        // 47:55 of yesterday's trip, its 48:05 stop is past the day shown
+        assert_eq!(viz.frequencies.get("A", 23).departures, 1);
+        assert_eq!(viz.frequencies.get("B", 23).departures, 1);
+        assert_eq!(viz.frequencies.get("B", 0).departures, 0);
+    }
+
+    #[test]
+    fn filter_hotkeys_recompute_the_frequencies() {
+        let mut ctx = Context::fixture(&["--date=20240501"]);
+        let mut viz = FrequencyViz::new();
+        viz.enter(&mut ctx);
+        assert_eq!(viz.frequencies.get("A", 9).departures, 1);
+
+        viz.event(&mut ctx, &Input::Char('f'));
+        viz.update(&mut ctx);
+        assert_eq!(viz.frequencies.get("A", 9).departures, 0);
+        assert_eq!(viz.frequencies.get("A", 8).departures, 2);
+    }
+}
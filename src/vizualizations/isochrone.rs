@@ -0,0 +1,284 @@
+use chrono::NaiveDate;
+use nannou::color::Rgb8;
+use nannou::event::MouseButton;
+use nannou::Draw;
+
+use super::layers::faded;
+use super::overlays::LegendEntry;
+use super::stops::start_id;
+use super::tooltip::draw_tooltip;
+use super::Context;
+use super::Viz;
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::routing::csa::Timetable;
+use crate::routing::footpaths::Footpaths;
+use crate::routing::footpaths::Walking;
+use crate::utils::input::Input;
+use crate::utils::math::meters_per_px;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::format_time;
+
+const MAX_MINUTES: u32 = 60;
+// Minutes of travel time colored alike
+const BAND_MINUTES: u32 = 10;
+// Size of a contour cell on screen, in pixels
+const CELL: f64 = 6.;
+const DEPARTURE_STEP: f64 = 10. * 60.;
+
+// Travel times from a stop to every other one leaving at the shared time of day, clicking a
+// stop starts from there instead
+pub struct IsochroneViz {
+    timetable: Timetable,
+    footpaths: Footpaths,
+    walking: Walking,
+    // Filter and date the timetable was built for
+    key: Option<(RouteFilter, NaiveDate)>,
+    source: Option<String>,
+    departure: u32,
+    max_duration: u32,
+    // Seconds from the departure to every stop of the timetable
+    travel_times: Vec<Option<u32>>,
+    contours: bool,
+}
+
+impl IsochroneViz {
+    pub fn new() -> Self {
+        Self {
+            timetable: Timetable::default(),
+            footpaths: Footpaths::default(),
+            walking: Walking::default(),
+            key: None,
+            source: None,
+            departure: 0,
+            max_duration: MAX_MINUTES * 60,
+            travel_times: vec![],
+            contours: false,
+        }
+    }
+
+    // Rebuilds the timetable if the filter or the date changed and runs the search again
+    fn search(&mut self, ctx: &Context) {
+        let key = (ctx.filter.clone(), ctx.date);
+
+        if self.key.as_ref() != Some(&key) {
+            self.timetable = Timetable::from_gtfs(&ctx.gtfs, &ctx.filter, ctx.date);
+            self.footpaths = Footpaths::new(&self.timetable.stops, &self.walking, &ctx.transfers);
+            self.key = Some(key);
+        }
+
+        self.departure = ctx.time as u32;
+
+        let source = self
+            .source
+            .as_deref()
+            .and_then(|id| self.timetable.stop_index(id));
+
+        self.travel_times = match source {
+            Some(source) => self
+                .timetable
+                .earliest_arrivals(&self.footpaths, source, self.departure, self.max_duration)
+                .into_iter()
+                .map(|arrival| arrival.map(|arrival| arrival - self.departure))
+                .collect(),
+            None => vec![],
+        };
+    }
+
+    fn bands(&self) -> u32 {
+        ((self.max_duration / 60 + BAND_MINUTES - 1) / BAND_MINUTES).max(1)
+    }
+
+    fn band_color(&self, ctx: &Context, seconds: u32) -> Rgb8 {
+        let band = seconds / 60 / BAND_MINUTES;
+        ctx.theme.heat_color(1. - band as f32 / self.bands() as f32)
+    }
+
+    fn legend(&self, ctx: &Context) -> Vec<LegendEntry> {
+        (0..self.bands())
+            .map(|band| LegendEntry {
+                label: format!("{}–{} min", band * BAND_MINUTES, (band + 1) * BAND_MINUTES),
+                color: self.band_color(ctx, band * BAND_MINUTES * 60),
+            })
+            .collect()
+    }
+
+    fn reached(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.travel_times
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, time)| Some((idx, (*time)?)))
+            .filter(|(idx, _)| {
+                let stop = &self.timetable.stops[*idx];
+                stop.longitude.is_some() && stop.latitude.is_some()
+            })
+    }
+
+    // Each screen cell gets the quickest travel time of a stop in walking distance plus the
+    // walk from there
+    fn draw_contours(&self, ctx: &Context, draw: &Draw) {
+        let (cols, rows) = (
+            (CANVAS_WIDTH as f64 / CELL).ceil() as i64,
+            (CANVAS_HEIGHT as f64 / CELL).ceil() as i64,
+        );
+        let mut grid = vec![u32::MAX; (cols * rows) as usize];
+        let meters_per_px = meters_per_px(&ctx.meta, &ctx.camera);
+
+        for (idx, seconds) in self.reached() {
+            let (x, y) = ctx.project(&self.timetable.stops[idx]);
+            let (col, row) = (
+                ((x + CANVAS_WIDTH as f64 / 2.) / CELL).floor() as i64,
+                ((y + CANVAS_HEIGHT as f64 / 2.) / CELL).floor() as i64,
+            );
+
+            // Only as far as the time left allows
+            let budget = (self.max_duration - seconds) as f64 * self.walking.speed;
+            let reach = (self.walking.radius.min(budget) / meters_per_px / CELL).ceil() as i64;
+
+            for r in (row - reach).max(0)..=(row + reach).min(rows - 1) {
+                for c in (col - reach).max(0)..=(col + reach).min(cols - 1) {
+                    let meters =
+                        (((c - col) as f64).hypot((r - row) as f64)) * CELL * meters_per_px;
+
+                    if meters <= self.walking.radius {
+                        let cell = &mut grid[(r * cols + c) as usize];
+                        *cell = (*cell).min(seconds + self.walking.seconds(meters));
+                    }
+                }
+            }
+        }
+
+        for (idx, seconds) in grid.into_iter().enumerate() {
+            if seconds > self.max_duration {
+                continue;
+            }
+
+            let (col, row) = ((idx as i64 % cols) as f64, (idx as i64 / cols) as f64);
+
+            draw.rect()
+                .x_y(
+                    ((col + 0.5) * CELL - CANVAS_WIDTH as f64 / 2.) as f32,
+                    ((row + 0.5) * CELL - CANVAS_HEIGHT as f64 / 2.) as f32,
+                )
+                .w_h(CELL as f32, CELL as f32)
+                .color(faded(self.band_color(ctx, seconds), 0.6));
+        }
+    }
+
+    fn draw_stops(&self, ctx: &Context, draw: &Draw) {
+        for (idx, seconds) in self.reached() {
+            let (x, y) = ctx.project(&self.timetable.stops[idx]);
+
+            draw.ellipse()
+                .x_y(x as f32, y as f32)
+                .radius(ctx.theme.dot_radius * 2.)
+                .color(self.band_color(ctx, seconds));
+        }
+    }
+}
+
+impl Viz for IsochroneViz {
+    // Starts from `--from=<stop id>` or the stops visualization's start stop
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.meta = ctx.extent.clone();
+
+        if self.source.is_none() {
+            self.walking = Walking::from_args(&ctx.args);
+            self.max_duration = ctx.args.value("max-minutes").unwrap_or(MAX_MINUTES) * 60;
+            self.source = Some(
+                ctx.args
+                    .get("from")
+                    .map_or_else(|| start_id(&ctx.gtfs.stops).to_string(), String::from),
+            );
+        }
+
+        self.search(ctx);
+    }
+
+    fn update(&mut self, _: &mut Context) {}
+
+    // Click a stop to start from it, `,` and `.` move the departure by 10 minutes and `c`
+    // switches between stops and contours
+    fn event(&mut self, ctx: &mut Context, input: &Input) {
+        ctx.camera_event(input);
+
+        if ctx.filter_event(input) {
+            self.search(ctx);
+        }
+
+        match input {
+            Input::MouseReleased(MouseButton::Left) if !ctx.camera.was_dragged() => {
+                if let Some(stop) = ctx.hovered_stop(ctx.gtfs.stops.values()) {
+                    self.source = Some(stop.id.clone());
+                    self.search(ctx);
+                }
+            }
+            Input::Char(',') => {
+                ctx.time = (ctx.time - DEPARTURE_STEP).max(0.);
+                self.search(ctx);
+            }
+            Input::Char('.') => {
+                ctx.time += DEPARTURE_STEP;
+                self.search(ctx);
+            }
+            Input::Char('c') => self.contours = !self.contours,
+            _ => (),
+        }
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw) {
+        let theme = &ctx.theme;
+
+        draw.background().color(theme.background);
+        ctx.draw_basemap(draw);
+
+        if self.contours {
+            self.draw_contours(ctx, draw);
+        } else {
+            self.draw_stops(ctx, draw);
+        }
+
+        let source = self.source.as_deref().and_then(|id| ctx.gtfs.stops.get(id));
+
+        if let Some(stop) = source {
+            let (x, y) = ctx.project(stop);
+
+            draw.ellipse()
+                .x_y(x as f32, y as f32)
+                .radius(theme.stop_radius)
+                .no_fill()
+                .stroke(theme.foreground)
+                .stroke_weight(theme.line_weight);
+        }
+
+        ctx.draw_overlays(draw, &self.legend(ctx), Some(self.departure as f64));
+        ctx.draw_filter(draw);
+
+        let reached = self.reached().map(|(idx, _)| &self.timetable.stops[idx]);
+        if let Some(stop) = ctx.hovered_stop(reached) {
+            let mut lines = ctx.stop_details(stop);
+
+            if let Some(seconds) = self
+                .timetable
+                .stop_index(&stop.id)
+                .and_then(|idx| self.travel_times[idx])
+            {
+                lines.insert(
+                    1,
+                    format!(
+                        "Reached in {} min, at {}",
+                        seconds / 60,
+                        format_time(self.departure + seconds)
+                    ),
+                );
+            }
+
+            let (x, y) = ctx.mouse;
+            draw_tooltip(draw, theme, x as f32, y as f32, &lines);
+        }
+    }
+
+    fn is_animating(&self, _: &Context) -> bool {
+        false
+    }
+}
@@ -0,0 +1,338 @@
+use chrono::NaiveDate;
+use nannou::color::Rgb8;
+use nannou::prelude::pt2;
+use nannou::Draw;
+use rand::Rng;
+
+use super::overlays::legend_entries;
+use super::stops::start_id;
+use super::tooltip::draw_tooltip;
+use super::Context;
+use super::Viz;
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::constants::EXPORT_DIR;
+use crate::routing::csa::Timetable;
+use crate::routing::footpaths::Footpaths;
+use crate::routing::footpaths::Walking;
+use crate::routing::journey::Journey;
+use crate::routing::journey::Leg;
+use crate::routing::plan::itinerary;
+use crate::utils::input::Input;
+use crate::utils::meta::Meta;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::format_time;
+
+const DEPARTURE_STEP: f64 = 10. * 60.;
+// Length of the dashes of walking legs, in pixels
+const DASH: f32 = 4.;
+
+// The fastest journey between two stops leaving at the shared time of day, every ride in its
+// route's color like a trip poster
+pub struct JourneyViz {
+    timetable: Timetable,
+    footpaths: Footpaths,
+    walking: Walking,
+    // Filter and date the timetable was built for
+    key: Option<(RouteFilter, NaiveDate)>,
+    // Stop ids or names
+    from: Option<String>,
+    to: Option<String>,
+    journey: Option<Journey>,
+}
+
+impl JourneyViz {
+    pub fn new() -> Self {
+        Self {
+            timetable: Timetable::default(),
+            footpaths: Footpaths::default(),
+            walking: Walking::default(),
+            key: None,
+            from: None,
+            to: None,
+            journey: None,
+        }
+    }
+
+    // Rebuilds the timetable if the filter or the date changed and plans again
+    fn search(&mut self, ctx: &mut Context) {
+        let key = (ctx.filter.clone(), ctx.date);
+
+        if self.key.as_ref() != Some(&key) {
+            self.timetable = Timetable::from_gtfs(&ctx.gtfs, &ctx.filter, ctx.date);
+            self.footpaths = Footpaths::new(&self.timetable.stops, &self.walking, &ctx.transfers);
+            self.key = Some(key);
+        }
+
+        let (from, to) = match (&self.from, &self.to) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return,
+        };
+
+        let sources = self.timetable.find_stops(from);
+        let targets = self.timetable.find_stops(to);
+
+        self.journey = self
+            .timetable
+            .journey(&self.footpaths, &sources, &targets, ctx.time as u32);
+
+        if matches!(&self.journey, Some(journey) if journey.legs.is_empty()) {
+            eprintln!("{from} and {to} are the same stop, already there");
+            self.journey = None;
+            return;
+        }
+
+        match &self.journey {
+            Some(journey) => {
+                ctx.meta = Meta::from_coords(self.stops(journey).iter().filter_map(|idx| {
+                    let stop = &self.timetable.stops[*idx];
+                    Some((stop.longitude?, stop.latitude?))
+                }));
+                ctx.camera.reset();
+            }
+            None => eprintln!(
+                "No connection from {from} to {to} after {}",
+                format_time(ctx.time as u32)
+            ),
+        }
+    }
+
+    // Somewhere served by a vehicle to go to
+    fn random_destination(&mut self) {
+        let stops = self
+            .timetable
+            .connections
+            .iter()
+            .map(|c| c.to)
+            .collect::<Vec<usize>>();
+
+        if !stops.is_empty() {
+            let idx = stops[rand::thread_rng().gen_range(0..stops.len())];
+            self.to = Some(self.timetable.stops[idx].id.clone());
+        }
+    }
+
+    // Every stop the journey passes, in order
+    fn stops(&self, journey: &Journey) -> Vec<usize> {
+        journey
+            .legs
+            .iter()
+            .flat_map(|leg| match leg {
+                Leg::Ride { stops, .. } => stops.clone(),
+                Leg::Walk { from, to, .. } => vec![*from, *to],
+            })
+            .collect()
+    }
+
+    fn project(&self, ctx: &Context, idx: usize) -> (f32, f32) {
+        let (x, y) = ctx.project(&self.timetable.stops[idx]);
+        (x as f32, y as f32)
+    }
+
+    fn ride_color(&self, ctx: &Context, trip_id: &str) -> Rgb8 {
+        ctx.gtfs
+            .trips
+            .get(trip_id)
+            .and_then(|trip| ctx.gtfs.routes.get(&trip.route_id))
+            .map_or(ctx.theme.foreground, |route| {
+                let color = route.route_color;
+                Rgb8::new(color.r, color.g, color.b)
+            })
+    }
+
+    fn draw_walk(&self, draw: &Draw, color: Rgb8, (x0, y0): (f32, f32), (x1, y1): (f32, f32)) {
+        let length = (x1 - x0).hypot(y1 - y0);
+        let dashes = (length / (DASH * 2.)).ceil().max(1.) as usize;
+
+        for dash in 0..dashes {
+            let t0 = dash as f32 * DASH * 2. / length;
+            let t1 = ((dash as f32 * 2. + 1.) * DASH / length).min(1.);
+
+            if t0 >= 1. {
+                break;
+            }
+
+            draw.line()
+                .start(pt2(x0 + (x1 - x0) * t0, y0 + (y1 - y0) * t0))
+                .end(pt2(x0 + (x1 - x0) * t1, y0 + (y1 - y0) * t1))
+                .weight(1.5)
+                .color(color);
+        }
+    }
+}
+
+impl Viz for JourneyViz {
+    // From `--from` to `--to` (stop ids or names), from the stops visualization's start stop
+    // to a random one by default
+    fn enter(&mut self, ctx: &mut Context) {
+        if self.key.is_none() {
+            self.walking = Walking::from_args(&ctx.args);
+            self.from = Some(
+                ctx.args
+                    .get("from")
+                    .map_or_else(|| start_id(&ctx.gtfs.stops).to_string(), String::from),
+            );
+            self.to = ctx.args.get("to").map(String::from);
+        }
+
+        if self.to.is_none() {
+            // The timetable has to be built to pick a destination it serves
+            self.search(ctx);
+            self.random_destination();
+        }
+
+        self.search(ctx);
+    }
+
+    fn update(&mut self, _: &mut Context) {}
+
+    // Space plans to a random stop, `,` and `.` move the departure by 10 minutes and `r` saves
+    // a PNG
+    fn event(&mut self, ctx: &mut Context, input: &Input) {
+        ctx.camera_event(input);
+
+        if ctx.filter_event(input) {
+            self.search(ctx);
+        }
+
+        match input {
+            Input::Char(' ') => {
+                self.random_destination();
+                self.search(ctx);
+            }
+            Input::Char(',') => {
+                ctx.time = (ctx.time - DEPARTURE_STEP).max(0.);
+                self.search(ctx);
+            }
+            Input::Char('.') => {
+                ctx.time += DEPARTURE_STEP;
+                self.search(ctx);
+            }
+            Input::Char('r') => {
+                if let (Some(from), Some(to)) = (&self.from, &self.to) {
+                    ctx.save_frame(format!("{EXPORT_DIR}/journeys/journey_{from}_{to}.png"));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw) {
+        let theme = &ctx.theme;
+
+        draw.background().color(theme.background);
+        ctx.draw_basemap(draw);
+
+        let journey = match &self.journey {
+            Some(journey) => journey,
+            None => {
+                ctx.draw_overlays(draw, &[], Some(ctx.time));
+                return ctx.draw_filter(draw);
+            }
+        };
+
+        for leg in journey.legs.iter() {
+            match leg {
+                Leg::Ride { trip_id, stops, .. } => {
+                    let color = self.ride_color(ctx, trip_id);
+                    let points = stops.iter().map(|idx| {
+                        let (x, y) = self.project(ctx, *idx);
+                        pt2(x, y)
+                    });
+
+                    draw.polyline()
+                        .weight(theme.line_weight)
+                        .join_round()
+                        .points(points)
+                        .color(color);
+
+                    for idx in stops.iter() {
+                        let (x, y) = self.project(ctx, *idx);
+
+                        draw.ellipse()
+                            .stroke(color)
+                            .stroke_weight(theme.line_weight)
+                            .color(theme.route_background(color))
+                            .radius(theme.stop_radius)
+                            .x_y(x, y);
+                    }
+                }
+                Leg::Walk { from, to, .. } => {
+                    let (a, b) = (self.project(ctx, *from), self.project(ctx, *to));
+                    self.draw_walk(draw, theme.foreground, a, b);
+                }
+            }
+        }
+
+        // Where each ride is boarded, and the destination
+        for leg in journey.legs.iter() {
+            if let Leg::Ride {
+                stops, departure, ..
+            } = leg
+            {
+                let stop = &self.timetable.stops[stops[0]];
+                let (x, y) = self.project(ctx, stops[0]);
+
+                draw.text(&format!("{} {}", format_time(*departure), stop.name))
+                    .x_y(x + 4. + 75., y + 10.)
+                    .w(150.)
+                    .left_justify()
+                    .color(theme.foreground)
+                    .font(theme.typeface())
+                    .font_size(10);
+            }
+        }
+
+        if let Some(last) = self.stops(journey).last() {
+            let stop = &self.timetable.stops[*last];
+            let (x, y) = self.project(ctx, *last);
+
+            draw.text(&format!("{} {}", format_time(journey.arrival()), stop.name))
+                .x_y(x + 4. + 75., y + 10.)
+                .w(150.)
+                .left_justify()
+                .color(theme.foreground)
+                .font(theme.typeface())
+                .font_size(10);
+        }
+
+        // The itinerary in the top left corner, below the title
+        let top = CANVAS_HEIGHT as f32 / 2. - 16. - ctx.title_height();
+        let lines = itinerary(&ctx.gtfs, &self.timetable, journey);
+        for (idx, line) in lines.iter().enumerate() {
+            draw.text(line)
+                .x_y(0., top - idx as f32 * 14.)
+                .w(CANVAS_WIDTH as f32 - 16.)
+                .left_justify()
+                .color(theme.foreground)
+                .font(theme.typeface())
+                .font_size(if idx == 0 { 12 } else { 9 });
+        }
+
+        let mut routes = journey
+            .legs
+            .iter()
+            .filter_map(|leg| match leg {
+                Leg::Ride { trip_id, .. } => ctx.gtfs.trips.get(trip_id),
+                Leg::Walk { .. } => None,
+            })
+            .filter_map(|trip| ctx.gtfs.routes.get(&trip.route_id))
+            .collect::<Vec<_>>();
+        routes.dedup_by(|a, b| a.id == b.id);
+
+        ctx.draw_overlays(draw, &legend_entries(routes.into_iter()), Some(ctx.time));
+        ctx.draw_filter(draw);
+
+        let stops = self.stops(journey);
+        let hovered = ctx.hovered_stop(stops.iter().map(|idx| &self.timetable.stops[*idx]));
+        if let Some(stop) = hovered {
+            let lines = ctx.stop_details(stop);
+            let (x, y) = ctx.mouse;
+            draw_tooltip(draw, theme, x as f32, y as f32, &lines);
+        }
+    }
+
+    fn is_animating(&self, _: &Context) -> bool {
+        false
+    }
+}
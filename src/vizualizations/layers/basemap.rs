@@ -0,0 +1,15 @@
+use nannou::Draw;
+
+use super::Layer;
+use crate::vizualizations::Context;
+
+// The `--basemap` underlay, rendered by the shell with the opacity the map sets on the context
+pub struct BasemapLayer;
+
+impl Layer for BasemapLayer {
+    fn update(&mut self, _: &Context) {}
+
+    fn draw(&self, ctx: &Context, draw: &Draw, _: f32) {
+        ctx.draw_basemap(draw);
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::NaiveDate;
+use nannou::Draw;
+use std::collections::HashMap;
+
+use super::faded;
+use super::Layer;
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::runs_on;
+use crate::vizualizations::Context;
+
+// Size of a heatmap cell on screen, in pixels
+const CELL: f64 = 16.;
+
+// Departures on the selected date summed over a screen grid, brighter where service is denser
+#[derive(Default)]
+pub struct HeatmapLayer {
+    // (lng, lat) of every stop with its number of departures
+    departures: Vec<(f64, f64, usize)>,
+    key: Option<(RouteFilter, NaiveDate)>,
+}
+
+impl Layer for HeatmapLayer {
+    fn update(&mut self, ctx: &Context) {
+        let key = (ctx.filter.clone(), ctx.date);
+        if self.key.as_ref() == Some(&key) {
+            return;
+        }
+
+        let gtfs = &ctx.gtfs;
+        let mut services = HashMap::new();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+
+        for trip in gtfs.trips.values() {
+            let runs = *services
+                .entry(trip.service_id.as_str())
+                .or_insert_with(|| runs_on(gtfs, &trip.service_id, ctx.date));
+
+            if !runs || !ctx.filter.keeps_trip(gtfs, trip) {
+                continue;
+            }
+
+            for st in trip.stop_times.iter() {
+                *counts.entry(st.stop.id.as_str()).or_default() += 1;
+            }
+        }
+
+        self.departures = counts
+            .into_iter()
+            .filter_map(|(id, count)| {
+                let stop = gtfs.stops.get(id)?;
+                Some((stop.longitude?, stop.latitude?, count))
+            })
+            .collect();
+        self.key = Some(key);
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw, opacity: f32) {
+        let (w, h) = (CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+        let mut cells: HashMap<(i64, i64), usize> = HashMap::new();
+
+        for (lng, lat, count) in self.departures.iter() {
+            let (x, y) = ctx.project_coordinate(*lng, *lat);
+
+            if x.abs() <= w / 2. && y.abs() <= h / 2. {
+                let cell = ((x / CELL).floor() as i64, (y / CELL).floor() as i64);
+                *cells.entry(cell).or_default() += count;
+            }
+        }
+
+        let max = cells.values().copied().max().unwrap_or(1) as f32;
+
+        for ((cx, cy), count) in cells.into_iter() {
+            // Square root so a few busy hubs don't wash out the rest
+            let heat = (count as f32 / max).sqrt();
+
+            draw.rect()
+                .x_y(
+                    ((cx as f64 + 0.5) * CELL) as f32,
+                    ((cy as f64 + 0.5) * CELL) as f32,
+                )
+                .w_h(CELL as f32, CELL as f32)
+                .color(faded(ctx.theme.foreground, opacity * heat * 0.8));
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use nannou::Draw;
+
+use super::Layer;
+use crate::utils::labels::Placement;
+use crate::utils::route_filter::RouteFilter;
+use crate::vizualizations::stops::draw_station_labels;
+use crate::vizualizations::stops::station_labels;
+use crate::vizualizations::Context;
+
+// Names of the busiest stations served by a trip passing the filter
+#[derive(Default)]
+pub struct LabelsLayer {
+    labels: Vec<(String, Placement)>,
+    filter: Option<RouteFilter>,
+}
+
+impl Layer for LabelsLayer {
+    fn update(&mut self, ctx: &Context) {
+        if self.filter.as_ref() == Some(&ctx.filter) {
+            return;
+        }
+
+        let stops = ctx.kept_stops();
+        self.labels = station_labels(ctx, stops.values());
+        self.filter = Some(ctx.filter.clone());
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw, opacity: f32) {
+        draw_station_labels(ctx, draw, &self.labels, opacity);
+    }
+}
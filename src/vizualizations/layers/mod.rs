@@ -0,0 +1,246 @@
+pub mod basemap;
+pub mod heatmap;
+pub mod labels;
+pub mod network;
+pub mod stops;
+pub mod transfers;
+pub mod vehicles;
+
+use nannou::color::rgba8;
+use nannou::color::Rgb8;
+use nannou::color::Rgba8;
+use nannou::Draw;
+
+use self::basemap::BasemapLayer;
+use self::heatmap::HeatmapLayer;
+use self::labels::LabelsLayer;
+use self::network::NetworkLayer;
+use self::stops::StopsLayer;
+use self::transfers::TransfersLayer;
+use self::vehicles::VehiclesLayer;
+use super::Context;
+use crate::args::Args;
+use crate::utils::input::Input;
+
+const OPACITY_STEP: f32 = 0.1;
+
+// One independent part of the map, drawn with the opacity of its slot in the stack
+pub trait Layer {
+    // Only called while the layer is visible, caches whatever doesn't change every frame
+    fn update(&mut self, ctx: &Context);
+    fn draw(&self, ctx: &Context, draw: &Draw, opacity: f32);
+
+    fn is_animating(&self, _: &Context) -> bool {
+        false
+    }
+}
+
+pub struct Entry {
+    pub name: &'static str,
+    pub new: fn() -> Box<dyn Layer>,
+}
+
+// Every layer in the default order, bottom to top, `1` toggles the first one and so on
+pub const LAYERS: [Entry; 7] = [
+    Entry {
+        name: "basemap",
+        new: || Box::new(BasemapLayer),
+    },
+    Entry {
+        name: "heatmap",
+        new: || Box::new(HeatmapLayer::default()),
+    },
+    Entry {
+        name: "network",
+        new: || Box::new(NetworkLayer::default()),
+    },
+    Entry {
+        name: "stops",
+        new: || Box::new(StopsLayer::default()),
+    },
+    Entry {
+        name: "vehicles",
+        new: || Box::new(VehiclesLayer::default()),
+    },
+    Entry {
+        name: "labels",
+        new: || Box::new(LabelsLayer::default()),
+    },
+    Entry {
+        name: "transfers",
+        new: || Box::new(TransfersLayer::default()),
+    },
+];
+
+pub fn faded(color: Rgb8, opacity: f32) -> Rgba8 {
+    let alpha = (opacity.clamp(0., 1.) * 255.).round() as u8;
+    rgba8(color.red, color.green, color.blue, alpha)
+}
+
+struct Slot {
+    name: &'static str,
+    visible: bool,
+    opacity: f32,
+    layer: Box<dyn Layer>,
+}
+
+// Layers bottom to top, each with its own visibility and opacity
+pub struct LayerStack {
+    slots: Vec<Slot>,
+    // Layer the opacity and order keys apply to, the last one toggled
+    selected: &'static str,
+}
+
+impl LayerStack {
+    // `--layers=basemap,network,stops` picks the visible layers bottom to top, the others
+    // are stacked hidden above them, `--opacity=heatmap:0.5,basemap:0.7` sets opacities
+    pub fn from_args(args: &Args) -> Self {
+        let visible = args.get("layers").map_or_else(
+            || vec!["basemap", "network", "stops", "vehicles", "labels"],
+            |layers| layers.split(',').map(str::trim).collect(),
+        );
+
+        let mut slots = vec![];
+        for name in visible.iter() {
+            let entry = LAYERS
+                .iter()
+                .find(|entry| entry.name == *name)
+                .unwrap_or_else(|| panic!("Invalid value for --layers: {name}"));
+
+            slots.push(Slot::new(entry, true));
+        }
+
+        for entry in LAYERS.iter() {
+            if !visible.contains(&entry.name) {
+                slots.push(Slot::new(entry, false));
+            }
+        }
+
+        let mut stack = Self {
+            slots,
+            selected: LAYERS[0].name,
+        };
+
+        for spec in args.get("opacity").into_iter().flat_map(|s| s.split(',')) {
+            let parsed = spec.split_once(':').and_then(|(name, opacity)| {
+                Some((name.trim(), opacity.trim().parse::<f32>().ok()?))
+            });
+
+            match parsed.and_then(|(name, opacity)| Some((stack.slot_mut(name)?, opacity))) {
+                Some((slot, opacity)) => slot.opacity = opacity.clamp(0., 1.),
+                None => panic!("Invalid value for --opacity: {spec}"),
+            }
+        }
+
+        stack
+    }
+
+    fn slot(&self, name: &str) -> Option<&Slot> {
+        self.slots.iter().find(|slot| slot.name == name)
+    }
+
+    fn slot_mut(&mut self, name: &str) -> Option<&mut Slot> {
+        self.slots.iter_mut().find(|slot| slot.name == name)
+    }
+
+    // Opacity of the layer if it's shown
+    pub fn opacity(&self, name: &str) -> Option<f32> {
+        self.slot(name)
+            .filter(|slot| slot.visible)
+            .map(|slot| slot.opacity)
+    }
+
+    // Number keys toggle layers, `[` and `]` change the opacity of the last one toggled,
+    // `<` and `>` move it down or up, returns whether the stack changed
+    pub fn event(&mut self, input: &Input) -> bool {
+        let c = match input {
+            Input::Char(c) => *c,
+            _ => return false,
+        };
+
+        let selected = self.selected;
+        let idx = self
+            .slots
+            .iter()
+            .position(|slot| slot.name == selected)
+            .unwrap();
+
+        match c {
+            '1'..='9' => {
+                let entry = match LAYERS.get(c as usize - '1' as usize) {
+                    Some(entry) => entry,
+                    None => return false,
+                };
+
+                let slot = self.slot_mut(entry.name).unwrap();
+                slot.visible = !slot.visible;
+                self.selected = entry.name;
+            }
+            '[' => self.slots[idx].opacity = (self.slots[idx].opacity - OPACITY_STEP).max(0.),
+            ']' => self.slots[idx].opacity = (self.slots[idx].opacity + OPACITY_STEP).min(1.),
+            '<' if idx > 0 => self.slots.swap(idx, idx - 1),
+            '>' if idx + 1 < self.slots.len() => self.slots.swap(idx, idx + 1),
+            _ => return false,
+        }
+
+        eprintln!("Layers: {}", self.describe());
+        true
+    }
+
+    pub fn update(&mut self, ctx: &Context) {
+        for slot in self.slots.iter_mut().filter(|slot| slot.visible) {
+            slot.layer.update(ctx);
+        }
+    }
+
+    pub fn draw(&self, ctx: &Context, draw: &Draw) {
+        for slot in self
+            .slots
+            .iter()
+            .filter(|slot| slot.visible && slot.opacity > 0.)
+        {
+            slot.layer.draw(ctx, draw, slot.opacity);
+        }
+    }
+
+    pub fn is_animating(&self, ctx: &Context) -> bool {
+        self.slots
+            .iter()
+            .any(|slot| slot.visible && slot.layer.is_animating(ctx))
+    }
+
+    // Bottom to top, e.g. "basemap 70%, network, [stops], -heatmap" with the selected layer
+    // in brackets and hidden ones prefixed with `-`
+    pub fn describe(&self) -> String {
+        self.slots
+            .iter()
+            .map(|slot| {
+                let mut text = String::from(slot.name);
+
+                if slot.opacity < 1. {
+                    text.push_str(&format!(" {:.0}%", slot.opacity * 100.));
+                }
+                if !slot.visible {
+                    text.insert(0, '-');
+                }
+                if slot.name == self.selected {
+                    text = format!("[{text}]");
+                }
+
+                text
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+impl Slot {
+    fn new(entry: &Entry, visible: bool) -> Self {
+        Self {
+            name: entry.name,
+            visible,
+            opacity: 1.,
+            layer: (entry.new)(),
+        }
+    }
+}
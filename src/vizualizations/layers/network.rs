@@ -0,0 +1,88 @@
+use nannou::color::Rgb8;
+use nannou::prelude::pt2;
+use nannou::Draw;
+use std::collections::HashMap;
+
+use super::faded;
+use super::Layer;
+use crate::utils::route_filter::RouteFilter;
+use crate::vizualizations::Context;
+
+// Every route passing the filter in its own color, one line per distinct shape
+#[derive(Default)]
+pub struct NetworkLayer {
+    // Route color and (lng, lat) points
+    lines: Vec<(Rgb8, Vec<(f64, f64)>)>,
+    filter: Option<RouteFilter>,
+}
+
+impl Layer for NetworkLayer {
+    fn update(&mut self, ctx: &Context) {
+        if self.filter.as_ref() == Some(&ctx.filter) {
+            return;
+        }
+
+        let gtfs = &ctx.gtfs;
+        let mut lines = HashMap::new();
+
+        for trip in gtfs.trips.values() {
+            if !ctx.filter.keeps_trip(gtfs, trip) {
+                continue;
+            }
+
+            // Trips without a shape are drawn stop to stop
+            let key = match &trip.shape_id {
+                Some(shape_id) => shape_id.clone(),
+                None => trip
+                    .stop_times
+                    .iter()
+                    .map(|st| st.stop.id.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(" "),
+            };
+
+            lines.entry((key, &trip.route_id)).or_insert_with(|| {
+                let points = match trip.shape_id.as_ref().and_then(|id| gtfs.shapes.get(id)) {
+                    Some(shape) => shape.iter().map(|s| (s.longitude, s.latitude)).collect(),
+                    None => trip
+                        .stop_times
+                        .iter()
+                        .filter_map(|st| Some((st.stop.longitude?, st.stop.latitude?)))
+                        .collect(),
+                };
+
+                let color = gtfs
+                    .routes
+                    .get(&trip.route_id)
+                    .map_or(ctx.theme.muted, |route| {
+                        let color = route.route_color;
+                        Rgb8::new(color.r, color.g, color.b)
+                    });
+
+                (color, points)
+            });
+        }
+
+        // Sorted so overlapping lines stack the same way every time
+        let mut lines = lines.into_iter().collect::<Vec<_>>();
+        lines.sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.lines = lines.into_iter().map(|(_, line)| line).collect();
+        self.filter = Some(ctx.filter.clone());
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw, opacity: f32) {
+        for (color, points) in self.lines.iter() {
+            let points = points.iter().map(|(lng, lat)| {
+                let (x, y) = ctx.project_coordinate(*lng, *lat);
+                pt2(x as f32, y as f32)
+            });
+
+            draw.polyline()
+                .weight(ctx.theme.line_weight / 2.)
+                .join_round()
+                .points(points)
+                .color(faded(*color, opacity));
+        }
+    }
+}
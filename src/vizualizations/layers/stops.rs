@@ -0,0 +1,39 @@
+use gtfs_structures::Stop;
+use nannou::Draw;
+use std::sync::Arc;
+
+use super::faded;
+use super::Layer;
+use crate::utils::route_filter::RouteFilter;
+use crate::vizualizations::Context;
+
+// A dot for every stop served by a trip passing the filter
+#[derive(Default)]
+pub struct StopsLayer {
+    stops: Vec<Arc<Stop>>,
+    filter: Option<RouteFilter>,
+}
+
+impl Layer for StopsLayer {
+    fn update(&mut self, ctx: &Context) {
+        if self.filter.as_ref() == Some(&ctx.filter) {
+            return;
+        }
+
+        self.stops = ctx.kept_stops().into_values().collect();
+        self.filter = Some(ctx.filter.clone());
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw, opacity: f32) {
+        let color = faded(ctx.theme.foreground, opacity);
+
+        for stop in self.stops.iter() {
+            let (x, y) = ctx.project(stop);
+
+            draw.ellipse()
+                .x_y(x as f32, y as f32)
+                .radius(ctx.theme.dot_radius * 1.5)
+                .color(color);
+        }
+    }
+}
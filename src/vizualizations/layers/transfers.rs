@@ -0,0 +1,88 @@
+use gtfs_structures::Stop;
+use nannou::color::Rgb8;
+use nannou::prelude::pt2;
+use nannou::Draw;
+use std::sync::Arc;
+
+use super::faded;
+use super::Layer;
+use crate::routing::footpaths::Footpaths;
+use crate::routing::footpaths::Walking;
+use crate::routing::transfers::TransferKind;
+use crate::utils::route_filter::RouteFilter;
+use crate::vizualizations::Context;
+
+// Transfer links between stops served by the filter, walks faint, station links brighter and
+// those of `transfers.txt` highlighted
+#[derive(Default)]
+pub struct TransfersLayer {
+    stops: Vec<Arc<Stop>>,
+    footpaths: Option<Footpaths>,
+    // Both ends and the kind, every pair once
+    links: Vec<(usize, usize, TransferKind)>,
+    filter: Option<RouteFilter>,
+}
+
+impl TransfersLayer {
+    fn color(ctx: &Context, kind: TransferKind) -> (Rgb8, f32) {
+        match kind {
+            TransferKind::Walk => (ctx.theme.foreground, 0.3),
+            TransferKind::Station => (ctx.theme.foreground, 0.8),
+            TransferKind::Feed => (ctx.theme.heat_color(1.), 1.),
+        }
+    }
+}
+
+impl Layer for TransfersLayer {
+    // The graph is built once with `--walk-radius` and `--walk-speed`, only the links shown
+    // follow the filter
+    fn update(&mut self, ctx: &Context) {
+        if self.filter.as_ref() == Some(&ctx.filter) {
+            return;
+        }
+
+        if self.footpaths.is_none() {
+            let mut stops = ctx.gtfs.stops.values().cloned().collect::<Vec<Arc<Stop>>>();
+            stops.sort_by(|a, b| a.id.cmp(&b.id));
+
+            let walking = Walking::from_args(&ctx.args);
+            self.footpaths = Some(Footpaths::new(&stops, &walking, &ctx.transfers));
+            self.stops = stops;
+        }
+
+        let kept = ctx.kept_stops();
+        let shown = |idx: usize| {
+            let stop = &self.stops[idx];
+            kept.contains_key(&stop.id) && stop.longitude.is_some() && stop.latitude.is_some()
+        };
+
+        let footpaths = self.footpaths.as_ref().unwrap();
+        self.links = footpaths
+            .links()
+            .filter(|(from, transfer)| {
+                // One way only when there is a way back
+                let back = footpaths.from(transfer.to).iter().any(|t| t.to == *from);
+                (*from < transfer.to || !back) && shown(*from) && shown(transfer.to)
+            })
+            .map(|(from, transfer)| (from, transfer.to, transfer.kind))
+            .collect();
+        // Highlighted links on top
+        self.links.sort_by_key(|(_, _, kind)| *kind);
+
+        self.filter = Some(ctx.filter.clone());
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw, opacity: f32) {
+        for (from, to, kind) in self.links.iter() {
+            let (x0, y0) = ctx.project(&self.stops[*from]);
+            let (x1, y1) = ctx.project(&self.stops[*to]);
+            let (color, alpha) = Self::color(ctx, *kind);
+
+            draw.line()
+                .start(pt2(x0 as f32, y0 as f32))
+                .end(pt2(x1 as f32, y1 as f32))
+                .weight(ctx.theme.line_weight / 2.)
+                .color(faded(color, opacity * alpha));
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::Duration;
+use chrono::NaiveDate;
+use nannou::color::Rgb8;
+use nannou::Draw;
+use std::collections::HashMap;
+
+use super::faded;
+use super::Layer;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::runs_on;
+use crate::vizualizations::Context;
+
+const DAY: f64 = 86_400.;
+
+// A trip on the selected date, its times shifted so the previous day's trips running past
+// midnight line up with this one's
+struct Run {
+    color: Rgb8,
+    // (seconds past midnight, lng, lat) at every arrival and departure, in order
+    events: Vec<(f64, f64, f64)>,
+}
+
+impl Run {
+    // Where the vehicle is at `time`, interpolated linearly between stops
+    fn position(&self, time: f64) -> Option<(f64, f64)> {
+        let (first, last) = (self.events.first()?, self.events.last()?);
+        if time < first.0 || time > last.0 {
+            return None;
+        }
+
+        let idx = self.events.partition_point(|event| event.0 <= time);
+        let (t0, lng0, lat0) = self.events[idx - 1];
+        let (t1, lng1, lat1) = match self.events.get(idx) {
+            Some(event) => *event,
+            None => return Some((lng0, lat0)),
+        };
+
+        let t = if t1 > t0 { (time - t0) / (t1 - t0) } else { 0. };
+        Some((lng0 + t * (lng1 - lng0), lat0 + t * (lat1 - lat0)))
+    }
+}
+
+// Every trip passing the filter moving along its stops at the context's time of day
+#[derive(Default)]
+pub struct VehiclesLayer {
+    runs: Vec<Run>,
+    key: Option<(RouteFilter, NaiveDate)>,
+}
+
+impl Layer for VehiclesLayer {
+    fn update(&mut self, ctx: &Context) {
+        let key = (ctx.filter.clone(), ctx.date);
+        if self.key.as_ref() == Some(&key) {
+            return;
+        }
+
+        let gtfs = &ctx.gtfs;
+        let days = [(ctx.date - Duration::days(1), -DAY), (ctx.date, 0.)];
+        let mut services = HashMap::new();
+
+        self.runs.clear();
+
+        for trip in gtfs.trips.values() {
+            if !ctx.filter.keeps_trip(gtfs, trip) {
+                continue;
+            }
+
+            let color = gtfs
+                .routes
+                .get(&trip.route_id)
+                .map_or(ctx.theme.foreground, |route| {
+                    let color = route.route_color;
+                    Rgb8::new(color.r, color.g, color.b)
+                });
+
+            for (date, offset) in days {
+                let runs = *services
+                    .entry((trip.service_id.as_str(), date))
+                    .or_insert_with(|| runs_on(gtfs, &trip.service_id, date));
+
+                if !runs {
+                    continue;
+                }
+
+                let mut events = vec![];
+                for st in trip.stop_times.iter() {
+                    let (lng, lat) = match (st.stop.longitude, st.stop.latitude) {
+                        (Some(lng), Some(lat)) => (lng, lat),
+                        _ => continue,
+                    };
+
+                    // Stops without times are skipped, the vehicle passes them in a straight line
+                    let arrival = st.arrival_time.or(st.departure_time);
+                    let departure = st.departure_time.or(arrival);
+
+                    for time in [arrival, departure].into_iter().flatten() {
+                        events.push((time as f64 + offset, lng, lat));
+                    }
+                }
+
+                events.dedup_by(|a, b| a.0 == b.0);
+
+                let running = events.last().map_or(false, |event| event.0 >= 0.);
+                if events.len() >= 2 && running {
+                    self.runs.push(Run { color, events });
+                }
+            }
+        }
+
+        self.key = Some(key);
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw, opacity: f32) {
+        for run in self.runs.iter() {
+            if let Some((lng, lat)) = run.position(ctx.time) {
+                let (x, y) = ctx.project_coordinate(lng, lat);
+
+                draw.ellipse()
+                    .x_y(x as f32, y as f32)
+                    .radius(ctx.theme.stop_radius / 2.)
+                    .color(faded(run.color, opacity));
+            }
+        }
+    }
+
+    // Moves with the clock
+    fn is_animating(&self, _: &Context) -> bool {
+        true
+    }
+}
@@ -0,0 +1,129 @@
+use nannou::Draw;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::layers::LayerStack;
+use super::overlays::legend_entries;
+use super::overlays::LegendEntry;
+use super::Context;
+use super::Viz;
+use crate::utils::input::Input;
+use crate::utils::route_filter::RouteFilter;
+
+const DAY: f64 = 86_400.;
+// Simulated seconds per real second
+const SPEED: f64 = 60.;
+
+// The layers stacked on one map, with the vehicles moving at `--speed` times real time
+pub struct MapViz {
+    layers: Option<LayerStack>,
+    speed: f64,
+    paused: bool,
+    // Time since the window opened as of the last update the clock ran in
+    last: Option<Duration>,
+    // Routes passing the filter it was built with
+    legend: Vec<LegendEntry>,
+    legend_filter: Option<RouteFilter>,
+}
+
+impl MapViz {
+    pub fn new() -> Self {
+        Self {
+            layers: None,
+            speed: SPEED,
+            paused: false,
+            last: None,
+            legend: vec![],
+            legend_filter: None,
+        }
+    }
+
+    fn layers(&self) -> &LayerStack {
+        self.layers.as_ref().unwrap()
+    }
+
+    fn is_playing(&self, ctx: &Context) -> bool {
+        !self.paused && self.layers().is_animating(ctx)
+    }
+}
+
+impl Viz for MapViz {
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.meta = ctx.extent.clone();
+
+        if self.layers.is_none() {
+            self.layers = Some(LayerStack::from_args(&ctx.args));
+            self.speed = ctx.args.value("speed").unwrap_or(SPEED);
+        }
+
+        self.last = None;
+    }
+
+    fn update(&mut self, ctx: &mut Context) {
+        if self.is_playing(ctx) {
+            // Fixed steps while recording so the footage plays at the right speed
+            let elapsed = if ctx.recorder.is_active() {
+                ctx.recorder.frame_duration()
+            } else {
+                self.last
+                    .map_or(Duration::ZERO, |last| ctx.clock.since_start - last)
+            };
+
+            ctx.time = (ctx.time + elapsed.as_secs_f64() * self.speed) % DAY;
+            self.last = Some(ctx.clock.since_start);
+        } else {
+            self.last = None;
+        }
+
+        if self.legend_filter.as_ref() != Some(&ctx.filter) {
+            let route_ids = ctx
+                .gtfs
+                .trips
+                .values()
+                .filter(|trip| ctx.filter.keeps_trip(&ctx.gtfs, trip))
+                .map(|trip| trip.route_id.as_str())
+                .collect::<HashSet<&str>>();
+
+            self.legend =
+                legend_entries(route_ids.iter().filter_map(|id| ctx.gtfs.routes.get(*id)));
+            self.legend_filter = Some(ctx.filter.clone());
+        }
+
+        let layers = self.layers.as_mut().unwrap();
+        ctx.basemap_opacity = layers.opacity("basemap").unwrap_or(1.);
+        layers.update(ctx);
+    }
+
+    // Space pauses the clock
+    fn event(&mut self, ctx: &mut Context, input: &Input) {
+        ctx.camera_event(input);
+        ctx.filter_event(input);
+
+        if let Input::Char(' ') = input {
+            self.paused = !self.paused;
+        }
+
+        self.layers.as_mut().unwrap().event(input);
+    }
+
+    fn draw(&self, ctx: &Context, draw: &Draw) {
+        let theme = &ctx.theme;
+
+        draw.background().color(theme.background);
+        self.layers().draw(ctx, draw);
+
+        let layers = self.layers();
+        let routes_drawn = ["network", "vehicles"]
+            .iter()
+            .any(|name| layers.opacity(name).is_some());
+        let legend = if routes_drawn { &self.legend[..] } else { &[] };
+        let time = layers.opacity("vehicles").map(|_| ctx.time);
+
+        ctx.draw_overlays(draw, legend, time);
+        ctx.draw_filter(draw);
+    }
+
+    fn is_animating(&self, ctx: &Context) -> bool {
+        self.is_playing(ctx)
+    }
+}
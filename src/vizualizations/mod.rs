@@ -1,55 +1,465 @@
 pub mod audio;
+pub mod centrality;
+pub mod frequency;
+pub mod isochrone;
+pub mod journey;
+pub mod layers;
+pub mod map;
+pub mod overlays;
+pub mod registry;
+pub mod shell;
 pub mod stops;
+pub mod tooltip;
 pub mod trips;
 
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::Timelike;
+use gtfs_structures::Availability;
 use gtfs_structures::Gtfs;
-use nannou::app::EventFn;
-use nannou::app::ModelFn;
-use nannou::app::UpdateFn;
-use nannou::app::ViewFn;
-use nannou::Event;
+use gtfs_structures::Stop;
+use nannou::App;
+use nannou::Draw;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::args::Args;
+use crate::basemap::layer::BasemapLayer;
+use crate::basemap::Basemap;
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::recorder::Recorder;
+use crate::routing::transfers::load_transfers;
+use crate::routing::transfers::FeedTransfer;
+use crate::theme::Theme;
+use crate::utils::camera::Camera;
+use crate::utils::extent::Fit;
+#[cfg(test)]
+use crate::utils::feed::fixture_gtfs;
+use crate::utils::feed::load_gtfs;
+use crate::utils::input::Input;
+use crate::utils::math::coordinate_to_xy;
+use crate::utils::math::nearest;
+use crate::utils::math::polyline_distance;
 use crate::utils::meta::Meta;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::service::format_time;
+use crate::utils::service::parse_date;
+use crate::utils::service::parse_time;
+use crate::utils::stop_index::StopIndex;
 
+use self::overlays::title_height;
+use self::overlays::title_lines;
+use self::overlays::LegendEntry;
+use self::overlays::OverlayContent;
+use self::overlays::Overlays;
+
+// How close the cursor has to be to a stop to hover it, in pixels
+const HOVER_RADIUS: f64 = 8.;
+const NEXT_DEPARTURES: usize = 3;
+
+// Frame count and time since the window opened, as of the current update
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Clock {
+    pub frame: u64,
+    pub since_start: Duration,
+}
+
+// State shared by every visualization and kept when switching between them: the feed,
+// its projection, configuration, clock and input
 #[derive(Default)]
-pub struct Model<Context> {
+pub struct Context {
     meta: Meta,
+    // Extent of the whole feed, `meta` is replaced by a trip's own in `trips`
+    extent: Meta,
+    date: NaiveDate,
     gtfs: Gtfs,
-    context: Context,
+    args: Args,
+    recorder: Recorder,
+    theme: Theme,
+    camera: Camera,
+    index: StopIndex,
+    // Rows of `transfers.txt`, merged into the generated walking transfers
+    transfers: Vec<FeedTransfer>,
+    basemap: Option<BasemapLayer>,
+    // Set by the map's basemap layer, back to opaque when switching visualizations
+    basemap_opacity: f32,
+    filter: RouteFilter,
+    // Why the last filter change was undone, shown next to the filter
+    filter_message: Option<String>,
+    overlays: Overlays,
+    // Feed publisher and validity for the title overlay
+    title: Vec<String>,
+    clock: Clock,
+    // Time of day shown, in seconds past midnight of `date`
+    time: f64,
+    mouse: (f64, f64),
+    // Window captures requested by the visualization, saved by the shell
+    captures: Vec<String>,
+    // Tooltip of the last hovered stop, with the stop id, date and time it was made for,
+    // filled while drawing
+    hovered_details: RefCell<Option<(StopDetailsKey, Vec<String>)>>,
 }
 
-impl<Context> Model<Context>
-where
-    Context: Default,
-{
-    fn from_url(url: &str) -> Self {
-        eprintln!("Parsing GTFS at {url}...");
-        let gtfs = Gtfs::new(url).expect("Failed to load GTFS");
-        let meta = Meta::from_gtfs(&gtfs);
+type StopDetailsKey = (String, NaiveDate, u32);
+
+impl Context {
+    pub fn load(url: &str, args: Args) -> Self {
+        let gtfs = load_gtfs(url, &args);
 
         Self {
-            gtfs,
+            transfers: load_transfers(url),
+            ..Self::from_gtfs(gtfs, args)
+        }
+    }
+
+    pub fn from_gtfs(gtfs: Gtfs, args: Args) -> Self {
+        let meta = Meta::from_gtfs(&gtfs, &Fit::from_args(&args));
+        let index = StopIndex::from_gtfs(&gtfs);
+
+        let basemap = args.get("basemap").and_then(|path| {
+            Basemap::load(path)
+                .map(BasemapLayer::new)
+                .map_err(|e| eprintln!("Couldn't load basemap: {e}"))
+                .ok()
+        });
+
+        let date = args.get("date").map_or(Local::now().date_naive(), |date| {
+            parse_date(date).unwrap_or_else(|| panic!("Invalid value for --date: {date}"))
+        });
+
+        let time = args.get("time").map_or_else(
+            || Local::now().num_seconds_from_midnight(),
+            |time| parse_time(time).unwrap_or_else(|| panic!("Invalid value for --time: {time}")),
+        );
+
+        let mut filter = RouteFilter::from_args(&args);
+        let mut filter_message = None;
+        if !matches_any_trip(&gtfs, &filter) {
+            let message = format!("No trips match {}, showing every trip", filter.describe());
+            eprintln!("{message}");
+            filter = RouteFilter::default();
+            filter_message = Some(message);
+        }
+
+        Self {
+            extent: meta.clone(),
             meta,
+            date,
+            index,
+            basemap,
+            basemap_opacity: 1.,
+            time: time as f64,
+            filter,
+            filter_message,
+            overlays: Overlays::from_args(&args),
+            title: title_lines(&gtfs),
+            theme: Theme::from_args(&args),
+            gtfs,
+            args,
             ..Default::default()
         }
     }
 
-    fn context<F>(mut self, context_fn: F) -> Self
-    where
-        F: FnOnce(&Self) -> Context,
-    {
-        self.context = context_fn(&self);
-        self
+    // Shared filter hotkeys: `f` cycles the route types with trips, `a` wheelchair accessible
+    // trips only and `z` night trips only, returns whether the filter changed. A toggle
+    // leaving no trips is undone
+    fn filter_event(&mut self, input: &Input) -> bool {
+        let previous = self.filter.clone();
+
+        match input {
+            Input::Char('f') => {
+                let gtfs = &self.gtfs;
+                self.filter
+                    .next_route_type(|filter| matches_any_trip(gtfs, filter));
+
+                if self.filter == previous {
+                    return false;
+                }
+            }
+            Input::Char('a') => self.filter.wheelchair = !self.filter.wheelchair,
+            Input::Char('z') => self.filter.night = !self.filter.night,
+            _ => return false,
+        }
+
+        if !matches_any_trip(&self.gtfs, &self.filter) {
+            let message = format!(
+                "No trips match {}, keeping the filter",
+                self.filter.describe()
+            );
+            eprintln!("{message}");
+            self.filter = previous;
+            self.filter_message = Some(message);
+            return false;
+        }
+
+        eprintln!("Filter: {}", self.filter.describe());
+        self.filter_message = None;
+        true
+    }
+
+    // Stops served by a trip passing the filter, all of them when there's no filter
+    fn kept_stops(&self) -> HashMap<String, Arc<Stop>> {
+        if !self.filter.is_active() {
+            return self.gtfs.stops.clone();
+        }
+
+        self.gtfs
+            .trips
+            .values()
+            .filter(|trip| self.filter.keeps_trip(&self.gtfs, trip))
+            .flat_map(|trip| trip.stop_times.iter())
+            .map(|st| (st.stop.id.clone(), Arc::clone(&st.stop)))
+            .collect()
+    }
+
+    // Saves the window as a PNG once the frame is drawn
+    fn save_frame(&mut self, path: String) {
+        self.captures.push(path);
+    }
+
+    // Active filter in the bottom left corner, with the message of a change undone above it
+    fn draw_filter(&self, draw: &Draw) {
+        let bottom = -(CANVAS_HEIGHT as f32) / 2. + 12.;
+        let lines = [
+            self.filter.is_active().then(|| self.filter.describe()),
+            self.filter_message.clone(),
+        ];
+
+        for (idx, line) in lines.iter().flatten().enumerate() {
+            draw.text(line)
+                .x_y(0., bottom + 14. * idx as f32)
+                .w(CANVAS_WIDTH as f32 - 16.)
+                .left_justify()
+                .color(self.theme.foreground)
+                .font(self.theme.typeface())
+                .font_size(10);
+        }
+    }
+
+    // Title, scale bar and north arrow, plus the `legend` of the routes drawn and the `time`
+    // of views moving through the day
+    fn draw_overlays(&self, draw: &Draw, legend: &[LegendEntry], time: Option<f64>) {
+        let content = OverlayContent {
+            title: &self.title,
+            meta: &self.meta,
+            camera: &self.camera,
+            legend,
+            time,
+        };
+
+        self.overlays.draw(&mut draw.clone(), &self.theme, &content);
+    }
+
+    // Room taken by the title overlay at the top of the window
+    fn title_height(&self) -> f32 {
+        if self.overlays.title && !self.overlays.hidden {
+            title_height(&self.title)
+        } else {
+            0.
+        }
+    }
+
+    // Shared pan and zoom, returns whether the view changed
+    fn camera_event(&mut self, input: &Input) -> bool {
+        self.camera.event(input, self.mouse)
+    }
+
+    // Re-renders the basemap underlay if the view moved away from the cached one
+    fn update_basemap(&mut self, app: &App) {
+        if let Some(basemap) = &mut self.basemap {
+            basemap.update(
+                app,
+                &self.meta,
+                &self.camera,
+                &self.theme,
+                self.basemap_opacity,
+            );
+        }
+    }
+
+    fn draw_basemap(&self, draw: &Draw) {
+        if let Some(basemap) = &self.basemap {
+            basemap.draw(draw, &self.camera);
+        }
+    }
+
+    fn project(&self, stop: &Stop) -> (f64, f64) {
+        self.project_coordinate(stop.longitude.unwrap(), stop.latitude.unwrap())
+    }
+
+    fn project_coordinate(&self, lng: f64, lat: f64) -> (f64, f64) {
+        coordinate_to_xy(lng, lat, &self.meta, &self.camera)
+    }
+
+    fn hovered_stop<'a>(
+        &self,
+        stops: impl Iterator<Item = &'a Arc<Stop>>,
+    ) -> Option<&'a Arc<Stop>> {
+        let projected = stops.map(|stop| (stop, self.project(stop)));
+        nearest(projected, self.mouse.0, self.mouse.1, HOVER_RADIUS)
+    }
+
+    fn is_hovering_polyline(&self, points: &[(f32, f32)]) -> bool {
+        let points = points
+            .iter()
+            .map(|(x, y)| (*x as f64, *y as f64))
+            .collect::<Vec<_>>();
+
+        polyline_distance(&points, self.mouse.0, self.mouse.1) <= HOVER_RADIUS / 2.
+    }
+
+    // Tooltip lines of a stop: name, id, zone, accessibility, routes and the next departures
+    // on the selected date and time, kept while the same stop stays hovered
+    fn stop_details(&self, stop: &Stop) -> Vec<String> {
+        let key = (stop.id.clone(), self.date, self.time as u32);
+        let mut cached = self.hovered_details.borrow_mut();
+
+        match cached.as_ref() {
+            Some((k, lines)) if *k == key => lines.clone(),
+            _ => {
+                let lines = self.build_stop_details(stop);
+                *cached = Some((key, lines.clone()));
+                lines
+            }
+        }
+    }
+
+    fn build_stop_details(&self, stop: &Stop) -> Vec<String> {
+        let mut id = format!("#{}", stop.id);
+        if let Some(zone) = &stop.zone_id {
+            id.push_str(&format!(" · zone {zone}"));
+        }
+
+        let wheelchair = match stop.wheelchair_boarding {
+            Availability::Available => "yes",
+            Availability::NotAvailable => "no",
+            _ => "unknown",
+        };
+
+        let mut routes = self
+            .index
+            .routes(stop)
+            .filter_map(|id| self.gtfs.routes.get(id))
+            .map(|route| route.short_name.as_str())
+            .collect::<Vec<&str>>();
+        routes.sort_unstable();
+        routes.dedup();
+
+        let mut lines = vec![
+            stop.name.clone(),
+            id,
+            format!("Wheelchair boarding: {wheelchair}"),
+        ];
+
+        if !routes.is_empty() {
+            lines.push(format!("Routes: {}", routes.join(", ")));
+        }
+
+        let departures = self
+            .index
+            .departures(&self.gtfs, stop, self.date, self.time as u32);
+
+        for (time, trip) in departures.into_iter().take(NEXT_DEPARTURES) {
+            let route = self
+                .gtfs
+                .routes
+                .get(&trip.route_id)
+                .map_or("", |route| route.short_name.as_str());
+            let headsign = trip
+                .trip_headsign
+                .as_deref()
+                .or_else(|| trip.stop_times.last().map(|st| st.stop.name.as_str()))
+                .unwrap_or_default();
+
+            lines.push(format!("{} {route} ► {headsign}", format_time(time)));
+        }
+
+        lines
     }
 }
 
+fn matches_any_trip(gtfs: &Gtfs, filter: &RouteFilter) -> bool {
+    gtfs.trips
+        .values()
+        .any(|trip| filter.keeps_trip(gtfs, trip))
+}
+
 pub trait Viz {
-    fn model(&self) -> ModelFn<Model<Box<Self>>>;
-    fn update(&self) -> UpdateFn<Model<Box<Self>>>;
-    fn event(&self) -> EventFn<Model<Box<Self>>, Event>;
-    fn view(&self) -> ViewFn<Model<Box<Self>>>;
+    // Called when the visualization is started or switched to
+    fn enter(&mut self, ctx: &mut Context);
+    fn update(&mut self, ctx: &mut Context);
+    fn event(&mut self, ctx: &mut Context, input: &Input);
+    fn draw(&self, ctx: &Context, draw: &Draw);
+    // Whether it has to redraw every frame, otherwise it only redraws on input
+    fn is_animating(&self, ctx: &Context) -> bool;
+}
+
+#[cfg(test)]
+impl Context {
+    // Context of the test feed with the command line `args`
+    pub fn fixture(args: &[&str]) -> Self {
+        let args = Args::parse(args.iter().map(|arg| arg.to_string()));
+
+        Self::from_gtfs(fixture_gtfs(), args)
+    }
 }
 
-pub trait Vizualization {
-    fn run(&self);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_hotkeys_cycle_and_toggle() {
+        let mut ctx = Context::fixture(&["--date=20240501"]);
+
+        assert!(ctx.filter_event(&Input::Char('f')));
+        assert_eq!(ctx.filter.describe(), "tram");
+        assert!(ctx.filter_event(&Input::Char('a')));
+        assert_eq!(ctx.filter.describe(), "tram · wheelchair accessible");
+        assert_eq!(ctx.kept_stops().len(), 3);
+
+        assert!(ctx.filter_event(&Input::Char('a')));
+        assert!(!ctx.filter_event(&Input::Char('x')));
+        assert_eq!(ctx.filter.describe(), "tram");
+    }
+
+    #[test]
+    fn route_types_without_trips_are_skipped() {
+        let mut ctx = Context::fixture(&["--date=20240501"]);
+
+        // No metro, rail, trolleybus or ferry in the feed
+        assert!(ctx.filter_event(&Input::Char('f')));
+        assert_eq!(ctx.filter.describe(), "tram");
+        assert!(ctx.filter_event(&Input::Char('f')));
+        assert_eq!(ctx.filter.describe(), "bus");
+        assert!(ctx.filter_event(&Input::Char('f')));
+        assert!(!ctx.filter.is_active());
+        assert!(ctx.filter_event(&Input::Char('f')));
+        assert_eq!(ctx.filter.describe(), "tram");
+    }
+
+    #[test]
+    fn toggles_leaving_no_trips_are_undone() {
+        let mut ctx = Context::fixture(&["--date=20240501"]);
+        ctx.filter_event(&Input::Char('f'));
+
+        // No tram at night
+        assert!(!ctx.filter_event(&Input::Char('z')));
+        assert!(!ctx.filter.night);
+        assert!(ctx.filter_message.is_some());
+
+        assert!(ctx.filter_event(&Input::Char('a')));
+        assert!(ctx.filter_message.is_none());
+    }
+
+    #[test]
+    fn command_line_filter_matching_nothing_is_dropped() {
+        let ctx = Context::fixture(&["--route-type=ferry"]);
+
+        assert!(!ctx.filter.is_active());
+        assert!(ctx.filter_message.is_some());
+    }
 }
@@ -0,0 +1,408 @@
+use gtfs_structures::Gtfs;
+use gtfs_structures::Route;
+use nannou::color::Rgb8;
+use nannou::prelude::pt2;
+use nannou::text::Font;
+use nannou::Draw;
+use std::collections::BTreeMap;
+
+use crate::args::Args;
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::theme::Theme;
+use crate::utils::camera::Camera;
+use crate::utils::math::meters_per_px;
+use crate::utils::meta::Meta;
+use crate::utils::raster::Canvas;
+use crate::utils::route_types::route_type_code;
+use crate::utils::route_types::route_type_name;
+use crate::utils::service::format_time;
+use crate::vizualizations::trips::Justify;
+use crate::vizualizations::trips::Label;
+
+const FONT_SIZE: u32 = 10;
+const MARGIN: f32 = 8.;
+const LEGEND_ENTRIES: usize = 8;
+const LEGEND_WIDTH: f32 = 200.;
+// Longest the scale bar gets, in pixels
+const SCALE_WIDTH: f64 = 100.;
+
+pub const OVERLAYS: [&str; 5] = ["legend", "scale", "north", "title", "time"];
+
+// Which overlay widgets are drawn, `o` hides or shows all of them
+#[derive(Debug, Clone)]
+pub struct Overlays {
+    pub legend: bool,
+    pub scale: bool,
+    pub north: bool,
+    pub title: bool,
+    pub time: bool,
+    pub hidden: bool,
+}
+
+impl Overlays {
+    // `--overlays=scale,north` picks the widgets, all of them by default, `--overlays=none`
+    // starts with them hidden
+    pub fn from_args(args: &Args) -> Self {
+        let names = match args.get("overlays") {
+            Some("none") => vec![],
+            Some(names) => names.split(',').map(str::trim).collect(),
+            None => OVERLAYS.to_vec(),
+        };
+
+        for name in names.iter() {
+            if !OVERLAYS.contains(name) {
+                panic!("Invalid value for --overlays: {name}");
+            }
+        }
+
+        Self {
+            legend: names.contains(&"legend"),
+            scale: names.contains(&"scale"),
+            north: names.contains(&"north"),
+            title: names.contains(&"title"),
+            time: names.contains(&"time"),
+            hidden: false,
+        }
+    }
+}
+
+// What a frame shows in the widgets: the feed's title, the view the scale bar measures, the
+// routes drawn and the time of views moving through the day
+pub struct OverlayContent<'a> {
+    pub title: &'a [String],
+    pub meta: &'a Meta,
+    pub camera: &'a Camera,
+    pub legend: &'a [LegendEntry],
+    pub time: Option<f64>,
+}
+
+impl Overlays {
+    pub fn draw(&self, surface: &mut impl Surface, theme: &Theme, content: &OverlayContent) {
+        if self.hidden {
+            return;
+        }
+
+        if self.title {
+            draw_title(surface, theme, content.title);
+        }
+        if self.scale {
+            draw_scale_bar(surface, theme, content.meta, content.camera);
+        }
+        if self.north {
+            draw_north_arrow(surface, theme);
+        }
+        if self.legend && !content.legend.is_empty() {
+            draw_legend(surface, theme, content.legend);
+        }
+        if let (true, Some(time)) = (self.time, content.time) {
+            draw_timestamp(surface, theme, &format_time(time as u32));
+        }
+    }
+}
+
+// What the widgets are drawn on, the window or the raster canvas of the exports
+pub trait Surface {
+    fn segment(&mut self, start: (f32, f32), end: (f32, f32), weight: f32, color: Rgb8);
+    fn fill(&mut self, points: &[(f32, f32)], color: Rgb8);
+    fn label(&mut self, label: &Label, font: &Font);
+}
+
+impl Surface for Draw {
+    fn segment(&mut self, start: (f32, f32), end: (f32, f32), weight: f32, color: Rgb8) {
+        self.line()
+            .start(pt2(start.0, start.1))
+            .end(pt2(end.0, end.1))
+            .weight(weight)
+            .color(color);
+    }
+
+    fn fill(&mut self, points: &[(f32, f32)], color: Rgb8) {
+        self.polygon()
+            .color(color)
+            .points(points.iter().map(|(x, y)| pt2(*x, *y)));
+    }
+
+    fn label(&mut self, label: &Label, font: &Font) {
+        let text = self
+            .text(&label.text)
+            .x_y(label.x, label.y)
+            .w(label.width)
+            .color(label.color)
+            .font(font.clone())
+            .font_size(label.font_size);
+
+        match label.justify {
+            Justify::Left => text.left_justify(),
+            Justify::Center => text.center_justify(),
+            Justify::Right => text.right_justify(),
+        };
+    }
+}
+
+impl Surface for Canvas {
+    fn segment(&mut self, start: (f32, f32), end: (f32, f32), weight: f32, color: Rgb8) {
+        self.polyline(&[start, end], weight, color);
+    }
+
+    fn fill(&mut self, points: &[(f32, f32)], color: Rgb8) {
+        self.polygon(points, color);
+    }
+
+    fn label(&mut self, label: &Label, font: &Font) {
+        self.text(label, font);
+    }
+}
+
+pub struct LegendEntry {
+    pub label: String,
+    pub color: Rgb8,
+}
+
+fn route_color(route: &Route) -> Rgb8 {
+    Rgb8::new(
+        route.route_color.r,
+        route.route_color.g,
+        route.route_color.b,
+    )
+}
+
+// One entry per route type and color among `routes`, the colors of a route type with several
+// of them are told apart by the first few route names
+pub fn legend_entries<'a>(routes: impl Iterator<Item = &'a Route>) -> Vec<LegendEntry> {
+    let mut groups: BTreeMap<i16, BTreeMap<(u8, u8, u8), Vec<&str>>> = BTreeMap::new();
+
+    for route in routes {
+        let color = route_color(route);
+        groups
+            .entry(route_type_code(route.route_type))
+            .or_default()
+            .entry((color.red, color.green, color.blue))
+            .or_default()
+            .push(route.short_name.as_str());
+    }
+
+    let mut entries = vec![];
+    for (code, colors) in groups {
+        let name = route_type_name(code);
+        let several = colors.len() > 1;
+
+        for ((r, g, b), mut names) in colors {
+            let label = if several {
+                names.sort_unstable();
+                names.dedup();
+
+                let mut label = format!("{name} {}", names[..names.len().min(3)].join(", "));
+                if names.len() > 3 {
+                    label.push_str("…");
+                }
+                label
+            } else {
+                name.clone()
+            };
+
+            entries.push(LegendEntry {
+                label,
+                color: Rgb8::new(r, g, b),
+            });
+        }
+    }
+
+    entries
+}
+
+// Color swatches in the bottom left corner, above the filter
+pub fn draw_legend(surface: &mut impl Surface, theme: &Theme, entries: &[LegendEntry]) {
+    let line_height = FONT_SIZE as f32 * 1.6;
+    let left = -(CANVAS_WIDTH as f32) / 2. + MARGIN;
+    let bottom = -(CANVAS_HEIGHT as f32) / 2. + 28.;
+    let font = theme.typeface();
+
+    let mut lines = entries
+        .iter()
+        .take(LEGEND_ENTRIES)
+        .map(|entry| (entry.label.clone(), Some(entry.color)))
+        .collect::<Vec<_>>();
+
+    if entries.len() > LEGEND_ENTRIES {
+        lines.push((format!("+{} more", entries.len() - LEGEND_ENTRIES), None));
+    }
+
+    for (idx, (label, color)) in lines.into_iter().rev().enumerate() {
+        let y = bottom + line_height * idx as f32;
+
+        if let Some(color) = color {
+            surface.segment((left, y), (left + 16., y), theme.line_weight * 2., color);
+        }
+
+        let label = Label {
+            text: label,
+            x: left + 24. + LEGEND_WIDTH / 2.,
+            y,
+            width: LEGEND_WIDTH,
+            font_size: FONT_SIZE,
+            justify: Justify::Left,
+            color: theme.foreground,
+        };
+        surface.label(&label, &font);
+    }
+}
+
+// Largest 1, 2 or 5 times a power of ten not above `max`
+fn round_length(max: f64) -> f64 {
+    let magnitude = 10f64.powf(max.log10().floor());
+
+    [5., 2., 1.]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|length| *length <= max)
+        .unwrap_or(magnitude)
+}
+
+// Metric scale bar in the bottom right corner, measured across the middle of the view
+pub fn draw_scale_bar(surface: &mut impl Surface, theme: &Theme, meta: &Meta, camera: &Camera) {
+    let meters_per_px = meters_per_px(meta, camera);
+
+    if !meters_per_px.is_normal() {
+        return;
+    }
+
+    let meters = round_length(meters_per_px * SCALE_WIDTH);
+    let width = (meters / meters_per_px) as f32;
+    let text = if meters >= 1000. {
+        format!("{} km", meters / 1000.)
+    } else {
+        format!("{meters} m")
+    };
+
+    let right = CANVAS_WIDTH as f32 / 2. - MARGIN;
+    let y = -(CANVAS_HEIGHT as f32) / 2. + 12.;
+
+    surface.segment((right - width, y), (right, y), 1.5, theme.foreground);
+
+    for x in [right - width, right] {
+        surface.segment((x, y - 3.), (x, y + 3.), 1.5, theme.foreground);
+    }
+
+    let label = Label {
+        text,
+        x: right - width / 2.,
+        y: y + 10.,
+        width: SCALE_WIDTH as f32,
+        font_size: FONT_SIZE,
+        justify: Justify::Center,
+        color: theme.foreground,
+    };
+    surface.label(&label, &theme.typeface());
+}
+
+// The projection keeps north up, so the arrow always points straight up
+pub fn draw_north_arrow(surface: &mut impl Surface, theme: &Theme) {
+    let x = CANVAS_WIDTH as f32 / 2. - MARGIN - 8.;
+    let y = CANVAS_HEIGHT as f32 / 2. - 52.;
+
+    surface.fill(
+        &[
+            (x, y + 10.),
+            (x + 6., y - 8.),
+            (x, y - 4.),
+            (x - 6., y - 8.),
+        ],
+        theme.foreground,
+    );
+
+    let label = Label {
+        text: String::from("N"),
+        x,
+        y: y + 20.,
+        width: MARGIN * 4.,
+        font_size: FONT_SIZE,
+        justify: Justify::Center,
+        color: theme.foreground,
+    };
+    surface.label(&label, &theme.typeface());
+}
+
+// Feed publisher and validity from feed_info.txt, the agencies when there's none
+pub fn title_lines(gtfs: &Gtfs) -> Vec<String> {
+    let mut lines = vec![];
+
+    match gtfs.feed_info.first() {
+        Some(info) => {
+            lines.push(info.name.clone());
+
+            let validity = match (info.start_date, info.end_date) {
+                (Some(start), Some(end)) => Some(format!("Valid {start} – {end}")),
+                (Some(start), None) => Some(format!("Valid from {start}")),
+                (None, Some(end)) => Some(format!("Valid until {end}")),
+                (None, None) => None,
+            };
+            lines.extend(validity);
+
+            if let Some(version) = &info.version {
+                lines.push(format!("Version {version}"));
+            }
+        }
+        None => {
+            let mut agencies = gtfs
+                .agencies
+                .iter()
+                .map(|agency| agency.name.as_str())
+                .collect::<Vec<&str>>();
+            agencies.sort_unstable();
+            agencies.dedup();
+
+            lines.push(agencies.join(", "));
+        }
+    }
+
+    lines.retain(|line| !line.is_empty());
+    lines
+}
+
+// Height of the title block, for views placing their own text below it
+pub fn title_height(lines: &[String]) -> f32 {
+    (0..lines.len())
+        .map(|idx| {
+            let size = if idx == 0 { FONT_SIZE + 4 } else { FONT_SIZE };
+            size as f32 * 1.4
+        })
+        .sum()
+}
+
+// Top left corner, the first line larger
+pub fn draw_title(surface: &mut impl Surface, theme: &Theme, lines: &[String]) {
+    let mut y = CANVAS_HEIGHT as f32 / 2. - 16.;
+    let font = theme.typeface();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let size = if idx == 0 { FONT_SIZE + 4 } else { FONT_SIZE };
+
+        let label = Label {
+            text: line.clone(),
+            x: 0.,
+            y,
+            width: CANVAS_WIDTH as f32 - MARGIN * 2.,
+            font_size: size,
+            justify: Justify::Left,
+            color: theme.foreground,
+        };
+        surface.label(&label, &font);
+
+        y -= size as f32 * 1.4;
+    }
+}
+
+// Top right corner, for views moving through the day
+pub fn draw_timestamp(surface: &mut impl Surface, theme: &Theme, text: &str) {
+    let label = Label {
+        text: text.to_string(),
+        x: 0.,
+        y: CANVAS_HEIGHT as f32 / 2. - 16.,
+        width: CANVAS_WIDTH as f32 - MARGIN * 2.,
+        font_size: FONT_SIZE + 4,
+        justify: Justify::Right,
+        color: theme.foreground,
+    };
+    surface.label(&label, &theme.typeface());
+}
@@ -0,0 +1,63 @@
+use super::audio::AudioViz;
+use super::centrality::CentralityViz;
+use super::frequency::FrequencyViz;
+use super::isochrone::IsochroneViz;
+use super::journey::JourneyViz;
+use super::map::MapViz;
+use super::stops::StopsViz;
+use super::trips::TripsViz;
+use super::Viz;
+
+pub struct Entry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub new: fn() -> Box<dyn Viz>,
+}
+
+// Every visualization the shell can switch to, in `Tab` order, F1 for the first one and so on
+pub const REGISTRY: [Entry; 8] = [
+    Entry {
+        name: "trips",
+        description: "Poster of a random trip",
+        new: || Box::new(TripsViz::new()),
+    },
+    Entry {
+        name: "stops",
+        description: "Every stop, spreading out from the center",
+        new: || Box::new(StopsViz::default()),
+    },
+    Entry {
+        name: "audio",
+        description: "Stop names played as chords",
+        new: || Box::new(AudioViz::new()),
+    },
+    Entry {
+        name: "map",
+        description: "Network, stops and moving vehicles as toggleable layers",
+        new: || Box::new(MapViz::new()),
+    },
+    Entry {
+        name: "frequency",
+        description: "Departures and headways by stop and time of day",
+        new: || Box::new(FrequencyViz::new()),
+    },
+    Entry {
+        name: "isochrone",
+        description: "Travel times from a stop, click another one to start there",
+        new: || Box::new(IsochroneViz::new()),
+    },
+    Entry {
+        name: "journey",
+        description: "Fastest journey between two stops, space for another destination",
+        new: || Box::new(JourneyViz::new()),
+    },
+    Entry {
+        name: "centrality",
+        description: "Transfer hubs by betweenness, closeness or degree",
+        new: || Box::new(CentralityViz::new()),
+    },
+];
+
+pub fn find(name: &str) -> Option<usize> {
+    REGISTRY.iter().position(|entry| entry.name == name)
+}
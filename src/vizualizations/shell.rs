@@ -0,0 +1,178 @@
+use nannou::event::Key;
+use nannou::event::Update;
+use nannou::App;
+use nannou::Event;
+use nannou::Frame;
+use nannou::LoopMode;
+
+use super::registry::find;
+use super::registry::REGISTRY;
+use super::Clock;
+use super::Context;
+use super::Viz;
+use crate::args::Args;
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::constants::GTFS_URL;
+use crate::recorder::Recorder;
+use crate::utils::input::Input;
+
+const FN_KEYS: [Key; 12] = [
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+];
+
+// One window for every visualization, the feed is only loaded once and each
+// visualization keeps its state while another one is shown
+struct Shell {
+    ctx: Context,
+    vizualizations: Vec<Option<Box<dyn Viz>>>,
+    current: usize,
+    animating: Option<bool>,
+}
+
+impl Shell {
+    fn viz(&self) -> &dyn Viz {
+        self.vizualizations[self.current].as_deref().unwrap()
+    }
+
+    fn enter(&mut self, app: &App, idx: usize) {
+        let entry = &REGISTRY[idx];
+
+        self.current = idx;
+        self.ctx.recorder = Recorder::from_args(&self.ctx.args, entry.name);
+        self.ctx.basemap_opacity = 1.;
+
+        let viz = self.vizualizations[idx].get_or_insert_with(entry.new);
+        viz.enter(&mut self.ctx);
+
+        app.main_window()
+            .set_title(&format!("bp_viz · {} — {}", entry.name, entry.description));
+    }
+
+    fn switch(&mut self, app: &App, idx: usize) {
+        if idx != self.current {
+            self.ctx.recorder.stop(app);
+            self.enter(app, idx);
+        }
+    }
+
+    // Redraws continuously while animating or recording, otherwise only on input
+    fn pace(&mut self, app: &App) {
+        let animating = self.viz().is_animating(&self.ctx) || self.ctx.recorder.is_active();
+
+        if self.animating != Some(animating) {
+            app.set_loop_mode(if animating {
+                LoopMode::RefreshSync
+            } else {
+                LoopMode::Wait
+            });
+            self.animating = Some(animating);
+        }
+    }
+}
+
+fn model(app: &App) -> Shell {
+    let args = Args::from_env();
+    let current = args
+        .positional
+        .first()
+        .and_then(|name| find(name))
+        .unwrap_or(0);
+
+    let mut shell = Shell {
+        ctx: Context::load(GTFS_URL, args),
+        vizualizations: REGISTRY.iter().map(|_| None).collect(),
+        current,
+        animating: None,
+    };
+
+    shell.enter(app, current);
+    shell.pace(app);
+
+    shell
+}
+
+fn update(app: &App, shell: &mut Shell, update: Update) {
+    shell.ctx.clock = Clock {
+        frame: app.elapsed_frames(),
+        since_start: update.since_start,
+    };
+
+    let viz = shell.vizualizations[shell.current].as_mut().unwrap();
+    viz.update(&mut shell.ctx);
+
+    shell.ctx.update_basemap(app);
+    shell.ctx.recorder.capture(app);
+    shell.pace(app);
+}
+
+// `Tab` switches to the next visualization, F1, F2... to a given one, `v` toggles
+// recording and `o` the overlays, everything else goes to the current visualization
+fn event(app: &App, shell: &mut Shell, event: Event) {
+    let input = match Input::from_event(&event) {
+        Some(input) => input,
+        None => return,
+    };
+
+    if let Input::MouseMoved(x, y) = input {
+        shell.ctx.mouse = (x, y);
+    }
+
+    match input {
+        Input::Key(Key::Tab) => shell.switch(app, (shell.current + 1) % REGISTRY.len()),
+        Input::Key(key) if FN_KEYS[..REGISTRY.len()].contains(&key) => {
+            let idx = FN_KEYS.iter().position(|k| *k == key).unwrap();
+            shell.switch(app, idx);
+        }
+        Input::Char('v') => shell.ctx.recorder.toggle(app),
+        Input::Char('o') => shell.ctx.overlays.hidden = !shell.ctx.overlays.hidden,
+        _ => {
+            let viz = shell.vizualizations[shell.current].as_mut().unwrap();
+            viz.event(&mut shell.ctx, &input);
+        }
+    }
+
+    for path in shell.ctx.captures.drain(..) {
+        app.main_window().capture_frame(path);
+    }
+
+    shell.pace(app);
+}
+
+fn view(app: &App, shell: &Shell, frame: Frame) {
+    let draw = app.draw();
+    shell.viz().draw(&shell.ctx, &draw);
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn exit(_: &App, mut shell: Shell) {
+    shell.ctx.recorder.finish();
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .simple_window(view)
+        .size(CANVAS_WIDTH, CANVAS_HEIGHT)
+        .run()
+}
+
+// `bp_viz list`
+pub fn list() {
+    for (idx, entry) in REGISTRY.iter().enumerate() {
+        println!("F{:<2} {:<9} {}", idx + 1, entry.name, entry.description);
+    }
+}
@@ -1,21 +1,31 @@
-use gtfs_structures::Gtfs;
 use gtfs_structures::Stop;
-use nannou::color;
-use nannou::LoopMode;
+use nannou::prelude::pt2;
+use nannou::Draw;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
 
-use super::Model;
+use super::layers::faded;
+use super::tooltip::draw_tooltip;
+use super::Context;
 use super::Viz;
-use super::Vizualization;
 use crate::constants::CANVAS_HEIGHT;
 use crate::constants::CANVAS_WIDTH;
-use crate::constants::GTFS_URL;
+use crate::utils::camera::Camera;
+use crate::utils::input::Input;
+use crate::utils::labels::place;
+use crate::utils::labels::LabelRequest;
+use crate::utils::labels::Placement;
+use crate::utils::labels::Rect;
+use crate::utils::labels::Strategy;
 use crate::utils::math::coordinate_to_xy;
 use crate::utils::math::distance;
+use crate::utils::raster::text_width;
+use crate::utils::route_filter::RouteFilter;
 
 const START: &'static str = "088453";
+const NAMED_STOPS: usize = 60;
+const LABEL_FONT_SIZE: u32 = 8;
 
 fn find_closest<'a>(
     start: &Stop,
@@ -45,97 +55,239 @@ fn find_closest<'a>(
     (winner_id, Arc::clone(winner))
 }
 
+// `START` if it survived clipping and filtering, otherwise the stop closest to the middle
+pub fn start_id(stops: &HashMap<String, Arc<Stop>>) -> &str {
+    if stops.contains_key(START) {
+        return START;
+    }
+
+    let coords = stops
+        .values()
+        .filter_map(|s| Some((s.longitude?, s.latitude?)))
+        .collect::<Vec<_>>();
+    let n = coords.len().max(1) as f64;
+    let (lng, lat) = coords
+        .iter()
+        .fold((0., 0.), |(x, y), (lng, lat)| (x + lng / n, y + lat / n));
+
+    stops
+        .values()
+        .filter_map(|s| Some((s, distance(lng, lat, s.longitude?, s.latitude?))))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(s, _)| s.id.as_str())
+        .expect("No stops in the feed")
+}
+
+// Every stop in the order `StopsViz` draws them, spreading out from `start_id`
+pub fn visit_order(stops: &HashMap<String, Arc<Stop>>, start_id: &str) -> Vec<Arc<Stop>> {
+    let start = stops.get(start_id).expect("Unknown start stop");
+
+    let mut visited = HashSet::new();
+    visited.insert(String::from(start_id));
+
+    let mut order = vec![];
+    while visited.len() < stops.len() {
+        let (id, next) = find_closest(start, stops, &mut visited);
+
+        // Nothing left within reach of the start
+        if !visited.insert(id.clone()) {
+            break;
+        }
+
+        order.push(next);
+    }
+
+    order
+}
+
+// Names of the busiest stations among `stops`, placed unzoomed so the camera only moves
+// them around afterwards
+pub fn station_labels<'a>(
+    ctx: &Context,
+    stops: impl Iterator<Item = &'a Arc<Stop>>,
+) -> Vec<(String, Placement)> {
+    let stop_index = &ctx.index;
+    let font = ctx.theme.typeface();
+
+    let mut stations = HashMap::new();
+    for stop in stops {
+        let station = stop.parent_station.as_ref().unwrap_or(&stop.id);
+        stations.entry(station).or_insert(stop);
+    }
+
+    let mut stations = stations.into_values().collect::<Vec<&Arc<Stop>>>();
+    stations.sort_by_key(|stop| std::cmp::Reverse(stop_index.count(stop)));
+    stations.truncate(NAMED_STOPS);
+
+    let requests = stations
+        .iter()
+        .map(|stop| {
+            let (x, y) = coordinate_to_xy(
+                stop.longitude.unwrap(),
+                stop.latitude.unwrap(),
+                &ctx.meta,
+                &Camera::default(),
+            );
+
+            LabelRequest {
+                x: x as f32,
+                y: y as f32,
+                width: text_width(&font, &stop.name, LABEL_FONT_SIZE as f32),
+                height: LABEL_FONT_SIZE as f32 * 1.25,
+                offset: ctx.theme.dot_radius + 2.,
+                priority: stop_index.count(stop) as f32,
+            }
+        })
+        .collect::<Vec<LabelRequest>>();
+
+    let bounds = Rect::centered(0., 0., CANVAS_WIDTH as f32, CANVAS_HEIGHT as f32);
+    let strategy = Strategy::Annealing {
+        iterations: 50_000,
+        seed: 0,
+    };
+
+    stations
+        .iter()
+        .zip(place(&requests, &[], bounds, strategy))
+        .filter_map(|(stop, placement)| Some((stop.name.clone(), placement?)))
+        .collect()
+}
+
+pub fn draw_station_labels(
+    ctx: &Context,
+    draw: &Draw,
+    labels: &[(String, Placement)],
+    opacity: f32,
+) {
+    let theme = &ctx.theme;
+
+    let to_screen = |(x, y): (f32, f32)| {
+        let (x, y) = ctx.camera.apply(x as f64, y as f64);
+        pt2(x as f32, y as f32)
+    };
+
+    for (name, placement) in labels.iter() {
+        if let Some((start, end)) = placement.leader {
+            draw.line()
+                .start(to_screen(start))
+                .end(to_screen(end))
+                .weight(0.5)
+                .color(faded(theme.muted, opacity));
+        }
+
+        draw.text(name)
+            .xy(to_screen(placement.rect.center()))
+            .w(placement.rect.width())
+            .color(faded(theme.foreground, opacity))
+            .font(theme.typeface())
+            .font_size(LABEL_FONT_SIZE);
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct StopsViz {
+    // Stops passing the route filter
+    stops: HashMap<String, Arc<Stop>>,
+    // Filter the stops were picked with
+    filter: Option<RouteFilter>,
     start: Arc<Stop>,
-    nexts: Vec<Arc<Stop>>,
+    drawn: Vec<Arc<Stop>>,
     visited: HashSet<String>,
+    stop_names: bool,
+    labels: Vec<(String, Placement)>,
 }
 
 impl StopsViz {
-    fn from_gtfs(gtfs: &Gtfs, start_id: &str) -> Self {
-        let start = Arc::clone(&gtfs.stops.get(start_id).unwrap());
+    fn is_done(&self) -> bool {
+        self.visited.len() >= self.stops.len()
+    }
 
-        let mut visited = HashSet::new();
-        visited.insert(String::from(start_id));
+    // Starts drawing again with the stops passing the current filter
+    fn restart(&mut self, ctx: &Context) {
+        let stops = ctx.kept_stops();
+        self.filter = Some(ctx.filter.clone());
 
-        Self {
-            start,
-            nexts: vec![],
-            visited,
+        if stops.is_empty() {
+            eprintln!("No stops match the filter");
+            return;
         }
+
+        let start_id = start_id(&stops).to_string();
+
+        self.start = Arc::clone(&stops[&start_id]);
+        self.stops = stops;
+        self.drawn.clear();
+        self.visited = HashSet::from([start_id]);
+        self.labels.clear();
     }
 }
 
 impl Viz for StopsViz {
-    fn model(&self) -> nannou::app::ModelFn<Model<Box<Self>>> {
-        |app| {
-            let model = Model::from_url(GTFS_URL)
-                .context(|model| Box::new(Self::from_gtfs(&model.gtfs, START)));
-
-            app.set_loop_mode(LoopMode::loop_ntimes(model.gtfs.stops.len()));
+    // Picks up where it left off, unless the filter changed in the meantime
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.meta = ctx.extent.clone();
+        self.stop_names = ctx.args.has("stop-names");
 
-            model
+        if self.filter.as_ref() != Some(&ctx.filter) {
+            self.restart(ctx);
         }
     }
 
-    fn update(&self) -> nannou::app::UpdateFn<Model<Box<Self>>> {
-        |_app, model, _| {
-            let mut nexts = vec![];
-            for _ in 0..20 {
-                let (id, next) = find_closest(
-                    &model.context.start,
-                    &model.gtfs.stops,
-                    &mut model.context.visited,
-                );
-
-                model.context.visited.insert(id.clone());
-                nexts.push(next);
-            }
-            model.context.nexts = nexts;
+    fn update(&mut self, ctx: &mut Context) {
+        if self.is_done() {
+            return;
+        }
+
+        for _ in 0..20 {
+            let (id, next) = find_closest(&self.start, &self.stops, &mut self.visited);
+            let id = id.clone();
+
+            self.visited.insert(id);
+            self.drawn.push(next);
+        }
+
+        if self.is_done() && self.stop_names && self.labels.is_empty() {
+            self.labels = station_labels(ctx, self.stops.values());
         }
     }
 
-    fn event(&self) -> nannou::app::EventFn<Model<Box<Self>>, nannou::Event> {
-        |_, _, _| {}
+    fn event(&mut self, ctx: &mut Context, input: &Input) {
+        ctx.camera_event(input);
+
+        if ctx.filter_event(input) {
+            self.restart(ctx);
+        }
     }
 
-    fn view(&self) -> nannou::app::ViewFn<Model<Box<Self>>> {
-        |app, model, frame| {
-            if frame.nth() == 0 {
-                frame.clear(color::BLACK);
-            }
+    fn draw(&self, ctx: &Context, draw: &Draw) {
+        let theme = &ctx.theme;
 
-            let draw = app.draw();
+        draw.background().color(theme.background);
+        ctx.draw_basemap(draw);
 
-            for stop in model.context.nexts.iter() {
-                let (x, y) =
-                    coordinate_to_xy(stop.longitude.unwrap(), stop.latitude.unwrap(), &model.meta);
+        for stop in self.drawn.iter() {
+            let (x, y) = ctx.project(stop);
 
-                draw.ellipse()
-                    .x_y(x as f32, y as f32)
-                    .radius(1.)
-                    .color(color::WHITE);
-            }
+            draw.ellipse()
+                .x_y(x as f32, y as f32)
+                .radius(theme.dot_radius)
+                .color(theme.foreground);
+        }
 
-            // app.main_window()
-            //     .capture_frame(format!("./export/stops/frame_{}.png", frame.nth()));
+        draw_station_labels(ctx, draw, &self.labels, 1.);
 
-            if model.context.visited.len() >= model.gtfs.stops.len() {
-                app.quit();
-            }
+        ctx.draw_overlays(draw, &[], None);
+        ctx.draw_filter(draw);
 
-            draw.to_frame(app, &frame).unwrap();
+        if let Some(stop) = ctx.hovered_stop(self.drawn.iter()) {
+            let lines = ctx.stop_details(stop);
+            let (x, y) = ctx.mouse;
+            draw_tooltip(draw, theme, x as f32, y as f32, &lines);
         }
     }
-}
 
-impl Vizualization for StopsViz {
-    fn run(&self) {
-        nannou::app(self.model())
-            .update(self.update())
-            .simple_window(self.view())
-            .size(CANVAS_WIDTH, CANVAS_HEIGHT)
-            .run()
+    // Keeps the finished map open to look around or switch to another visualization
+    fn is_animating(&self, _: &Context) -> bool {
+        !self.is_done()
     }
 }
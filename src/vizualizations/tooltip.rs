@@ -0,0 +1,53 @@
+use nannou::prelude::pt2;
+use nannou::Draw;
+
+use crate::constants::CANVAS_HEIGHT;
+use crate::constants::CANVAS_WIDTH;
+use crate::theme::Theme;
+use crate::utils::raster::text_width;
+
+const FONT_SIZE: u32 = 12;
+const PADDING: f32 = 6.;
+
+// Box of text lines next to the cursor, flipped to stay inside the canvas
+pub fn draw_tooltip(draw: &Draw, theme: &Theme, x: f32, y: f32, lines: &[String]) {
+    let font = theme.typeface();
+    let line_height = FONT_SIZE as f32 * 1.4;
+
+    let width = lines
+        .iter()
+        .map(|line| text_width(&font, line, FONT_SIZE as f32))
+        .fold(0., f32::max)
+        + PADDING * 2.;
+    let height = line_height * lines.len() as f32 + PADDING * 2.;
+
+    let left = if x + 12. + width > CANVAS_WIDTH as f32 / 2. {
+        x - 12. - width
+    } else {
+        x + 12.
+    };
+    let top = if y - 12. - height < -(CANVAS_HEIGHT as f32) / 2. {
+        y + 12. + height
+    } else {
+        y - 12.
+    };
+
+    draw.rect()
+        .x_y(left + width / 2., top - height / 2.)
+        .w_h(width, height)
+        .color(theme.background)
+        .stroke(theme.muted)
+        .stroke_weight(1.);
+
+    for (idx, line) in lines.iter().enumerate() {
+        let y = top - PADDING - line_height * (idx as f32 + 0.5);
+
+        draw.text(line)
+            .xy(pt2(left + width / 2., y))
+            .w(width - PADDING * 2.)
+            .color(theme.foreground)
+            .font(theme.typeface())
+            .font_size(FONT_SIZE)
+            .left_justify();
+    }
+}
@@ -1,189 +1,575 @@
+use gtfs_structures::Gtfs;
+use gtfs_structures::Stop;
 use gtfs_structures::Trip;
-use nannou::app::EventFn;
-use nannou::app::ModelFn;
-use nannou::app::UpdateFn;
-use nannou::app::ViewFn;
 use nannou::color::Rgb8;
 use nannou::event::Key;
-use nannou::event::WindowEvent;
+use nannou::event::MouseButton;
 use nannou::prelude::pt2;
-use nannou::Event;
-use nannou::LoopMode;
+use nannou::Draw;
 use rand::Rng;
 use std::sync::Arc;
 
 use crate::constants::CANVAS_HEIGHT;
 use crate::constants::CANVAS_WIDTH;
-use crate::constants::GTFS_URL;
+use crate::constants::EXPORT_DIR;
+use crate::export::gif::trip_gif;
+use crate::theme::Theme;
+use crate::utils::camera::Camera;
+use crate::utils::input::Input;
+use crate::utils::labels::place;
+use crate::utils::labels::LabelRequest;
+use crate::utils::labels::Placement;
+use crate::utils::labels::Rect;
+use crate::utils::labels::Strategy;
 use crate::utils::math::coordinate_to_xy;
+use crate::utils::math::rounded_rect;
 use crate::utils::meta::Meta;
+use crate::utils::raster::text_width;
+use crate::utils::route_filter::RouteFilter;
+use crate::utils::stop_index::StopIndex;
 
-use super::Model;
+use super::overlays::legend_entries;
+use super::tooltip::draw_tooltip;
+use super::Context;
 use super::Viz;
-use super::Vizualization;
+
+const BADGE_PADDING: f32 = 12.;
+const STOP_FONT_SIZE: u32 = 10;
+const TERMINUS_PRIORITY: f32 = 1000.;
+const LABEL_STRATEGY: Strategy = Strategy::Annealing {
+    iterations: 20_000,
+    seed: 0,
+};
+
+pub enum Justify {
+    Left,
+    Center,
+    Right,
+}
+
+pub struct Label {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub font_size: u32,
+    pub justify: Justify,
+    pub color: Rgb8,
+}
+
+// Route number on a filled rounded rectangle, like the BKK signs
+pub struct Badge {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub radius: f32,
+    pub fill: Rgb8,
+    pub label: Label,
+}
+
+impl Badge {
+    pub fn outline(&self) -> Vec<(f32, f32)> {
+        rounded_rect(self.x, self.y, self.width, self.height, self.radius)
+    }
+}
+
+pub struct PosterStop {
+    pub x: f32,
+    pub y: f32,
+    // Served by other routes too
+    pub transfer: bool,
+    pub label: Option<Label>,
+    pub leader: Option<((f32, f32), (f32, f32))>,
+}
+
+// Colors and layout of a trip poster, shared by the window and the GIF export. Stops, shape
+// and stop labels are placed unzoomed, the window's camera only moves them afterwards
+pub struct Poster {
+    pub color: Rgb8,
+    pub bg_color: Rgb8,
+    pub labels: Vec<Label>,
+    pub badge: Badge,
+    pub stops: Vec<PosterStop>,
+    // Projected shape of the trip, or its stops when it has no shape
+    pub shape: Vec<(f32, f32)>,
+}
+
+impl Poster {
+    pub fn new(
+        trip: &Trip,
+        gtfs: &Gtfs,
+        meta: &Meta,
+        stop_index: &StopIndex,
+        theme: &Theme,
+        stop_names: bool,
+    ) -> Self {
+        let camera = &Camera::default();
+        let route = gtfs.get_route(&trip.route_id).unwrap();
+        let color = {
+            let color = route.route_color;
+            Rgb8::new(color.r, color.g, color.b)
+        };
+        let route_text_color = {
+            let color = route.route_text_color;
+            Rgb8::new(color.r, color.g, color.b)
+        };
+
+        let bg_color = theme.route_background(color);
+        let font = theme.typeface();
+        let text_color = theme.text_color(color, bg_color);
+
+        let first = &trip.stop_times.first().unwrap().stop;
+        let last = &trip.stop_times.last().unwrap().stop;
+        let destination = trip.trip_headsign.as_deref().unwrap_or(&last.name);
+
+        let labels = vec![Label {
+            text: format!("{} ► {}", first.name, destination),
+            x: 0.,
+            y: -((CANVAS_HEIGHT / 2 - 50) as f32),
+            width: (CANVAS_WIDTH - 100) as f32,
+            font_size: 16,
+            justify: Justify::Left,
+            color: text_color,
+        }];
+
+        let font_size = 32;
+        let width = text_width(&font, &route.short_name, font_size as f32) + BADGE_PADDING * 2.;
+        let height = font_size as f32 * 1.25;
+        let right = (CANVAS_WIDTH - 100) as f32 / 2.;
+        let x = right - width / 2.;
+        let y = (CANVAS_HEIGHT / 2 - 50) as f32;
+
+        let badge = Badge {
+            x,
+            y,
+            width,
+            height,
+            radius: height * 0.2,
+            fill: color,
+            label: Label {
+                text: route.short_name.clone(),
+                x,
+                y,
+                width,
+                font_size,
+                justify: Justify::Center,
+                // route_text_color is meant to be read on route_color
+                color: theme.text_color(route_text_color, color),
+            },
+        };
+
+        let mut stops = trip
+            .stop_times
+            .iter()
+            .map(|st| {
+                let (x, y) = coordinate_to_xy(
+                    st.stop.longitude.unwrap(),
+                    st.stop.latitude.unwrap(),
+                    meta,
+                    camera,
+                );
+
+                PosterStop {
+                    x: x as f32,
+                    y: y as f32,
+                    transfer: stop_index.is_transfer(&st.stop, &route.id),
+                    label: None,
+                    leader: None,
+                }
+            })
+            .collect::<Vec<PosterStop>>();
+
+        let shape = match trip.shape_id.as_ref().and_then(|id| gtfs.shapes.get(id)) {
+            Some(shape) => shape
+                .iter()
+                .map(|s| {
+                    let (x, y) = coordinate_to_xy(s.longitude, s.latitude, meta, camera);
+                    (x as f32, y as f32)
+                })
+                .collect(),
+            None => stops.iter().map(|stop| (stop.x, stop.y)).collect(),
+        };
+
+        if stop_names {
+            let terminus = [0, stops.len() - 1];
+            let requests = trip
+                .stop_times
+                .iter()
+                .zip(stops.iter())
+                .enumerate()
+                .map(|(idx, (st, stop))| LabelRequest {
+                    x: stop.x,
+                    y: stop.y,
+                    width: text_width(&font, &st.stop.name, STOP_FONT_SIZE as f32),
+                    height: STOP_FONT_SIZE as f32 * 1.25,
+                    offset: theme.stop_radius + theme.line_weight,
+                    priority: if terminus.contains(&idx) {
+                        TERMINUS_PRIORITY
+                    } else {
+                        stop_index.count(&st.stop) as f32
+                    },
+                })
+                .collect::<Vec<LabelRequest>>();
+
+            let destination = &labels[0];
+            let mut obstacles = vec![
+                Rect::centered(badge.x, badge.y, badge.width, badge.height),
+                Rect {
+                    min_x: destination.x - destination.width / 2.,
+                    max_x: destination.x - destination.width / 2.
+                        + text_width(&font, &destination.text, destination.font_size as f32),
+                    ..Rect::centered(0., destination.y, 0., destination.font_size as f32 * 1.25)
+                },
+            ];
+            obstacles.extend(stops.iter().map(|stop| {
+                let size = (theme.stop_radius + theme.line_weight) * 2.;
+                Rect::centered(stop.x, stop.y, size, size)
+            }));
+
+            let bounds =
+                Rect::centered(0., 0., CANVAS_WIDTH as f32 - 8., CANVAS_HEIGHT as f32 - 8.);
+            let placed = place(&requests, &obstacles, bounds, LABEL_STRATEGY);
+
+            for ((stop, st), placement) in stops.iter_mut().zip(trip.stop_times.iter()).zip(placed)
+            {
+                if let Some(Placement { rect, leader }) = placement {
+                    let (x, y) = rect.center();
+
+                    stop.leader = leader;
+                    stop.label = Some(Label {
+                        text: st.stop.name.clone(),
+                        x,
+                        y,
+                        width: rect.width(),
+                        font_size: STOP_FONT_SIZE,
+                        justify: Justify::Center,
+                        color: text_color,
+                    });
+                }
+            }
+        }
+
+        Self {
+            color,
+            bg_color,
+            labels,
+            badge,
+            stops,
+            shape,
+        }
+    }
+}
+
+pub fn random_trip(gtfs: &Gtfs, filter: &RouteFilter) -> Option<Arc<Trip>> {
+    let trips = gtfs
+        .trips
+        .values()
+        .filter(|trip| filter.keeps_trip(gtfs, trip))
+        .collect::<Vec<&Trip>>();
+
+    if trips.is_empty() {
+        return None;
+    }
+
+    let idx = rand::thread_rng().gen_range(0..trips.len());
+
+    Some(Arc::new(trips[idx].clone()))
+}
+
+// Trip id, theme name and whether stops are named
+type PosterKey = (String, String, bool);
 
 #[derive(Default)]
 pub struct TripsViz {
     pub history: Vec<Arc<Trip>>,
-    pub light: bool,
+    pub stop_names: bool,
+    // Layout of the last trip, only redone when it or the key changes since placing the
+    // stop labels is slow
+    poster: Option<(PosterKey, Poster)>,
 }
 
 impl TripsViz {
     pub fn new() -> Self {
         Self {
             history: vec![],
-            light: false,
+            stop_names: false,
+            poster: None,
         }
     }
-}
 
-impl Model<Box<TripsViz>> {
-    fn new_trip(&mut self) {
-        let mut rng = rand::thread_rng();
-        let idx = rng.gen_range(0..self.gtfs.trips.len());
-        let id = self.gtfs.trips.keys().nth(idx).unwrap();
-        let trip = Arc::new(self.gtfs.get_trip(id).unwrap().clone());
+    fn refresh_poster(&mut self, ctx: &Context) {
+        let trip = match self.history.last() {
+            Some(trip) => trip,
+            None => return,
+        };
 
-        self.meta = Meta::from_trip(&trip);
-        self.context.history.push(Arc::clone(&trip));
+        let key = (trip.id.clone(), ctx.theme.name.clone(), self.stop_names);
+        if self.poster.as_ref().map(|(k, _)| k) != Some(&key) {
+            let poster = Poster::new(
+                trip,
+                &ctx.gtfs,
+                &ctx.meta,
+                &ctx.index,
+                &ctx.theme,
+                self.stop_names,
+            );
+            self.poster = Some((key, poster));
+        }
     }
 
-    fn back(&mut self) {
-        self.context.history.pop();
-        self.meta = Meta::from_trip(self.context.history.last().unwrap());
+    fn show(&mut self, ctx: &mut Context, trip: Arc<Trip>) {
+        ctx.meta = Meta::from_trip(&trip);
+        ctx.camera.reset();
+        self.history.push(trip);
     }
-}
 
-impl Viz for TripsViz {
-    fn model(&self) -> ModelFn<Model<Box<Self>>> {
-        |app| {
-            app.set_loop_mode(LoopMode::Wait);
+    fn new_trip(&mut self, ctx: &mut Context) {
+        match random_trip(&ctx.gtfs, &ctx.filter) {
+            Some(trip) => self.show(ctx, trip),
+            None => eprintln!("No trips match the filter"),
+        }
+    }
+
+    fn back(&mut self, ctx: &mut Context) {
+        self.history.pop();
+        ctx.meta = Meta::from_trip(self.history.last().unwrap());
+        ctx.camera.reset();
+    }
+
+    // Random trip stopping at the station of `stop`, clicked on the current poster
+    fn trip_through(&mut self, ctx: &mut Context, stop: &Stop) {
+        let trips = ctx
+            .index
+            .trips(stop)
+            .iter()
+            .filter_map(|id| ctx.gtfs.trips.get(id))
+            .filter(|trip| ctx.filter.keeps_trip(&ctx.gtfs, trip))
+            .collect::<Vec<&Trip>>();
+
+        if trips.is_empty() {
+            return;
+        }
+
+        let idx = rand::thread_rng().gen_range(0..trips.len());
+        let trip = Arc::new(trips[idx].clone());
+
+        self.show(ctx, trip);
+    }
+
+    fn trip_details(&self, ctx: &Context, trip: &Trip) -> Vec<String> {
+        let route = ctx.gtfs.get_route(&trip.route_id).unwrap();
+
+        let mut lines = vec![
+            format!("{} · {:?}", route.short_name, route.route_type),
+            route.long_name.clone(),
+        ];
+
+        if let Some(headsign) = &trip.trip_headsign {
+            lines.push(format!("► {headsign}"));
+        }
 
-            let mut model = Model::from_url(GTFS_URL).context(|_| Box::new(Self::new()));
+        lines.push(format!("Trip #{}", trip.id));
+        lines.retain(|line| !line.is_empty());
+        lines
+    }
+}
 
-            model.new_trip();
+impl Viz for TripsViz {
+    // Shows the last trip again, or a first one
+    fn enter(&mut self, ctx: &mut Context) {
+        self.stop_names |= ctx.args.has("stop-names");
 
-            model
+        match self.history.last() {
+            Some(trip) => {
+                ctx.meta = Meta::from_trip(trip);
+                ctx.camera.reset();
+            }
+            None => self.new_trip(ctx),
         }
+
+        self.refresh_poster(ctx);
     }
 
-    fn update(&self) -> UpdateFn<Model<Box<Self>>> {
-        |_, _, _| {}
+    fn update(&mut self, ctx: &mut Context) {
+        self.refresh_poster(ctx);
     }
 
-    fn event(&self) -> EventFn<Model<Box<Self>>, Event> {
-        |app, model, event| match event {
-            Event::WindowEvent { simple, .. } => {
-                if let Some(event) = simple {
-                    match event {
-                        WindowEvent::KeyPressed(code) if code == Key::Back => {
-                            if model.context.history.len() > 1 {
-                                model.back();
-                            }
-                        }
-                        WindowEvent::ReceivedCharacter(c) => match c {
-                            ' ' => {
-                                model.new_trip();
-                            }
-                            'r' => {
-                                if let Some(trip) = model.context.history.last() {
-                                    let filename = format!("./export/trips/trip_{}.png", trip.id);
-                                    app.main_window().capture_frame(filename);
-                                }
-                            }
-                            'i' => {
-                                model.context.light = !model.context.light;
-                            }
-                            _ => (),
-                        },
-                        _ => (),
-                    }
+    fn event(&mut self, ctx: &mut Context, input: &Input) {
+        ctx.camera_event(input);
+
+        if ctx.filter_event(input) {
+            self.new_trip(ctx);
+        }
+
+        match input {
+            Input::MouseReleased(MouseButton::Left) if !ctx.camera.was_dragged() => {
+                let trip = match self.history.last() {
+                    Some(trip) => Arc::clone(trip),
+                    None => return,
+                };
+                let stops = trip.stop_times.iter().map(|st| &st.stop);
+
+                if let Some(stop) = ctx.hovered_stop(stops).cloned() {
+                    self.trip_through(ctx, &stop);
+                }
+            }
+            Input::Key(Key::Back) => {
+                if self.history.len() > 1 {
+                    self.back(ctx);
+                }
+            }
+            Input::Char(' ') => self.new_trip(ctx),
+            Input::Char('r') => {
+                if let Some(trip) = self.history.last() {
+                    ctx.save_frame(format!("{EXPORT_DIR}/trips/trip_{}.png", trip.id));
+                }
+            }
+            Input::Char('i') => ctx.theme = ctx.theme.next_preset(),
+            Input::Char('n') => self.stop_names = !self.stop_names,
+            Input::Char('g') => {
+                self.refresh_poster(ctx);
+
+                if let (Some(trip), Some((_, poster))) = (self.history.last(), &self.poster) {
+                    let filename = format!("{EXPORT_DIR}/trips/trip_{}.gif", trip.id);
+                    trip_gif(
+                        trip,
+                        poster,
+                        &ctx.gtfs,
+                        &ctx.theme,
+                        &ctx.overlays,
+                        &filename,
+                    )
+                    .unwrap_or_else(|e| eprintln!("Couldn't export GIF: {e}"));
                 }
             }
             _ => (),
         }
+
+        self.refresh_poster(ctx);
     }
 
-    fn view(&self) -> ViewFn<Model<Box<Self>>> {
-        |app, model, frame| {
-            let draw = app.draw();
-
-            let trip = model.context.history.last().unwrap();
-            let route = model.gtfs.get_route(&trip.route_id).unwrap();
-            let shape = model
-                .gtfs
-                .shapes
-                .get(trip.shape_id.as_ref().unwrap())
-                .unwrap();
-
-            let color = {
-                let color = route.route_color;
-                Rgb8::new(color.r, color.g, color.b)
-            };
+    fn draw(&self, ctx: &Context, draw: &Draw) {
+        let (trip, (_, poster)) = match (self.history.last(), &self.poster) {
+            (Some(trip), Some(poster)) => (trip, poster),
+            _ => {
+                draw.background().color(ctx.theme.background);
+                return ctx.draw_filter(draw);
+            }
+        };
 
-            // TODO nicer bg color calculation
-            let darken_amount = if !model.context.light { 6. } else { 0.02 };
-            let bg_color = Rgb8::new(
-                (color.red as f32 / darken_amount) as u8,
-                (color.green as f32 / darken_amount) as u8,
-                (color.blue as f32 / darken_amount) as u8,
-            );
+        let theme = &ctx.theme;
+        let Poster {
+            color,
+            bg_color,
+            labels,
+            badge,
+            stops,
+            shape,
+        } = poster;
+        let (color, bg_color) = (*color, *bg_color);
 
-            draw.background().color(bg_color);
+        let to_screen = |(x, y): (f32, f32)| {
+            let (x, y) = ctx.camera.apply(x as f64, y as f64);
+            (x as f32, y as f32)
+        };
+        let shape = shape
+            .iter()
+            .copied()
+            .map(to_screen)
+            .collect::<Vec<(f32, f32)>>();
 
-            let poli = shape.iter().map(|s| {
-                let (x, y) = coordinate_to_xy(s.longitude, s.latitude, &model.meta);
-                (pt2(x as f32, y as f32), color)
-            });
+        draw.background().color(bg_color);
+        ctx.draw_basemap(draw);
 
-            draw.polyline()
-                .weight(2.0)
-                .join_round()
-                .points_colored(poli);
+        let poli = shape.iter().map(|(x, y)| (pt2(*x, *y), color));
 
-            for st in trip.stop_times.iter() {
-                let (x, y) = coordinate_to_xy(
-                    st.stop.longitude.unwrap(),
-                    st.stop.latitude.unwrap(),
-                    &model.meta,
-                );
+        draw.polyline()
+            .weight(theme.line_weight)
+            .join_round()
+            .points_colored(poli);
 
+        for stop in stops.iter() {
+            let (x, y) = to_screen((stop.x, stop.y));
+
+            draw.ellipse()
+                .stroke(color)
+                .stroke_weight(theme.line_weight)
+                .color(bg_color)
+                .radius(theme.stop_radius)
+                .x_y(x, y);
+
+            if stop.transfer {
                 draw.ellipse()
-                    .stroke(color)
-                    .stroke_weight(2.)
-                    .color(bg_color)
-                    .radius(8.)
-                    .x_y(x as f32, y as f32);
+                    .color(color)
+                    .radius(theme.stop_radius / 2.)
+                    .x_y(x, y);
             }
+        }
+
+        draw.polygon()
+            .color(badge.fill)
+            .points(badge.outline().into_iter().map(|(x, y)| pt2(x, y)));
 
-            let first = &trip.stop_times.first().unwrap().stop;
-            let last = &trip.stop_times.last().unwrap().stop;
+        for (start, end) in stops.iter().filter_map(|stop| stop.leader) {
+            let (start, end) = (to_screen(start), to_screen(end));
 
-            draw.text(&format!("{} ► {}", last.name, first.name))
-                .x_y(0., -((CANVAS_HEIGHT / 2 - 50) as f32))
-                .color(color)
-                .font_size(16)
-                .w((CANVAS_WIDTH - 100) as f32)
-                .left_justify();
+            draw.line()
+                .start(pt2(start.0, start.1))
+                .end(pt2(end.0, end.1))
+                .weight(1.)
+                .color(color);
+        }
+
+        let fixed = labels
+            .iter()
+            .chain([&badge.label])
+            .map(|label| (label, label.x, label.y));
+        let stop_labels = stops
+            .iter()
+            .filter_map(|stop| stop.label.as_ref())
+            .map(|label| {
+                let (x, y) = to_screen((label.x, label.y));
+                (label, x, y)
+            });
 
-            draw.text(&route.short_name)
-                .x_y(0., (CANVAS_HEIGHT / 2 - 50) as f32)
-                .color(color)
-                .font_size(32)
-                .w((CANVAS_WIDTH - 100) as f32)
-                .right_justify();
+        for (label, x, y) in fixed.chain(stop_labels) {
+            let text = draw
+                .text(&label.text)
+                .x_y(x, y)
+                .color(label.color)
+                .font(theme.typeface())
+                .font_size(label.font_size)
+                .w(label.width);
 
-            draw.to_frame(app, &frame).unwrap();
+            match label.justify {
+                Justify::Left => text.left_justify(),
+                Justify::Center => text.center_justify(),
+                Justify::Right => text.right_justify(),
+            };
+        }
+
+        let legend = legend_entries(ctx.gtfs.routes.get(&trip.route_id).into_iter());
+        ctx.draw_overlays(draw, &legend, None);
+        ctx.draw_filter(draw);
+
+        let trip_stops = trip.stop_times.iter().map(|st| &st.stop);
+        let tooltip = if let Some(stop) = ctx.hovered_stop(trip_stops) {
+            Some(ctx.stop_details(stop))
+        } else if ctx.is_hovering_polyline(&shape) {
+            Some(self.trip_details(ctx, trip))
+        } else {
+            None
+        };
+
+        if let Some(lines) = tooltip {
+            let (x, y) = ctx.mouse;
+            draw_tooltip(draw, theme, x as f32, y as f32, &lines);
         }
     }
-}
 
-impl Vizualization for TripsViz {
-    fn run(&self) {
-        nannou::app(self.model())
-            .event(self.event())
-            .simple_window(self.view())
-            .size(CANVAS_WIDTH, CANVAS_HEIGHT)
-            .run()
+    // Posters only redraw on input
+    fn is_animating(&self, _: &Context) -> bool {
+        false
     }
 }